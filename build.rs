@@ -0,0 +1,37 @@
+//! Precompresses the text assets in `static/` into `$OUT_DIR/compressed`, as
+//! `name.br` and `name.gz`, so they can be embedded and served without
+//! compressing anything per request.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+// Images and icons are already compressed; only text formats are worth it
+const COMPRESSIBLE: &[&str] = &["js", "css", "html", "json", "svg", "txt"];
+
+fn main() -> std::io::Result<()> {
+    println!("cargo:rerun-if-changed=static");
+    let out = Path::new(&std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo")).join("compressed");
+    fs::create_dir_all(&out)?;
+
+    for entry in fs::read_dir("static")? {
+        let path = entry?.path();
+        let compressible = path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| COMPRESSIBLE.contains(&ext));
+        if !path.is_file() || !compressible {
+            continue;
+        }
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let data = fs::read(&path)?;
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        gz.write_all(&data)?;
+        fs::write(out.join(format!("{}.gz", name)), gz.finish()?)?;
+
+        let mut br = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+        br.write_all(&data)?;
+        fs::write(out.join(format!("{}.br", name)), br.into_inner())?;
+    }
+    Ok(())
+}
@@ -1,3 +1,4 @@
 rem Debug mode
 set RUST_LOG=spin=debug,bord=debug
+rem jwt_secret is required: set SPIN_VARIABLE_JWT_SECRET to 32+ random characters first
 spin watch
\ No newline at end of file
@@ -0,0 +1,324 @@
+use spin_sdk::http::{Request, Response};
+use crate::models::models::{User, Post, Role, Warning, InstanceSettings, FooterLink};
+use crate::core::helpers::{store, now_iso, hash_password};
+use crate::core::errors::ApiError;
+use crate::core::validate::parse_body;
+use crate::core::permissions::{authorize, Permission};
+use crate::branding::{is_valid_color, is_valid_link};
+use crate::users::{build_user_json, register_user, sanitize_text, system_account_id};
+use crate::posts::{publish_post, post_json, moderator_post_json, PostContent};
+use crate::templates::{invalidate_profile_page, invalidate_profile_page_for};
+use crate::auth::sweep_expired_tokens;
+use crate::core::feed_index;
+use crate::core::db::sweep_expired;
+use crate::config::*;
+
+fn set_post_hidden(req: Request, post_id: &str, hidden: bool) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ModeratePosts) {
+        return Ok(e.into());
+    }
+
+    let store = store()?;
+    let post_key = post_key(post_id);
+    match store.get_json::<Post>(&post_key)? {
+        Some(mut post) => {
+            post.hidden = hidden;
+            store.set_json(&post_key, &post)?;
+            invalidate_profile_page_for(&store, &post.user_id)?;
+            Ok(Response::builder()
+                .status(200)
+                .header("Content-Type", "application/json")
+                .body(serde_json::to_vec(&moderator_post_json(&post))?)
+                .build())
+        }
+        None => Ok(ApiError::NotFound("post_not_found", "Post not found".to_string()).into()),
+    }
+}
+
+pub fn hide_post(req: Request, post_id: &str) -> anyhow::Result<Response> {
+    set_post_hidden(req, post_id, true)
+}
+
+pub fn unhide_post(req: Request, post_id: &str) -> anyhow::Result<Response> {
+    set_post_hidden(req, post_id, false)
+}
+
+pub fn warn_user(req: Request, user_id: &str) -> anyhow::Result<Response> {
+    let moderator = match authorize(&req, Permission::WarnUsers) {
+        Ok(u) => u,
+        Err(e) => return Ok(e.into()),
+    };
+
+    let value: serde_json::Value = serde_json::from_slice(req.body())?;
+    let reason = value["reason"].as_str().unwrap_or_default().trim();
+    if reason.is_empty() {
+        return Ok(ApiError::BadRequest("reason_required", "Reason is required".to_string()).into());
+    }
+
+    let store = store()?;
+    if store.get_json::<User>(&user_key(user_id))?.is_none() {
+        return Ok(ApiError::NotFound("user_not_found", "User not found".to_string()).into());
+    }
+
+    let key = warnings_key(user_id);
+    let mut warnings: Vec<Warning> = store.get_json(&key)?.unwrap_or_default();
+    warnings.push(Warning {
+        moderator_id: moderator.id,
+        reason: reason.to_string(),
+        created_at: now_iso(),
+    });
+    store.set_json(&key, &warnings)?;
+
+    Ok(Response::builder()
+        .status(201)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&warnings)?)
+        .build())
+}
+
+pub fn list_warnings(req: Request, user_id: &str) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::WarnUsers) {
+        return Ok(e.into());
+    }
+
+    let warnings: Vec<Warning> = store()?.get_json(&warnings_key(user_id))?.unwrap_or_default();
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&warnings)?)
+        .build())
+}
+
+pub fn set_user_role(req: Request, user_id: &str) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ManageAccounts) {
+        return Ok(e.into());
+    }
+
+    let value: serde_json::Value = serde_json::from_slice(req.body())?;
+    let role: Role = match serde_json::from_value(value["role"].clone()) {
+        Ok(r) => r,
+        Err(_) => return Ok(ApiError::BadRequest("invalid_role", "Role must be user, moderator or admin".to_string()).into()),
+    };
+
+    let store = store()?;
+    let user_key = user_key(user_id);
+    match store.get_json::<User>(&user_key)? {
+        Some(mut user) => {
+            user.role = role;
+            store.set_json(&user_key, &user)?;
+            let resp = serde_json::json!({
+                "id": user.id,
+                "username": user.username,
+                "role": user.role,
+            });
+            Ok(Response::builder()
+                .status(200)
+                .header("Content-Type", "application/json")
+                .body(serde_json::to_vec(&resp)?)
+                .build())
+        }
+        None => Ok(ApiError::NotFound("user_not_found", "User not found".to_string()).into()),
+    }
+}
+
+pub fn get_settings(req: Request) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ManageInstance) {
+        return Ok(e.into());
+    }
+
+    let settings: InstanceSettings = store()?.get_json(INSTANCE_SETTINGS_KEY)?.unwrap_or_default();
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&settings)?)
+        .build())
+}
+
+/// Partial update: only the fields present in the body change
+pub fn update_settings(req: Request) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ManageInstance) {
+        return Ok(e.into());
+    }
+
+    let value: serde_json::Value = serde_json::from_slice(req.body())?;
+    let store = store()?;
+    let mut settings: InstanceSettings = store.get_json(INSTANCE_SETTINGS_KEY)?.unwrap_or_default();
+
+    if let Some(enabled) = value.get("public_export_enabled") {
+        match enabled.as_bool() {
+            Some(enabled) => settings.public_export_enabled = enabled,
+            None => return Ok(ApiError::BadRequest("invalid_setting", "public_export_enabled must be a boolean".to_string()).into()),
+        }
+    }
+
+    // Branding: null clears an override back to the environment default
+    if let Some(name) = value.get("site_name") {
+        settings.site_name = name.as_str().map(str::trim).filter(|n| !n.is_empty()).map(String::from);
+    }
+    if let Some(url) = value.get("logo_url") {
+        match url.as_str() {
+            Some(url) if !url.is_empty() && !is_valid_link(url) => {
+                return Ok(ApiError::BadRequest("invalid_setting", "logo_url must be a relative or http(s) URL".to_string()).into());
+            }
+            url => settings.logo_url = url.map(String::from),
+        }
+    }
+    if let Some(color) = value.get("accent_color") {
+        match color.as_str() {
+            Some(color) if !is_valid_color(color) => {
+                return Ok(ApiError::BadRequest("invalid_setting", "accent_color must be a hex color like #209CEE".to_string()).into());
+            }
+            color => settings.accent_color = color.map(String::from),
+        }
+    }
+    if let Some(links) = value.get("footer_links") {
+        let links: Vec<FooterLink> = match serde_json::from_value(links.clone()) {
+            Ok(links) => links,
+            Err(_) => return Ok(ApiError::BadRequest("invalid_setting", "footer_links must be a list of {label, url}".to_string()).into()),
+        };
+        if links.iter().any(|link| !is_valid_link(&link.url)) {
+            return Ok(ApiError::BadRequest("invalid_setting", "Footer link URLs must be relative or http(s)".to_string()).into());
+        }
+        settings.footer_links = links;
+    }
+
+    settings.revision += 1;
+    store.set_json(INSTANCE_SETTINGS_KEY, &settings)?;
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&settings)?)
+        .build())
+}
+
+/// Create the system account if needed and update its bio.
+///
+/// The account has an unguessable password and its username is reserved, so
+/// it can't log in or be signed up for; this endpoint is the only way to manage it.
+pub fn upsert_system_account(req: Request) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ManageInstance) {
+        return Ok(e.into());
+    }
+
+    let store = store()?;
+    let value: serde_json::Value = if req.body().is_empty() {
+        serde_json::json!({})
+    } else {
+        serde_json::from_slice(req.body())?
+    };
+
+    let existing = match system_account_id(&store)? {
+        Some(id) => store.get_json::<User>(&user_key(&id))?,
+        None => None,
+    };
+
+    let (mut user, status) = match existing {
+        Some(user) => (user, 200),
+        None => {
+            let user = User {
+                id: uuid::Uuid::new_v4().to_string(),
+                username: SYSTEM_USERNAME.to_string(),
+                password: hash_password(&uuid::Uuid::new_v4().to_string())?,
+                bio: None,
+                created_at: Some(now_iso()),
+                role: Role::User,
+                timezone: None,
+                email: None,
+            };
+            register_user(&store, &user)?;
+            store.set_json(SYSTEM_ACCOUNT_KEY, &user.id)?;
+            (user, 201)
+        }
+    };
+
+    if let Some(bio) = value["bio"].as_str() {
+        if bio.len() > MAX_BIO_LENGTH {
+            return Ok(ApiError::BadRequest("bio_too_long", "Bio too long (max 500 chars)".to_string()).into());
+        }
+        let sanitized_bio = sanitize_text(bio);
+        user.bio = if sanitized_bio.is_empty() { None } else { Some(sanitized_bio) };
+        store.set_json(&user_key(&user.id), &user)?;
+        invalidate_profile_page(&store, &user.username)?;
+    }
+
+    Ok(Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&build_user_json(&store, &user)?)?)
+        .build())
+}
+
+/// Publish an announcement or other automated post as the system account
+pub fn create_system_post(req: Request) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ManageInstance) {
+        return Ok(e.into());
+    }
+
+    let store = store()?;
+    let system_id = match system_account_id(&store)? {
+        Some(id) => id,
+        None => return Ok(ApiError::NotFound("system_account_missing", "System account not created".to_string()).into()),
+    };
+
+    let body: PostContent = match parse_body(&req) {
+        Ok(body) => body,
+        Err(e) => return Ok(e.into()),
+    };
+
+    // Same pipeline as user posts, minus the new-account link gate
+    let post = publish_post(&store, &system_id, &body.content, None)?;
+
+    Ok(Response::builder()
+        .status(201)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&post_json(&post))?)
+        .build())
+}
+
+/// Remove expired sessions and denylist entries; meant to be called periodically
+pub fn cleanup_tokens(req: Request) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ManageInstance) {
+        return Ok(e.into());
+    }
+
+    let removed = sweep_expired_tokens(&store()?)?;
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&serde_json::json!({"removed": removed}))?)
+        .build())
+}
+
+/// Delete values written with a TTL once they have expired; meant to be called periodically
+pub fn sweep_expired_keys(req: Request) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ManageInstance) {
+        return Ok(e.into());
+    }
+
+    let removed = sweep_expired(&store()?)?;
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&serde_json::json!({"removed": removed}))?)
+        .build())
+}
+
+/// Fold the feed journal into its day buckets
+pub fn compact_feed(req: Request) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ManageInstance) {
+        return Ok(e.into());
+    }
+
+    let compacted = feed_index::compact(&store()?)?;
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&serde_json::json!({"compacted": compacted}))?)
+        .build())
+}
@@ -1,102 +1,613 @@
 use spin_sdk::http::{Request, Response};
+use serde::Deserialize;
+use crate::core::db::BordStore;
 use uuid::Uuid;
-use crate::models::models::{User, TokenData};
-use crate::config::{token_expiration_hours, USERS_LIST_KEY, TOKENS_LIST_KEY, user_key, token_key};
-use crate::core::helpers::{store, verify_password, validate_uuid, now_iso, unauthorized};
+use crate::models::models::{User, TokenData, SessionActivity, PasswordResetData, LoginFailures};
+use crate::config::*;
+use crate::core::helpers::{store, hash_password, verify_password, validate_uuid, now_iso, unauthorized, post_with_timeout};
+use crate::core::errors::{ApiError, FieldError};
+use crate::core::validate::{parse_body, Validate};
+use crate::core::jwt::{self, Claims};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use crate::core::db::{add_member, remove_member, members, delete_set};
+use crate::core::rate_limit::client_ip;
+use crate::users::{find_user_by_username, find_user_id_by_username, is_reserved_username, password_problem};
 
-pub fn login_user(req: Request) -> anyhow::Result<Response> {
-    let store = store();
-    let creds: serde_json::Value = serde_json::from_slice(req.body())?;
-    let username = creds["username"].as_str().unwrap_or_default();
-    let password = creds["password"].as_str().unwrap_or_default();
+/// Issue a signed access token for a user and record its session
+///
+/// The session record is bookkeeping for revocation only; validation of
+/// the token itself is stateless.
+pub fn issue_token(store: &dyn BordStore, user_id: &str, req: &Request) -> anyhow::Result<String> {
+    let jti = Uuid::new_v4().to_string();
+    let now = chrono::Utc::now();
+    let claims = Claims {
+        sub: user_id.to_string(),
+        jti: jti.clone(),
+        iat: now.timestamp(),
+        exp: now.timestamp() + token_expiration_hours() * 3600,
+    };
+    let secret = jwt_secret().ok_or_else(|| anyhow::anyhow!(JWT_SECRET_MISSING))?;
+    let token = jwt::encode(&claims, secret.as_bytes())?;
+
+    let data = TokenData {
+        user_id: user_id.to_string(),
+        created_at: now.to_rfc3339(),
+        last_used_at: Some(now.to_rfc3339()),
+        user_agent: req.header("User-Agent").and_then(|h| h.as_str()).map(|s| s.to_string()),
+        ip: Some(client_ip(req)),
+        last_ip: Some(client_ip(req)),
+    };
+    store.set_json(&token_key(&jti), &data)?;
+
+    add_member(store, TOKENS_LIST_KEY, &jti)?;
+    add_member(store, &user_tokens_set(user_id), &jti)?;
+
+    Ok(token)
+}
+
+/// Put a token ID on the denylist until the token would have expired anyway
+///
+/// The ID leaves the user's session set but stays in `tokens_list`, so the
+/// sweep can find the denylist entry later.
+fn revoke_jti(store: &dyn BordStore, user_id: &str, jti: &str, exp: i64) -> anyhow::Result<()> {
+    store.set_json(&revoked_token_key(jti), &exp)?;
+    store.delete(&token_key(jti))?;
+    store.delete(&session_activity_key(jti))?;
+    remove_member(store, &user_tokens_set(user_id), jti)?;
+    Ok(())
+}
+
+/// Drop sessions and denylist entries whose tokens have expired, along with
+/// their `tokens_list` entries. Returns how many were removed.
+pub fn sweep_expired_tokens(store: &dyn BordStore) -> anyhow::Result<usize> {
+    let now = chrono::Utc::now().timestamp();
+    let mut removed = 0;
+
+    for jti in members(store, TOKENS_LIST_KEY)? {
+        let session = store.get_json::<TokenData>(&token_key(&jti))?;
+        let expiry = match &session {
+            Some(data) => Some(session_expiry(data)),
+            // Revoked: the denylist entry holds the token's own expiry
+            None => store.get_json::<i64>(&revoked_token_key(&jti))?,
+        };
+
+        match expiry {
+            Some(exp) if exp > now => {}
+            _ => {
+                if let Some(data) = session {
+                    remove_member(store, &user_tokens_set(&data.user_id), &jti)?;
+                }
+                store.delete(&token_key(&jti))?;
+                store.delete(&session_activity_key(&jti))?;
+                store.delete(&revoked_token_key(&jti))?;
+                remove_member(store, TOKENS_LIST_KEY, &jti)?;
+                removed += 1;
+            }
+        }
+    }
+
+    Ok(removed)
+}
 
-    let users: Vec<String> = store.get_json(USERS_LIST_KEY)?.unwrap_or_default();
+/// Record that a session was just used. Sampled, and written to its own key
+/// without reading `token:{jti}`, so validating a token costs no extra read.
+fn touch_session(store: &dyn BordStore, jti: &str, req: &Request) -> anyhow::Result<()> {
+    if rand::random::<f64>() >= SESSION_TOUCH_SAMPLE_RATE {
+        return Ok(());
+    }
+    let activity = SessionActivity {
+        last_used_at: now_iso(),
+        last_ip: client_ip(req),
+    };
+    store.set_json(&session_activity_key(jti), &activity)
+}
 
-    for id in users {
-        if let Some(u) = store.get_json::<User>(&user_key(&id))? {
-            if u.id.is_empty() || !validate_uuid(&u.id) {
-                return Ok(unauthorized());
+/// When a session's token expires (unix seconds)
+fn session_expiry(data: &TokenData) -> i64 {
+    let issued = chrono::DateTime::parse_from_rfc3339(&data.created_at)
+        .map(|created| created.timestamp())
+        .unwrap_or_else(|_| chrono::Utc::now().timestamp());
+    issued + token_expiration_hours() * 3600
+}
+
+/// Active sessions of a user as (token ID, session data)
+pub fn user_sessions(store: &dyn BordStore, user_id: &str) -> anyhow::Result<Vec<(String, TokenData)>> {
+    let now = chrono::Utc::now().timestamp();
+    let mut sessions = Vec::new();
+
+    for jti in members(store, &user_tokens_set(user_id))? {
+        if let Ok(Some(mut token_data)) = store.get_json::<TokenData>(&token_key(&jti)) {
+            if token_data.user_id == user_id && session_expiry(&token_data) > now {
+                if let Some(activity) = store.get_json::<SessionActivity>(&session_activity_key(&jti))? {
+                    token_data.last_used_at = Some(activity.last_used_at);
+                    token_data.last_ip = Some(activity.last_ip);
+                }
+                sessions.push((jti, token_data));
             }
-            if u.username == username && verify_password(password, &u.password) {
-                let token = Uuid::new_v4().to_string();
-                let data = TokenData {
-                    user_id: u.id.clone(),
-                    created_at: now_iso(),
-                };
-                store.set_json(&token_key(&token), &data)?;
-                
-                // Track token in central list
-                let mut tokens: Vec<String> = store.get_json(TOKENS_LIST_KEY)?.unwrap_or_default();
-                tokens.push(token.clone());
-                store.set_json(TOKENS_LIST_KEY, &tokens)?;
-
-                let resp = serde_json::json!({
-                    "token": token,
-                    "user_id": u.id
-                });
-                return Ok(Response::builder()
-                    .status(200)
-                    .header("Content-Type", "application/json")
-                    .body(serde_json::to_vec(&resp)?)
-                    .build());
+        }
+    }
+
+    Ok(sessions)
+}
+
+/// CSRF token bound to one session: an HMAC of its token ID, so nothing extra is stored
+fn csrf_mac(jti: &str) -> Option<Hmac<Sha256>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(format!("csrf:{}", jwt_secret()?).as_bytes())
+        .expect("HMAC accepts any key length");
+    mac.update(jti.as_bytes());
+    Some(mac)
+}
+
+/// CSRF token to hand to a cookie-session client along with the session
+pub fn csrf_token(token: &str) -> Option<String> {
+    let claims = jwt::decode(token, jwt_secret()?.as_bytes())?;
+    Some(URL_SAFE_NO_PAD.encode(csrf_mac(&claims.jti)?.finalize().into_bytes()))
+}
+
+fn csrf_valid(req: &Request, jti: &str) -> bool {
+    req.header(CSRF_HEADER)
+        .and_then(|h| h.as_str())
+        .and_then(|presented| URL_SAFE_NO_PAD.decode(presented).ok())
+        .and_then(|presented| Some(csrf_mac(jti)?.verify_slice(&presented).is_ok()))
+        .unwrap_or(false)
+}
+
+/// `Set-Cookie` value carrying a session token
+pub fn session_cookie(token: &str) -> String {
+    format!(
+        "{}={}; Path=/; HttpOnly; Secure; SameSite=Strict; Max-Age={}",
+        SESSION_COOKIE_NAME,
+        token,
+        token_expiration_hours() * 3600
+    )
+}
+
+fn expired_session_cookie() -> String {
+    format!("{}=; Path=/; HttpOnly; Secure; SameSite=Strict; Max-Age=0", SESSION_COOKIE_NAME)
+}
+
+/// The request's session token: the bearer header wins, then the session cookie.
+/// The flag is true when it came from the cookie.
+fn request_token(req: &Request) -> Option<(String, bool)> {
+    if let Some(header) = req.header("Authorization").and_then(|h| h.as_str()) {
+        return header.strip_prefix("Bearer ").map(|t| (t.to_string(), false));
+    }
+
+    let cookies = req.header("Cookie")?.as_str()?;
+    cookies
+        .split(';')
+        .filter_map(|c| c.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE_NAME)
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(_, value)| (value.to_string(), true))
+}
+
+/// Whether the request is authenticated by the session cookie rather than a bearer header
+pub fn uses_session_cookie(req: &Request) -> bool {
+    matches!(request_token(req), Some((_, true)))
+}
+
+/// Cookies are sent by the browser automatically, so writes must also prove
+/// the caller could read the CSRF token
+fn needs_csrf(req: &Request, from_cookie: bool) -> bool {
+    from_cookie && !matches!(req.method().to_string().as_str(), "GET" | "HEAD" | "OPTIONS")
+}
+
+/// Revoke every active token belonging to a user
+pub fn revoke_user_tokens(store: &dyn BordStore, user_id: &str) -> anyhow::Result<()> {
+    for (jti, token_data) in user_sessions(store, user_id)? {
+        revoke_jti(store, user_id, &jti, session_expiry(&token_data))?;
+    }
+
+    Ok(())
+}
+
+/// Revoke a user's tokens and delete the sessions the sweep hasn't reached
+/// yet (account deletion). Denylist entries stay until their tokens expire.
+pub fn delete_user_sessions(store: &dyn BordStore, user_id: &str) -> anyhow::Result<()> {
+    revoke_user_tokens(store, user_id)?;
+    for jti in members(store, &user_tokens_set(user_id))? {
+        store.delete(&token_key(&jti))?;
+        store.delete(&session_activity_key(&jti))?;
+    }
+    delete_set(store, &user_tokens_set(user_id))
+}
+
+/// Seconds left on a login lockout for a subject (username or IP), if locked
+fn lockout_remaining(store: &dyn BordStore, subject: &str) -> anyhow::Result<Option<u64>> {
+    let now = chrono::Utc::now().timestamp();
+    Ok(store
+        .get_json_ttl::<LoginFailures>(&login_failures_key(subject))?
+        .and_then(|f| f.locked_until)
+        .filter(|until| *until > now)
+        .map(|until| (until - now) as u64))
+}
+
+/// Count a failed login, locking the subject once it hits `max_failures` within the window
+fn record_login_failure(store: &dyn BordStore, subject: &str, max_failures: u32) -> anyhow::Result<()> {
+    let key = login_failures_key(subject);
+    let now = chrono::Utc::now().timestamp();
+
+    let mut failures = store.get_json_ttl::<LoginFailures>(&key)?
+        .filter(|f| now - f.window_start < LOGIN_FAILURE_WINDOW_MINUTES * 60)
+        .unwrap_or(LoginFailures {
+            count: 0,
+            window_start: now,
+            locked_until: None,
+        });
+    failures.count += 1;
+    if failures.count >= max_failures {
+        failures.locked_until = Some(now + LOGIN_LOCKOUT_MINUTES * 60);
+    }
+    let ttl_minutes = LOGIN_FAILURE_WINDOW_MINUTES.max(LOGIN_LOCKOUT_MINUTES);
+    store.set_json_ttl(&key, &failures, ttl_minutes * 60)?;
+
+    Ok(())
+}
+
+/// Body of `POST /login`
+#[derive(Deserialize)]
+struct Credentials {
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    password: String,
+    /// Browser clients can opt into an HttpOnly cookie instead of keeping the token in JS
+    #[serde(default)]
+    cookie: bool,
+}
+
+impl Validate for Credentials {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut problems = Vec::new();
+        if self.username.is_empty() {
+            problems.push(FieldError::new("username", "username_required", "Username is required"));
+        }
+        if self.password.is_empty() {
+            problems.push(FieldError::new("password", "password_required", "Password is required"));
+        }
+        problems
+    }
+}
+
+pub fn login_user(req: Request) -> anyhow::Result<Response> {
+    let store = store()?;
+    let creds: Credentials = match parse_body(&req) {
+        Ok(body) => body,
+        Err(e) => return Ok(e.into()),
+    };
+    let username = creds.username.as_str();
+    let password = creds.password.as_str();
+
+    // Per-IP throttling is skipped on test instances, like the rate limiter
+    let ip_subject = format!("ip:{}", client_ip(&req));
+    let throttle_ip = !test_mode();
+    if throttle_ip {
+        if let Some(remaining) = lockout_remaining(&store, &ip_subject)? {
+            return Ok(ApiError::Locked(remaining).into());
+        }
+    }
+
+    // Keyed by the name tried, so unknown names lock and fail exactly like real ones
+    let user_subject = format!("user:{}", username);
+    if let Some(remaining) = lockout_remaining(&store, &user_subject)? {
+        return Ok(ApiError::Locked(remaining).into());
+    }
+
+    let user = match find_user_by_username(&store, username)? {
+        Some(u) if validate_uuid(&u.id) && verify_password(password, &u.password) => u,
+        _ => {
+            record_login_failure(&store, &user_subject, LOGIN_MAX_FAILURES)?;
+            if throttle_ip {
+                record_login_failure(&store, &ip_subject, LOGIN_MAX_FAILURES_PER_IP)?;
             }
+            return Ok(unauthorized());
         }
+    };
+
+    store.delete(&login_failures_key(&user_subject))?;
+    let token = issue_token(&store, &user.id, &req)?;
+
+    let mut resp = serde_json::json!({
+        "token": token,
+        "user_id": user.id
+    });
+
+    let mut builder = Response::builder();
+    if creds.cookie {
+        resp["csrf_token"] = csrf_token(&token).into();
+        builder.header("Set-Cookie", session_cookie(&token));
     }
 
-    Ok(unauthorized())
+    Ok(builder
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&resp)?)
+        .build())
 }
 
 pub fn logout_user(req: Request) -> anyhow::Result<Response> {
-    let store = store();
-    let auth_header = req.header("Authorization").and_then(|h| h.as_str()).unwrap_or_default();
+    let store = store()?;
+    let (token, from_cookie) = match request_token(&req) {
+        Some(t) => t,
+        None => return Ok(unauthorized()),
+    };
     
-    if !auth_header.starts_with("Bearer ") {
-        return Ok(unauthorized());
+    // Expired or forged tokens are already unusable, nothing to revoke
+    if let Some(claims) = jwt_secret().and_then(|secret| jwt::decode(&token, secret.as_bytes())) {
+        if needs_csrf(&req, from_cookie) && !csrf_valid(&req, &claims.jti) {
+            return Ok(ApiError::Forbidden.into());
+        }
+        revoke_jti(&store, &claims.sub, &claims.jti, claims.exp)?;
     }
     
-    let token = auth_header.strip_prefix("Bearer ").unwrap();
-    let key = token_key(token);
-    store.delete(&key)?;
-    
-    // Remove from central list
-    let mut tokens: Vec<String> = store.get_json(TOKENS_LIST_KEY)?.unwrap_or_default();
-    tokens.retain(|t| t != token);
-    store.set_json(TOKENS_LIST_KEY, &tokens)?;
-    
     let resp = serde_json::json!({
         "message": "Logged out successfully"
     });
-    Ok(Response::builder()
+    let mut builder = Response::builder();
+    if from_cookie {
+        builder.header("Set-Cookie", expired_session_cookie());
+    }
+    Ok(builder
         .status(200)
         .header("Content-Type", "application/json")
         .body(serde_json::to_vec(&resp)?)
         .build())
 }
 
-pub fn validate_token(req: &Request) -> Option<String> {
-    let store = store();
-    let auth_header = req.header("Authorization")?.as_str().unwrap_or_default();
-    if !auth_header.starts_with("Bearer ") {
+/// Verified claims of the request's session token (bearer header or cookie),
+/// if it is valid and not revoked
+fn bearer_claims(req: &Request) -> Option<Claims> {
+    let (token, from_cookie) = request_token(req)?;
+    
+    // Signature and expiry are checked without touching the store
+    let claims = jwt::decode(&token, jwt_secret()?.as_bytes())?;
+    if needs_csrf(req, from_cookie) && !csrf_valid(req, &claims.jti) {
         return None;
     }
-    let token = auth_header.strip_prefix("Bearer ").unwrap();
-    let key = token_key(token);
-    if let Some(data) = store.get_json::<TokenData>(&key).ok()? {
-        // Check if token is expired
-        if let Ok(created) = chrono::DateTime::parse_from_rfc3339(&data.created_at) {
-            let now = chrono::Utc::now();
-            let age_hours = (now - created.with_timezone(&chrono::Utc)).num_hours();
-            if age_hours > token_expiration_hours() {
-                return None;
-            }
+    
+    let store = store().ok()?;
+    if store.exists(&revoked_token_key(&claims.jti)).ok()? {
+        return None;
+    }
+    // Check if user still exists
+    let user_key = user_key(&claims.sub);
+    if store.get_json::<User>(&user_key).ok()?.is_none() {
+        return None;
+    }
+
+    // Bookkeeping only; a failed write must not reject a valid token
+    let _ = touch_session(&store, &claims.jti, req);
+    Some(claims)
+}
+
+pub fn validate_token(req: &Request) -> Option<String> {
+    bearer_claims(req).map(|claims| claims.sub)
+}
+
+pub fn list_sessions(req: Request) -> anyhow::Result<Response> {
+    let claims = match bearer_claims(&req) {
+        Some(c) => c,
+        None => return Ok(unauthorized()),
+    };
+
+    let store = store()?;
+    let sessions: Vec<serde_json::Value> = user_sessions(&store, &claims.sub)?
+        .into_iter()
+        .map(|(jti, data)| serde_json::json!({
+            "id": jti,
+            "created_at": data.created_at,
+            "last_used_at": data.last_used_at,
+            "user_agent": data.user_agent,
+            "ip": data.ip,
+            "last_ip": data.last_ip,
+            "current": jti == claims.jti,
+        }))
+        .collect();
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&sessions)?)
+        .build())
+}
+
+pub fn revoke_session(req: Request, session_id: &str) -> anyhow::Result<Response> {
+    let user_id = match validate_token(&req) {
+        Some(uid) => uid,
+        None => return Ok(unauthorized()),
+    };
+
+    let store = store()?;
+    match store.get_json::<TokenData>(&token_key(session_id))? {
+        Some(data) if data.user_id == user_id => {
+            revoke_jti(&store, &user_id, session_id, session_expiry(&data))?;
+            Ok(Response::builder().status(204).build())
         }
-        // Check if user still exists
-        let user_key = user_key(&data.user_id);
-        if store.get_json::<User>(&user_key).ok()?.is_none() {
-            return None;
+        _ => Ok(ApiError::NotFound("session_not_found", "Session not found".to_string()).into()),
+    }
+}
+
+pub fn logout_all(req: Request) -> anyhow::Result<Response> {
+    let user_id = match validate_token(&req) {
+        Some(uid) => uid,
+        None => return Ok(unauthorized()),
+    };
+
+    revoke_user_tokens(&store()?, &user_id)?;
+
+    let resp = serde_json::json!({
+        "message": "Logged out of all sessions"
+    });
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&resp)?)
+        .build())
+}
+
+/// Mail the token to the account's address through the relay at
+/// BORD_MAIL_RELAY_URL. The token itself is never logged; when it can't be
+/// sent, only the request is noted for the operator.
+fn deliver_reset_token(user: &User, token: &str) {
+    let (email, relay) = match (&user.email, mail_relay_url()) {
+        (Some(email), Some(relay)) => (email, relay),
+        (None, _) => return eprintln!("Password reset requested for '{}', who has no email address", user.username),
+        (_, None) => return eprintln!("Password reset requested for '{}', but BORD_MAIL_RELAY_URL is not set", user.username),
+    };
+
+    let message = serde_json::json!({
+        "to": email,
+        "subject": "Reset your Bord password",
+        "text": format!(
+            "Hi {},\n\nUse this token to reset your password within {} minutes:\n\n{}\n\nIf you didn't ask for a reset, ignore this message.\n",
+            user.username, PASSWORD_RESET_EXPIRATION_MINUTES, token
+        ),
+    });
+    let mut headers = vec![("content-type", "application/json".to_string())];
+    if let Some(relay_token) = mail_relay_token() {
+        headers.push(("authorization", format!("Bearer {}", relay_token)));
+    }
+
+    match post_with_timeout(&relay, &headers, message.to_string().as_bytes(), MAIL_RELAY_TIMEOUT_MS) {
+        Ok(200..=299) => {}
+        Ok(status) => eprintln!("Mail relay answered {} to the password reset for '{}'", status, user.username),
+        Err(e) => eprintln!("Mail relay failed for the password reset for '{}': {}", user.username, e),
+    }
+}
+
+/// Body of `POST /password/forgot`
+#[derive(Deserialize)]
+struct ResetRequest {
+    #[serde(default)]
+    username: String,
+}
+
+impl Validate for ResetRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        if self.username.is_empty() {
+            vec![FieldError::new("username", "username_required", "Username is required")]
+        } else {
+            Vec::new()
         }
-        Some(data.user_id)
-    } else {
+    }
+}
+
+pub fn forgot_password(req: Request) -> anyhow::Result<Response> {
+    let store = store()?;
+    let body: ResetRequest = match parse_body(&req) {
+        Ok(body) => body,
+        Err(e) => return Ok(e.into()),
+    };
+    let username = body.username.as_str();
+
+    let mut resp = serde_json::json!({
+        "message": "If the account exists, a reset token has been sent"
+    });
+
+    // The system account has no usable password and must stay that way
+    let user_id = if is_reserved_username(username) {
         None
+    } else {
+        find_user_id_by_username(&store, username)?
+    };
+    let user = match user_id {
+        Some(id) => store.get_json::<User>(&user_key(&id))?,
+        None => None,
+    };
+    if let Some(u) = user {
+        let token = Uuid::new_v4().to_string();
+        let data = PasswordResetData {
+            user_id: u.id.clone(),
+            created_at: now_iso(),
+        };
+        store.set_json_ttl(&reset_token_key(&token), &data, PASSWORD_RESET_EXPIRATION_MINUTES * 60)?;
+        deliver_reset_token(&u, &token);
+
+        // Test instances hand the token back so the flow can be exercised without mail
+        if test_mode() {
+            resp["reset_token"] = serde_json::Value::String(token);
+        }
     }
+
+    // Same response whether or not the user exists, to avoid username enumeration
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&resp)?)
+        .build())
+}
+
+/// Body of `POST /password/reset`
+#[derive(Deserialize)]
+struct PasswordReset {
+    #[serde(default)]
+    token: String,
+    #[serde(default)]
+    new_password: String,
+}
+
+impl Validate for PasswordReset {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut problems = Vec::new();
+        if !validate_uuid(&self.token) {
+            problems.push(FieldError::new("token", "invalid_reset_token", "Invalid reset token"));
+        }
+        problems.extend(password_problem("new_password", &self.new_password));
+        problems
+    }
+}
+
+pub fn reset_password(req: Request) -> anyhow::Result<Response> {
+    let store = store()?;
+    let body: PasswordReset = match parse_body(&req) {
+        Ok(body) => body,
+        Err(e) => return Ok(e.into()),
+    };
+    let token = body.token.as_str();
+    let new_password = body.new_password.as_str();
+
+    let key = reset_token_key(token);
+    let data = match store.get_json_ttl::<PasswordResetData>(&key)? {
+        Some(d) => d,
+        None => return Ok(ApiError::BadRequest("invalid_reset_token", "Invalid reset token".to_string()).into()),
+    };
+
+    // Single use: burn the token before doing anything else
+    store.delete(&key)?;
+
+    let expired = match chrono::DateTime::parse_from_rfc3339(&data.created_at) {
+        Ok(created) => {
+            let age = chrono::Utc::now() - created.with_timezone(&chrono::Utc);
+            age.num_minutes() > PASSWORD_RESET_EXPIRATION_MINUTES
+        }
+        Err(_) => true,
+    };
+    if expired {
+        return Ok(ApiError::BadRequest("reset_token_expired", "Reset token expired".to_string()).into());
+    }
+
+    let user_key = user_key(&data.user_id);
+    let mut user = match store.get_json::<User>(&user_key)? {
+        Some(u) => u,
+        None => return Ok(ApiError::NotFound("user_not_found", "User not found".to_string()).into()),
+    };
+
+    user.password = hash_password(new_password)?;
+    store.set_json(&user_key, &user)?;
+
+    // Existing sessions were opened with the old password
+    revoke_user_tokens(&store, &user.id)?;
+
+    let resp = serde_json::json!({
+        "message": "Password reset successfully"
+    });
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&resp)?)
+        .build())
 }
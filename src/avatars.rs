@@ -0,0 +1,105 @@
+//! Generated avatars. Nobody can upload one yet, so every account gets an
+//! identicon: a mirrored 5x5 pattern and a hue, both taken from the hash of
+//! the username, so the same user always looks the same.
+
+use spin_sdk::http::{Request, Response};
+use sha2::{Digest, Sha256};
+use crate::models::models::{User, Tombstone};
+use crate::core::helpers::store;
+use crate::core::errors::ApiError;
+use crate::core::etag::{weak_etag, none_match};
+use crate::config::*;
+
+/// Where a user's avatar is served
+pub fn avatar_url(user_id: &str) -> String {
+    format!("/avatars/{}", user_id)
+}
+
+/// SVG identicon for `seed`
+pub fn identicon_svg(seed: &str) -> String {
+    let hash = Sha256::digest(seed.as_bytes());
+    let hue = u16::from_be_bytes([hash[0], hash[1]]) % 360;
+
+    // The left half (with the middle column) comes from the hash bits after
+    // the hue, and is mirrored onto the right
+    let half = IDENTICON_GRID.div_ceil(2);
+    let cell = |x: usize, y: usize| format!(r#"<rect x="{}" y="{}" width="1" height="1"/>"#, x, y);
+    let mut cells = String::new();
+    for row in 0..IDENTICON_GRID {
+        for col in 0..half {
+            let bit = row * half + col;
+            if (hash[2 + bit / 8] >> (bit % 8)) & 1 == 0 {
+                continue;
+            }
+            cells.push_str(&cell(col, row));
+            let mirror = IDENTICON_GRID - 1 - col;
+            if mirror != col {
+                cells.push_str(&cell(mirror, row));
+            }
+        }
+    }
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-0.5 -0.5 {size} {size}" width="{px}" height="{px}" shape-rendering="crispEdges"><rect x="-0.5" y="-0.5" width="{size}" height="{size}" fill="#f0f0f0"/><g fill="hsl({hue}, 55%, 50%)">{cells}</g></svg>"##,
+        size = IDENTICON_GRID + 1,
+        px = IDENTICON_PIXELS,
+        hue = hue,
+        cells = cells,
+    )
+}
+
+/// `GET /avatars/{user_id}`: the user's identicon; deleted accounts share one
+pub fn serve_avatar(req: &Request, user_id: &str) -> anyhow::Result<Response> {
+    let store = store()?;
+    let username = match store.get_json::<User>(&user_key(user_id))? {
+        Some(user) => user.username,
+        None if store.get_json::<Tombstone>(&tombstone_key(user_id))?.is_some() => DELETED_USERNAME.to_string(),
+        None => return Ok(ApiError::NotFound("user_not_found", "User not found".to_string()).into()),
+    };
+
+    let svg = identicon_svg(&username);
+    let etag = weak_etag(svg.as_bytes());
+    let mut builder = Response::builder();
+    builder
+        .header("ETag", etag.as_str())
+        .header("Cache-Control", AVATAR_CACHE_CONTROL);
+
+    let fresh = req.header("If-None-Match")
+        .and_then(|h| h.as_str())
+        .is_some_and(|header| none_match(header, &etag));
+    if fresh {
+        return Ok(builder.status(304).build());
+    }
+
+    Ok(builder
+        .status(200)
+        .header("Content-Type", "image/svg+xml")
+        // Opened directly, an SVG is a document; this one never needs to run anything
+        .header("Content-Security-Policy", "default-src 'none'; style-src 'unsafe-inline'")
+        .body(svg.into_bytes())
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identicons_are_stable_and_symmetric() {
+        let alice = identicon_svg("alice");
+        assert_eq!(alice, identicon_svg("alice"));
+        assert_ne!(alice, identicon_svg("bob"));
+        assert!(alice.starts_with("<svg") && alice.ends_with("</svg>"));
+
+        let cells: Vec<(usize, usize)> = alice.split(r#"<rect x=""#).skip(2)
+            .map(|rect| {
+                let (x, rest) = rect.split_once('"').unwrap();
+                let y = rest.split('"').nth(1).unwrap();
+                (x.parse().unwrap(), y.parse().unwrap())
+            })
+            .collect();
+        for &(x, y) in &cells {
+            assert!(cells.contains(&(IDENTICON_GRID - 1 - x, y)), "({}, {}) has no mirror", x, y);
+        }
+    }
+}
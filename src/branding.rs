@@ -0,0 +1,57 @@
+use spin_sdk::http::Response;
+use crate::core::db::BordStore;
+use regex::Regex;
+use std::sync::OnceLock;
+use crate::models::models::{InstanceSettings, FooterLink};
+use crate::core::helpers::store;
+use crate::config::*;
+
+/// Site name, logo, accent color and footer links as rendered into pages
+#[derive(serde::Serialize)]
+pub struct Branding {
+    pub site_name: String,
+    pub logo_url: String,
+    pub accent_color: String,
+    pub footer_links: Vec<FooterLink>,
+}
+
+fn color_regex() -> &'static Regex {
+    static COLOR_REGEX: OnceLock<Regex> = OnceLock::new();
+    COLOR_REGEX.get_or_init(|| Regex::new(r"^#(?:[0-9a-fA-F]{3}|[0-9a-fA-F]{6})$").unwrap())
+}
+
+/// Accent colors end up in a `<style>` block, so only hex colors are allowed
+pub fn is_valid_color(color: &str) -> bool {
+    color_regex().is_match(color)
+}
+
+/// Logos and footer links must be site-relative or http(s)
+pub fn is_valid_link(url: &str) -> bool {
+    (url.starts_with('/') && !url.starts_with("//"))
+        || url.starts_with("https://")
+        || url.starts_with("http://")
+}
+
+/// Environment defaults with the admin's KV overrides applied on top
+pub fn current_branding(store: &dyn BordStore) -> anyhow::Result<Branding> {
+    let settings: InstanceSettings = store.get_json(INSTANCE_SETTINGS_KEY)?.unwrap_or_default();
+    let accent = settings.accent_color.unwrap_or_else(accent_color);
+
+    Ok(Branding {
+        site_name: settings.site_name.unwrap_or_else(site_name),
+        logo_url: settings.logo_url.unwrap_or_else(logo_url),
+        accent_color: if is_valid_color(&accent) { accent } else { DEFAULT_ACCENT_COLOR.to_string() },
+        footer_links: settings.footer_links,
+    })
+}
+
+pub fn get_instance() -> anyhow::Result<Response> {
+    let branding = current_branding(&store()?)?;
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .header("Cache-Control", "no-cache")
+        .body(serde_json::to_vec(&branding)?)
+        .build())
+}
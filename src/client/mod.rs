@@ -0,0 +1,455 @@
+//! Typed HTTP client for the Bord API (`/api/v1`)
+//!
+//! Built with the `client` feature on non-wasm targets. Used by the test
+//! suites and usable by third-party bots:
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), bord::client::ClientError> {
+//! let mut bord = bord::client::BordClient::new("http://127.0.0.1:3000");
+//! bord.login("alice", "alice").await?;
+//! bord.create_post("Hello from a bot").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod types;
+
+use reqwest::{Method, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::config::API_PREFIX;
+pub use types::*;
+
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request never got a response (connection refused, invalid body, ...)
+    Http(reqwest::Error),
+    /// The server answered with a non-success status
+    Api { status: u16, body: serde_json::Value },
+}
+
+impl ClientError {
+    /// HTTP status of an API error
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ClientError::Http(e) => e.status().map(|s| s.as_u16()),
+            ClientError::Api { status, .. } => Some(*status),
+        }
+    }
+
+    /// Machine-readable `code` from the server's problem+json body, e.g. `post_not_found`
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            ClientError::Http(_) => None,
+            ClientError::Api { body, .. } => body["code"].as_str(),
+        }
+    }
+
+    /// Per-field codes of a `validation_failed` error, in the order the server reported them
+    pub fn field_codes(&self) -> Vec<&str> {
+        match self {
+            ClientError::Http(_) => Vec::new(),
+            ClientError::Api { body, .. } => body["errors"]
+                .as_array()
+                .map(|errors| errors.iter().filter_map(|e| e["code"].as_str()).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ClientError::Http(e) => write!(f, "HTTP error: {}", e),
+            ClientError::Api { status, body } => write!(f, "API error {}: {}", status, body),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        ClientError::Http(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
+
+pub struct BordClient {
+    base_url: String,
+    http: reqwest::Client,
+    token: Option<String>,
+}
+
+impl BordClient {
+    pub fn new(base_url: &str) -> Self {
+        BordClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            token: None,
+        }
+    }
+
+    /// Bearer token sent with every request, set by `login`
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    pub fn set_token(&mut self, token: Option<String>) {
+        self.token = token;
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let builder = self.http.request(method, format!("{}{}{}", self.base_url, API_PREFIX, path));
+        match &self.token {
+            Some(token) => builder.bearer_auth(token),
+            None => builder,
+        }
+    }
+
+    async fn check(builder: RequestBuilder) -> Result<reqwest::Response> {
+        let resp = builder.send().await?;
+        if resp.status().is_success() {
+            return Ok(resp);
+        }
+        let status = resp.status().as_u16();
+        let body = resp.json().await.unwrap_or(serde_json::Value::Null);
+        Err(ClientError::Api { status, body })
+    }
+
+    async fn send<T: DeserializeOwned>(builder: RequestBuilder) -> Result<T> {
+        Ok(Self::check(builder).await?.json().await?)
+    }
+
+    async fn send_empty(builder: RequestBuilder) -> Result<()> {
+        Self::check(builder).await?;
+        Ok(())
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        Self::send(self.request(Method::GET, path)).await
+    }
+
+    async fn post<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T> {
+        Self::send(self.request(Method::POST, path).json(body)).await
+    }
+
+    async fn put<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T> {
+        Self::send(self.request(Method::PUT, path).json(body)).await
+    }
+
+    // === Accounts and sessions ===
+
+    pub async fn create_user(&self, username: &str, password: &str) -> Result<CreatedUser> {
+        self.post("/users", &NewUser {
+            username: username.to_string(),
+            password: password.to_string(),
+        }).await
+    }
+
+    /// Check a username against the registration rules without signing up
+    pub async fn username_available(&self, username: &str) -> Result<UsernameAvailability> {
+        self.get(&format!("/users/available?username={}", urlencoding::encode(username))).await
+    }
+
+    /// Log in and keep the token for subsequent requests
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<LoginResponse> {
+        let resp: LoginResponse = self.post("/login", &Credentials {
+            username: username.to_string(),
+            password: password.to_string(),
+        }).await?;
+        self.token = Some(resp.token.clone());
+        Ok(resp)
+    }
+
+    pub async fn logout(&mut self) -> Result<Message> {
+        let resp = Self::send(self.request(Method::POST, "/logout")).await?;
+        self.token = None;
+        Ok(resp)
+    }
+
+    pub async fn logout_all(&mut self) -> Result<Message> {
+        let resp = Self::send(self.request(Method::POST, "/logout-all")).await?;
+        self.token = None;
+        Ok(resp)
+    }
+
+    pub async fn sessions(&self) -> Result<Vec<Session>> {
+        self.get("/sessions").await
+    }
+
+    pub async fn revoke_session(&self, session_id: &str) -> Result<()> {
+        Self::send_empty(self.request(Method::DELETE, &format!("/sessions/{}", session_id))).await
+    }
+
+    pub async fn forgot_password(&self, username: &str) -> Result<ForgotPasswordResponse> {
+        self.post("/password/forgot", &serde_json::json!({ "username": username })).await
+    }
+
+    pub async fn reset_password(&self, token: &str, new_password: &str) -> Result<Message> {
+        self.post("/password/reset", &PasswordReset {
+            token: token.to_string(),
+            new_password: new_password.to_string(),
+        }).await
+    }
+
+    // === Profiles and users ===
+
+    pub async fn profile(&self) -> Result<UserDetails> {
+        self.get("/profile").await
+    }
+
+    /// Update bio and/or password; a password change swaps in the newly issued token
+    pub async fn update_profile(&mut self, update: &ProfileUpdate) -> Result<UserDetails> {
+        let details: UserDetails = self.put("/profile", update).await?;
+        if let Some(token) = &details.token {
+            self.token = Some(token.clone());
+        }
+        Ok(details)
+    }
+
+    pub async fn export_data(&self) -> Result<DataExport> {
+        self.get("/profile/export").await
+    }
+
+    pub async fn delete_account(&mut self, password: &str) -> Result<()> {
+        let builder = self.request(Method::DELETE, "/profile")
+            .json(&serde_json::json!({ "password": password }));
+        Self::send_empty(builder).await?;
+        self.token = None;
+        Ok(())
+    }
+
+    pub async fn user(&self, user_id: &str) -> Result<UserDetails> {
+        self.get(&format!("/users/{}", user_id)).await
+    }
+
+    // === Posts ===
+
+    pub async fn create_post(&self, content: &str) -> Result<Post> {
+        self.post("/posts", &PostContent { content: content.to_string() }).await
+    }
+
+    pub async fn list_posts(&self, query: &PostQuery) -> Result<Vec<Post>> {
+        let mut params = Vec::new();
+        if let Some(user) = &query.user {
+            params.push(format!("user={}", urlencoding::encode(user)));
+        }
+        if query.all {
+            params.push("all=true".to_string());
+        }
+        if let Some(page) = query.page {
+            params.push(format!("page={}", page));
+        }
+        let path = if params.is_empty() { "/posts".to_string() } else { format!("/posts?{}", params.join("&")) };
+        self.get(&path).await
+    }
+
+    pub async fn edit_post(&self, post_id: &str, content: &str) -> Result<Post> {
+        self.put(&format!("/posts/{}", post_id), &PostContent { content: content.to_string() }).await
+    }
+
+    pub async fn delete_post(&self, post_id: &str) -> Result<()> {
+        Self::send_empty(self.request(Method::DELETE, &format!("/posts/{}", post_id))).await
+    }
+
+    /// The caller's posts held for moderator review, including rejected ones
+    pub async fn held_posts(&self) -> Result<Vec<HeldPost>> {
+        self.get("/posts/held").await
+    }
+
+    pub async fn feed(&self, page: usize) -> Result<Vec<Post>> {
+        self.get(&format!("/feed?page={}", page)).await
+    }
+
+    pub async fn record_impressions(&self, post_ids: &[String]) -> Result<ImpressionsRecorded> {
+        self.post("/impressions", &Impressions { post_ids: post_ids.to_vec() }).await
+    }
+
+    pub async fn export_public_posts(&self, cursor: Option<&str>) -> Result<PublicPostsPage> {
+        let path = match cursor {
+            Some(cursor) => format!("/export/public-posts?cursor={}", urlencoding::encode(cursor)),
+            None => "/export/public-posts".to_string(),
+        };
+        self.get(&path).await
+    }
+
+    // === Follows ===
+
+    pub async fn follow(&self, target_user_id: &str) -> Result<FollowStatus> {
+        self.post("/follow", &FollowTarget {
+            target_user_id: Some(target_user_id.to_string()),
+            target_username: None,
+        }).await
+    }
+
+    pub async fn follow_username(&self, username: &str) -> Result<FollowStatus> {
+        self.post("/follow", &FollowTarget {
+            target_user_id: None,
+            target_username: Some(username.to_string()),
+        }).await
+    }
+
+    pub async fn unfollow(&self, target_user_id: &str) -> Result<FollowStatus> {
+        self.post("/unfollow", &FollowTarget {
+            target_user_id: Some(target_user_id.to_string()),
+            target_username: None,
+        }).await
+    }
+
+    pub async fn follow_batch(&self, target_user_ids: &[String]) -> Result<FollowBatchResult> {
+        self.post("/follow/batch", &FollowBatch {
+            target_user_ids: target_user_ids.to_vec(),
+            action: "follow".to_string(),
+        }).await
+    }
+
+    pub async fn unfollow_batch(&self, target_user_ids: &[String]) -> Result<FollowBatchResult> {
+        self.post("/follow/batch", &FollowBatch {
+            target_user_ids: target_user_ids.to_vec(),
+            action: "unfollow".to_string(),
+        }).await
+    }
+
+    pub async fn followings(&self, user_id: &str) -> Result<Vec<String>> {
+        self.get(&format!("/followings/{}", user_id)).await
+    }
+
+    pub async fn followers(&self, user_id: &str) -> Result<Vec<String>> {
+        self.get(&format!("/followers/{}", user_id)).await
+    }
+
+    pub async fn followings_expanded(&self, user_id: &str, page: usize) -> Result<Vec<UserSummary>> {
+        self.get(&format!("/followings/{}?expand=true&page={}", user_id, page)).await
+    }
+
+    pub async fn followers_expanded(&self, user_id: &str, page: usize) -> Result<Vec<UserSummary>> {
+        self.get(&format!("/followers/{}?expand=true&page={}", user_id, page)).await
+    }
+
+    pub async fn friends(&self) -> Result<Vec<String>> {
+        self.get("/friends").await
+    }
+
+    pub async fn friends_expanded(&self, page: usize) -> Result<Vec<UserSummary>> {
+        self.get(&format!("/friends?expand=true&page={}", page)).await
+    }
+
+    // === Instance ===
+
+    pub async fn client_config(&self) -> Result<ClientConfig> {
+        self.get("/client-config").await
+    }
+
+    pub async fn instance(&self) -> Result<Instance> {
+        self.get("/instance").await
+    }
+
+    /// The message catalog for `locale`, or the server's default when it has none
+    pub async fn messages(&self, locale: &str) -> Result<MessageCatalog> {
+        self.get(&format!("/i18n?locale={}", urlencoding::encode(locale))).await
+    }
+
+    // === Moderation and admin ===
+
+    pub async fn hide_post(&self, post_id: &str) -> Result<Post> {
+        Self::send(self.request(Method::POST, &format!("/admin/posts/{}/hide", post_id))).await
+    }
+
+    pub async fn unhide_post(&self, post_id: &str) -> Result<Post> {
+        Self::send(self.request(Method::POST, &format!("/admin/posts/{}/unhide", post_id))).await
+    }
+
+    pub async fn moderation_queue(&self) -> Result<Vec<HeldPost>> {
+        self.get("/admin/moderation/queue").await
+    }
+
+    /// Publish a held post; returns the post as published
+    pub async fn approve_held_post(&self, held_id: &str) -> Result<Post> {
+        Self::send(self.request(Method::POST, &format!("/admin/moderation/{}/approve", held_id))).await
+    }
+
+    pub async fn reject_held_post(&self, held_id: &str, reason: Option<&str>) -> Result<HeldPost> {
+        self.post(&format!("/admin/moderation/{}/reject", held_id), &serde_json::json!({ "reason": reason })).await
+    }
+
+    pub async fn warn_user(&self, user_id: &str, reason: &str) -> Result<Vec<Warning>> {
+        self.post(&format!("/admin/users/{}/warn", user_id), &serde_json::json!({ "reason": reason })).await
+    }
+
+    pub async fn warnings(&self, user_id: &str) -> Result<Vec<Warning>> {
+        self.get(&format!("/admin/users/{}/warnings", user_id)).await
+    }
+
+    pub async fn set_role(&self, user_id: &str, role: Role) -> Result<RoleChange> {
+        self.put(&format!("/admin/users/{}/role", user_id), &serde_json::json!({ "role": role })).await
+    }
+
+    pub async fn settings(&self) -> Result<InstanceSettings> {
+        self.get("/admin/settings").await
+    }
+
+    pub async fn update_settings(&self, update: &SettingsUpdate) -> Result<InstanceSettings> {
+        self.put("/admin/settings", update).await
+    }
+
+    /// Sweep expired sessions; returns how many were removed
+    pub async fn cleanup_tokens(&self) -> Result<TokenCleanup> {
+        self.post("/admin/maintenance/tokens", &serde_json::json!({})).await
+    }
+
+    /// Delete expired lockouts, rate-limit buckets, reset tokens and OAuth states
+    pub async fn sweep_expired(&self) -> Result<ExpiredSweep> {
+        self.post("/admin/maintenance/expired", &serde_json::json!({})).await
+    }
+
+    /// Fold recently published posts into the feed's day buckets
+    pub async fn compact_feed(&self) -> Result<FeedCompaction> {
+        self.post("/admin/maintenance/feed", &serde_json::json!({})).await
+    }
+
+    /// Create the system account, or update its bio if it exists
+    /// Register a webhook; the response carries its signing secret, shown only this once
+    pub async fn create_webhook(&self, url: &str, events: &[&str]) -> Result<Webhook> {
+        self.post("/admin/webhooks", &serde_json::json!({ "url": url, "events": events })).await
+    }
+
+    pub async fn webhooks(&self) -> Result<Vec<Webhook>> {
+        self.get("/admin/webhooks").await
+    }
+
+    pub async fn delete_webhook(&self, webhook_id: &str) -> Result<()> {
+        Self::send_empty(self.request(Method::DELETE, &format!("/admin/webhooks/{}", webhook_id))).await
+    }
+
+    /// Retry failed webhook deliveries whose backoff has passed
+    pub async fn retry_webhooks(&self) -> Result<WebhookRetry> {
+        self.post("/admin/maintenance/webhooks", &serde_json::json!({})).await
+    }
+
+    pub async fn upsert_system_account(&self, bio: Option<&str>) -> Result<UserDetails> {
+        self.put("/admin/system-account", &serde_json::json!({ "bio": bio })).await
+    }
+
+    pub async fn create_system_post(&self, content: &str) -> Result<Post> {
+        self.post("/admin/system-account/posts", &PostContent {
+            content: content.to_string(),
+        }).await
+    }
+}
+
+/// Status of a failed call, for asserting on expected errors in tests
+pub fn error_status<T>(result: &Result<T>) -> Option<u16> {
+    result.as_ref().err().and_then(ClientError::status)
+}
+
+/// Error code of a failed call, see `ClientError::code`
+pub fn error_code<T>(result: &Result<T>) -> Option<&str> {
+    result.as_ref().err().and_then(ClientError::code)
+}
+
+/// Field codes of a failed call, see `ClientError::field_codes`
+pub fn field_codes<T>(result: &Result<T>) -> Vec<&str> {
+    result.as_ref().err().map(ClientError::field_codes).unwrap_or_default()
+}
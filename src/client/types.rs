@@ -0,0 +1,409 @@
+//! Request and response bodies for the Bord HTTP API, as seen by a client
+
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Moderator,
+    Admin,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct NewUser {
+    pub username: String,
+    pub password: String,
+}
+
+/// Returned by `GET /users/available`
+#[derive(Deserialize, Debug, Clone)]
+pub struct UsernameAvailability {
+    pub username: String,
+    pub available: bool,
+    /// Why the name can't be registered, when it can't
+    pub reason: Option<String>,
+}
+
+/// Returned by `POST /users`
+#[derive(Deserialize, Debug, Clone)]
+pub struct CreatedUser {
+    pub id: String,
+    pub username: String,
+    #[serde(default)]
+    pub bio: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LoginResponse {
+    pub token: String,
+    pub user_id: String,
+    /// Only for cookie-session logins
+    #[serde(default)]
+    pub csrf_token: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Message {
+    pub message: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Session {
+    pub id: String,
+    pub created_at: String,
+    #[serde(default)]
+    pub last_used_at: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub ip: Option<String>,
+    #[serde(default)]
+    pub last_ip: Option<String>,
+    pub current: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ForgotPasswordResponse {
+    pub message: String,
+    /// Only returned by dev (perf) builds
+    #[serde(default)]
+    pub reset_token: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PasswordReset {
+    pub token: String,
+    pub new_password: String,
+}
+
+/// Returned by `/profile` and `/users/{id}`
+#[derive(Deserialize, Debug, Clone)]
+pub struct UserDetails {
+    pub id: String,
+    pub username: String,
+    #[serde(default)]
+    pub bio: String,
+    #[serde(default)]
+    pub role: Option<Role>,
+    #[serde(default)]
+    pub avatar_url: String,
+    #[serde(default)]
+    pub follower_count: u64,
+    #[serde(default)]
+    pub following_count: u64,
+    /// Set for accounts that have been deleted
+    #[serde(default)]
+    pub deleted: bool,
+    /// Relationship to the caller, on `/users/{id}` when logged in
+    #[serde(default)]
+    pub is_following: Option<bool>,
+    #[serde(default)]
+    pub follows_you: Option<bool>,
+    /// Only on the caller's own profile
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// New token issued after a password change
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ProfileUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bio: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_password: Option<String>,
+    /// IANA time zone name; an empty string resets to UTC
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Post {
+    pub id: String,
+    pub user_id: String,
+    pub content: String,
+    pub created_at: String,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    #[serde(default)]
+    pub author_avatar_url: String,
+    /// Only returned to moderators
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moderation: Option<ModerationScores>,
+}
+
+/// What the moderation filter reported about a post
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ModerationScores {
+    #[serde(default)]
+    pub sentiment: Option<f64>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PostContent {
+    pub content: String,
+}
+
+/// Filters for `GET /posts`; with neither `user` nor `all`, lists the caller's own posts
+#[derive(Debug, Clone, Default)]
+pub struct PostQuery {
+    pub user: Option<String>,
+    pub all: bool,
+    pub page: Option<usize>,
+}
+
+/// Either a user ID or a username to follow
+#[derive(Serialize, Debug, Clone)]
+pub struct FollowTarget {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_user_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_username: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FollowStatus {
+    pub status: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FollowBatch {
+    pub target_user_ids: Vec<String>,
+    /// "follow" or "unfollow"
+    pub action: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FollowBatchResult {
+    pub results: Vec<FollowBatchItem>,
+}
+
+/// Outcome for one target: followed, unfollowed, invalid, not_found or forbidden
+#[derive(Deserialize, Debug, Clone)]
+pub struct FollowBatchItem {
+    pub target_user_id: String,
+    pub status: String,
+}
+
+/// Entry of an expanded (`?expand=true`) follower/following list
+#[derive(Deserialize, Debug, Clone)]
+pub struct UserSummary {
+    pub id: String,
+    pub username: String,
+    #[serde(default)]
+    pub bio: String,
+    #[serde(default)]
+    pub avatar_url: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Impressions {
+    pub post_ids: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ImpressionsRecorded {
+    pub recorded: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClientFeatures {
+    #[serde(default)]
+    pub oauth_providers: Vec<String>,
+    #[serde(default)]
+    pub password_reset: bool,
+}
+
+/// Returned by `GET /client-config`
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClientConfig {
+    pub posts_per_page: usize,
+    pub users_per_page: usize,
+    pub max_post_length: usize,
+    pub max_bio_length: usize,
+    pub min_username_length: usize,
+    pub max_username_length: usize,
+    pub min_password_length: usize,
+    pub features: ClientFeatures,
+    #[serde(default)]
+    pub announcement: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FooterLink {
+    pub label: String,
+    pub url: String,
+}
+
+/// Returned by `GET /instance`
+#[derive(Deserialize, Debug, Clone)]
+pub struct Instance {
+    pub site_name: String,
+    pub logo_url: String,
+    pub accent_color: String,
+    #[serde(default)]
+    pub footer_links: Vec<FooterLink>,
+}
+
+/// Returned by `GET /i18n`
+#[derive(Deserialize, Debug, Clone)]
+pub struct MessageCatalog {
+    pub locale: String,
+    pub locales: Vec<String>,
+    pub messages: std::collections::BTreeMap<String, String>,
+}
+
+/// Returned by `GET /export/public-posts`
+#[derive(Deserialize, Debug, Clone)]
+pub struct PublicPostsPage {
+    pub posts: Vec<Post>,
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+}
+
+/// A post waiting for moderator review, or rejected in it
+#[derive(Deserialize, Debug, Clone)]
+pub struct HeldPost {
+    pub id: String,
+    pub user_id: String,
+    pub content: String,
+    pub created_at: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// "pending" or "rejected"
+    pub status: String,
+    #[serde(default)]
+    pub moderation: Option<ModerationScores>,
+    #[serde(default)]
+    pub rejection_reason: Option<String>,
+    #[serde(default)]
+    pub reviewed_at: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Warning {
+    pub moderator_id: String,
+    pub reason: String,
+    pub created_at: String,
+}
+
+/// `GET /profile/export` archive of everything stored about the caller
+#[derive(Deserialize, Debug, Clone)]
+pub struct DataExport {
+    pub exported_at: String,
+    pub profile: ExportedProfile,
+    pub posts: Vec<Post>,
+    pub followings: Vec<String>,
+    pub followers: Vec<String>,
+    pub sessions: Vec<ExportedSession>,
+    pub warnings: Vec<Warning>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ExportedProfile {
+    pub id: String,
+    pub username: String,
+    pub bio: String,
+    pub role: Role,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ExportedSession {
+    pub created_at: String,
+    #[serde(default)]
+    pub last_used_at: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub ip: Option<String>,
+    #[serde(default)]
+    pub last_ip: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TokenCleanup {
+    pub removed: usize,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    pub created_at: String,
+    /// Only returned when the webhook is created
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WebhookRetry {
+    pub delivered: usize,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ExpiredSweep {
+    pub removed: usize,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FeedCompaction {
+    pub compacted: usize,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RoleChange {
+    pub id: String,
+    pub username: String,
+    pub role: Role,
+}
+
+/// Returned by `GET/PUT /admin/settings`
+#[derive(Deserialize, Debug, Clone)]
+pub struct InstanceSettings {
+    #[serde(default)]
+    pub public_export_enabled: bool,
+    #[serde(default)]
+    pub site_name: Option<String>,
+    #[serde(default)]
+    pub logo_url: Option<String>,
+    #[serde(default)]
+    pub accent_color: Option<String>,
+    #[serde(default)]
+    pub footer_links: Vec<FooterLink>,
+    #[serde(default)]
+    pub revision: u64,
+}
+
+/// Partial update for `PUT /admin/settings`; unset fields are left unchanged
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SettingsUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_export_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logo_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accent_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub footer_links: Option<Vec<FooterLink>>,
+}
@@ -0,0 +1,28 @@
+use spin_sdk::http::Response;
+use crate::config::*;
+use crate::oauth;
+
+/// Limits and feature flags the embedded frontend reads at startup instead of hardcoding
+pub fn get_client_config() -> anyhow::Result<Response> {
+    let resp = serde_json::json!({
+        "posts_per_page": POSTS_PER_PAGE,
+        "users_per_page": USERS_PER_PAGE,
+        "max_post_length": MAX_POST_LENGTH,
+        "max_bio_length": MAX_BIO_LENGTH,
+        "min_username_length": MIN_USERNAME_LENGTH,
+        "max_username_length": MAX_USERNAME_LENGTH,
+        "min_password_length": MIN_PASSWORD_LENGTH,
+        "features": {
+            "oauth_providers": oauth::enabled_providers(),
+            "password_reset": true
+        },
+        "announcement": announcement()
+    });
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .header("Cache-Control", "no-cache")
+        .body(serde_json::to_vec(&resp)?)
+        .build())
+}
@@ -5,40 +5,559 @@ pub fn token_expiration_hours() -> i64 {
         .unwrap_or(24)
 }
 
+// Largest request body accepted, in bytes
+pub fn max_body_bytes() -> usize {
+    std::env::var("BORD_MAX_BODY_BYTES")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(64 * 1024)
+}
+
+// Minimum seconds between two posts by the same user; 0 disables the cooldown
+pub fn post_cooldown_seconds() -> i64 {
+    std::env::var("BORD_POST_COOLDOWN_SECONDS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(10)
+}
+
+// Redis address (redis://host:port) to use instead of Spin's KV store
+pub fn redis_url() -> Option<String> {
+    std::env::var("BORD_REDIS_URL")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+}
+
+// Test-only switch for the integration suite: password reset tokens are returned
+// in the `POST /password/forgot` response, and rate limits, the per-IP login
+// lockout and the post cooldown are not applied. Off unless set to "true"; never
+// enable it on a real instance.
+pub fn test_mode() -> bool {
+    std::env::var("BORD_TEST_MODE").map(|v| v == "true").unwrap_or(false)
+}
+
+// Seed the sample users/posts from the fixture; for dev instances only
+pub fn seed_data_enabled() -> bool {
+    std::env::var("BORD_SEED_DATA").map(|v| v == "true").unwrap_or(false)
+}
+
+// Fixture file to seed from instead of the embedded fixtures/seed.json
+pub fn seed_fixture_path() -> Option<String> {
+    std::env::var("BORD_SEED_FIXTURE")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+}
+
+// Log one JSON line per request to stdout (see middleware::request_log)
+pub fn request_log_enabled() -> bool {
+    std::env::var("BORD_LOG_REQUESTS").map(|v| v == "true").unwrap_or(false)
+}
+
+// Fraction of successful requests logged, 0.0-1.0; server errors are always logged
+pub fn request_log_sample_rate() -> f64 {
+    std::env::var("BORD_LOG_SAMPLE_RATE")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .map(|rate| rate.clamp(0.0, 1.0))
+        .unwrap_or(1.0)
+}
+
+// Whether requests for static assets (paths with a file extension) are logged too
+pub fn request_log_static() -> bool {
+    std::env::var("BORD_LOG_STATIC").map(|v| v == "true").unwrap_or(false)
+}
+
+// Where dev builds (`dev` feature) read static assets from, inside the component
+pub fn dev_static_dir() -> String {
+    std::env::var("BORD_DEV_STATIC_DIR")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| "/static".to_string())
+}
+
+// Origins allowed to call the API from browsers (comma-separated, "*" for any)
+pub fn cors_origins() -> Vec<String> {
+    std::env::var("BORD_CORS_ORIGINS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|origin| origin.trim().to_string())
+        .filter(|origin| !origin.is_empty())
+        .collect()
+}
+
+// Existing accounts (comma-separated usernames) to promote to admin once, to
+// bootstrap roles; listed names can't be taken by new signups
+pub fn admin_usernames() -> Vec<String> {
+    std::env::var("BORD_ADMIN_USERNAMES")
+        .unwrap_or_default()
+        .split(',')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+// Secret for signing HS256 access tokens, from the `jwt_secret` Spin variable
+// (or BORD_JWT_SECRET). Anyone who knows it can sign a token for any user, so
+// there is no built-in default: without a real secret every request is
+// answered with a 503 naming the variable.
+pub const MIN_JWT_SECRET_LENGTH: usize = 32;
+const PLACEHOLDER_JWT_SECRETS: &[&str] = &["change-me-in-production", "bord-dev-secret"];
+pub const JWT_SECRET_MISSING: &str =
+    "Set the jwt_secret variable (SPIN_VARIABLE_JWT_SECRET) to a random value of at least 32 characters";
+
+pub fn jwt_secret() -> Option<String> {
+    spin_sdk::variables::get("jwt_secret")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .or_else(|| std::env::var("BORD_JWT_SECRET").ok())
+        .filter(|v| v.len() >= MIN_JWT_SECRET_LENGTH && !PLACEHOLDER_JWT_SECRETS.contains(&v.as_str()))
+}
+
+// Fraction of authenticated requests that record the session's last use
+pub const SESSION_TOUCH_SAMPLE_RATE: f64 = 0.1;
+
+// Browser sessions: HttpOnly cookie holding the JWT, plus a CSRF header on writes
+pub const SESSION_COOKIE_NAME: &str = "bord_session";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+// Signup bot-signal thresholds (see core::bot_signals)
+pub fn signup_retry_score() -> u32 {
+    std::env::var("BORD_SIGNUP_RETRY_SCORE")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(50)
+}
+
+pub fn signup_block_score() -> u32 {
+    std::env::var("BORD_SIGNUP_BLOCK_SCORE")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(100)
+}
+
+// Optional banner shown by the frontend, served via GET /client-config
+pub fn announcement() -> Option<String> {
+    std::env::var("BORD_ANNOUNCEMENT")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+}
+
+// Identicons are IDENTICON_GRID cells square, drawn at IDENTICON_PIXELS;
+// usernames don't change, so browsers can keep them for a day
+pub const IDENTICON_GRID: usize = 5;
+pub const IDENTICON_PIXELS: usize = 120;
+pub const AVATAR_CACHE_CONTROL: &str = "public, max-age=86400";
+
+// Size of the iframe oEmbed consumers get (`maxwidth`/`maxheight` can only shrink
+// it), and how long they and browsers may cache embeds
+pub const EMBED_WIDTH: usize = 550;
+pub const EMBED_HEIGHT: usize = 240;
+pub const EMBED_CACHE_SECONDS: u64 = 3600;
+
+// Catalog in `locales/` used when nothing in Accept-Language matches, and for missing keys
+pub const DEFAULT_LOCALE: &str = "en";
+
+// Branding defaults; admins can override them at runtime (see branding.rs)
+pub const DEFAULT_SITE_NAME: &str = "Bord";
+pub const DEFAULT_LOGO_URL: &str = "/B.png";
+pub const DEFAULT_ACCENT_COLOR: &str = "#209CEE";
+
+pub fn site_name() -> String {
+    std::env::var("BORD_SITE_NAME")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_SITE_NAME.to_string())
+}
+
+pub fn logo_url() -> String {
+    std::env::var("BORD_LOGO_URL")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_LOGO_URL.to_string())
+}
+
+pub fn accent_color() -> String {
+    std::env::var("BORD_ACCENT_COLOR")
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_ACCENT_COLOR.to_string())
+}
+
+// Password reset tokens are single use and short-lived
+pub const PASSWORD_RESET_EXPIRATION_MINUTES: i64 = 30;
+
+// HTTP endpoint that sends mail for the app (password reset tokens): it gets a
+// JSON `POST` of `{"to", "subject", "text"}`, with `Authorization: Bearer
+// BORD_MAIL_RELAY_TOKEN` when that is set. Unset means no mail is sent. Its
+// origin must be in allowed_outbound_hosts in spin.toml.
+pub fn mail_relay_url() -> Option<String> {
+    std::env::var("BORD_MAIL_RELAY_URL").ok().filter(|v| !v.trim().is_empty())
+}
+
+pub fn mail_relay_token() -> Option<String> {
+    std::env::var("BORD_MAIL_RELAY_TOKEN").ok().filter(|v| !v.is_empty())
+}
+
+// How long sending a mail waits to connect, and then for the relay's response
+pub const MAIL_RELAY_TIMEOUT_MS: u64 = 5000;
+
+// OAuth login round-trips must complete within this window, in the browser that
+// started them: the state is also set in this HttpOnly cookie and checked on callback
+pub const OAUTH_STATE_EXPIRATION_MINUTES: i64 = 10;
+pub const OAUTH_STATE_COOKIE_NAME: &str = "bord_oauth_state";
+
+// Path prefix of the current API version; the unversioned paths remain as aliases
+pub const API_PREFIX: &str = "/api/v1";
+
 // Content length limits
 pub const MAX_POST_LENGTH: usize = 5000;
 pub const MAX_BIO_LENGTH: usize = 500;
+// Longest email address RFC 5321 allows in a path
+pub const MAX_EMAIL_LENGTH: usize = 254;
+
+// Bump when post rendering (sanitizing, linkifying) changes so stored HTML is regenerated
+pub const POST_RENDER_VERSION: u32 = 1;
 
 // Username constraints
 pub const MIN_USERNAME_LENGTH: usize = 3;
 pub const MAX_USERNAME_LENGTH: usize = 50;
 
+// Username of the reserved account that publishes announcements and automated posts
+pub const SYSTEM_USERNAME: &str = "system";
+
+// Display name for deleted accounts
+pub const DELETED_USERNAME: &str = "[deleted]";
+
 // Password constraints
 pub const MIN_PASSWORD_LENGTH: usize = 3;
 
-// Pagination limits
-// Must match POSTS_PER_PAGE in static/index.html
+// New-account gates (see core::policy): an account clears a gate once it is
+// old enough OR has published enough posts
+pub fn link_post_min_account_age_hours() -> i64 {
+    std::env::var("BORD_LINK_POST_MIN_ACCOUNT_AGE_HOURS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(24)
+}
+
+pub fn link_post_min_posts() -> u64 {
+    std::env::var("BORD_LINK_POST_MIN_POSTS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(3)
+}
+
+// Following more than this many accounts counts as mass following
+pub fn mass_follow_threshold() -> usize {
+    std::env::var("BORD_MASS_FOLLOW_THRESHOLD")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(50)
+}
+
+pub fn mass_follow_min_account_age_hours() -> i64 {
+    std::env::var("BORD_MASS_FOLLOW_MIN_ACCOUNT_AGE_HOURS")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .unwrap_or(72)
+}
+
+pub fn mass_follow_min_posts() -> u64 {
+    std::env::var("BORD_MASS_FOLLOW_MIN_POSTS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(5)
+}
+// Most targets accepted in one POST /follow/batch
+pub const MAX_FOLLOW_BATCH: usize = 50;
+
+// Rate limits as token buckets: (burst capacity, tokens refilled per minute)
+pub const POST_RATE_LIMIT: (f64, f64) = (10.0, 5.0); // per user
+pub const SIGNUP_RATE_LIMIT: (f64, f64) = (5.0, 1.0); // per IP
+pub const LOGIN_RATE_LIMIT: (f64, f64) = (10.0, 5.0); // per IP
+pub const EXPORT_RATE_LIMIT: (f64, f64) = (30.0, 30.0); // per IP
+
+// Login lockout: this many failures within the window locks the username (or IP)
+pub const LOGIN_MAX_FAILURES: u32 = 5;
+pub const LOGIN_MAX_FAILURES_PER_IP: u32 = 20;
+pub const LOGIN_FAILURE_WINDOW_MINUTES: i64 = 15;
+pub const LOGIN_LOCKOUT_MINUTES: i64 = 15;
+
+// How much the weekly digest lists of each section
+pub const DIGEST_TOP_POSTS: usize = 10;
+pub const DIGEST_TRENDING_TAGS: usize = 10;
+pub const DIGEST_NEW_USERS: usize = 20;
+
+// Request bodies nested deeper or with more object fields than this are rejected unparsed
+pub const MAX_JSON_DEPTH: usize = 32;
+pub const MAX_JSON_FIELDS: usize = 1000;
+
+// Events a webhook can subscribe to
+pub const WEBHOOK_EVENTS: &[&str] = &["post.created", "post.rejected", "user.created", "user.followed"];
+// Failed deliveries are retried with exponential backoff, then dropped
+pub const WEBHOOK_MAX_ATTEMPTS: u32 = 5;
+pub const WEBHOOK_RETRY_BASE_SECONDS: i64 = 60;
+// How long a delivery waits to connect, and then for the receiver's response
+pub const WEBHOOK_TIMEOUT_MS: u64 = 2000;
+
+// Origins webhooks may point at (comma-separated, e.g. https://hooks.example.com).
+// Keep in sync with allowed_outbound_hosts in spin.toml, which the component
+// can't read; empty means no webhook can be registered.
+pub fn webhook_allowed_origins() -> Vec<String> {
+    std::env::var("BORD_WEBHOOK_ALLOWED_ORIGINS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|origin| origin.trim().trim_end_matches('/').to_ascii_lowercase())
+        .filter(|origin| !origin.is_empty())
+        .collect()
+}
+
+// Shared secret the moderation filter sends in MODERATION_SECRET_HEADER; without it
+// the X-Moderation-* headers are ignored
+pub fn moderation_filter_secret() -> Option<String> {
+    std::env::var("BORD_MODERATION_FILTER_SECRET").ok().filter(|v| !v.is_empty())
+}
+pub const MODERATION_SECRET_HEADER: &str = "X-Moderation-Secret";
+// Set by the moderation filter on requests it forwards: "hold" queues a new post for review
+pub const MODERATION_ACTION_HEADER: &str = "X-Moderation-Action";
+pub const MODERATION_REASON_HEADER: &str = "X-Moderation-Reason";
+// Filter scores stored on the post: a number, a comma-separated list and a label
+pub const MODERATION_SENTIMENT_HEADER: &str = "X-Moderation-Sentiment";
+pub const MODERATION_CATEGORIES_HEADER: &str = "X-Moderation-Categories";
+pub const MODERATION_SOURCE_HEADER: &str = "X-Moderation-Source";
+pub const MAX_MODERATION_CATEGORIES: usize = 10;
+pub const MAX_MODERATION_LABEL_LENGTH: usize = 32;
+pub const MAX_REJECTION_REASON_LENGTH: usize = 500;
+
+// Retries carrying the same Idempotency-Key within this window replay the first response
+pub const IDEMPOTENCY_HEADER: &str = "Idempotency-Key";
+pub const IDEMPOTENCY_KEY_TTL_HOURS: i64 = 24;
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+// A user's set of stored idempotency responses drops expired ones past this size
+pub const USER_IDEMPOTENCY_PRUNE_AT: usize = 100;
+
+// Most post IDs accepted in one POST /impressions batch
+pub const MAX_IMPRESSIONS_BATCH: usize = 100;
+
+// Pagination limits (served to the frontend via GET /client-config)
 pub const POSTS_PER_PAGE: usize = 10;
+// Page size for expanded follower/following lists
+pub const USERS_PER_PAGE: usize = 20;
+// Page size for the public posts export
+pub const EXPORT_PAGE_SIZE: usize = 100;
+
+// Largest value the KV backend takes; older large lists were split across chunk keys (see core::chunked)
+pub const MAX_KV_VALUE_BYTES: usize = 1024 * 1024;
+
+// Journal size at which publishing a post folds the journal into the day buckets
+pub const FEED_JOURNAL_COMPACT_AT: usize = 50;
+
+// Compare-and-swap retries for one set update on the KV store before giving up
+pub const SET_SWAP_ATTEMPTS: u32 = 20;
+
+// Cursor batch size when walking Redis keys with SCAN
+pub const REDIS_SCAN_COUNT: i64 = 1000;
 
 // KV Store Keys
 pub const USERS_LIST_KEY: &str = "users_list";
 pub const FEED_KEY: &str = "feed";
+// Set of feed entries not yet compacted into a day bucket
+pub const FEED_JOURNAL_KEY: &str = "feed_journal";
+// One key per journal entry, the layout before FEED_JOURNAL_KEY (index_feed_sets migration)
+pub const FEED_JOURNAL_PREFIX: &str = "feed_journal:";
+// Set of the days that have a feed bucket
+pub const FEED_DAYS_KEY: &str = "feed_days";
+pub const TTL_PREFIX: &str = "ttl:";
 pub const TOKENS_LIST_KEY: &str = "tokens_list";
+pub const INSTANCE_SETTINGS_KEY: &str = "instance_settings";
+pub const SYSTEM_ACCOUNT_KEY: &str = "system_account";
+// Which one-time setup steps have run, see core::boot
+pub const BOOT_KEY: &str = "boot";
+// Set once the seed fixture has been applied
+pub const SEED_MARKER_KEY: &str = "seeded";
+pub const WEBHOOKS_KEY: &str = "webhooks";
+pub const WEBHOOK_DELIVERY_PREFIX: &str = "webhook_delivery:";
+pub const HELD_POST_PREFIX: &str = "held_post:";
+// ID set of every held post, pending or rejected
+pub const HELD_POSTS_KEY: &str = "held_posts";
+pub const OAUTH_LINK_PREFIX: &str = "oauth:";
 
 // KV Store Key Functions
+// Expiry marker of a value written with set_json_ttl
+pub fn ttl_key(key: &str) -> String {
+    format!("{}{}", TTL_PREFIX, key)
+}
+
+// Index key holding the members of an ID set such as USERS_LIST_KEY, see core::db::members
+pub fn set_key(set: &str) -> String {
+    format!("set:{}", set)
+}
+
+// One member of an ID set in the old one-key-per-member layout (index_id_sets migration)
+pub fn member_key(set: &str, member: &str) -> String {
+    format!("{}:{}", set, member)
+}
+
+// ID set of a user's session token IDs, next to the global TOKENS_LIST_KEY
+pub fn user_tokens_set(user_id: &str) -> String {
+    format!("user_tokens:{}", user_id)
+}
+
+// ID set of the stored idempotency responses that belong to a user, so
+// deleting the account deletes them too
+pub fn user_idempotency_set(user_id: &str) -> String {
+    format!("user_idempotency:{}", user_id)
+}
+
 pub fn user_key(id: &str) -> String {
     format!("user:{}", id)
 }
 
+// Username -> user ID index
+pub fn username_key(username: &str) -> String {
+    format!("username:{}", username)
+}
+
 pub fn post_key(id: &str) -> String {
     format!("post:{}", id)
 }
 
+// Raw content a post's rendered HTML was produced from
+pub fn post_source_key(id: &str) -> String {
+    format!("post_source:{}", id)
+}
+
+// Set of the feed entries of one UTC day, day as YYYY-MM-DD
+pub fn feed_bucket_key(day: &str) -> String {
+    format!("feed:{}", day)
+}
+
+// Set of the feed entries of one user's posts
+pub fn user_posts_set(user_id: &str) -> String {
+    format!("user_posts:{}", user_id)
+}
+
 pub fn token_key(token: &str) -> String {
     format!("token:{}", token)
 }
 
+// Last recorded use of a session, kept apart from token_key so it can be written blind
+pub fn session_activity_key(jti: &str) -> String {
+    format!("session_activity:{}", jti)
+}
+
+pub fn revoked_token_key(jti: &str) -> String {
+    format!("revoked:{}", jti)
+}
+
+pub fn reset_token_key(token: &str) -> String {
+    format!("reset:{}", token)
+}
+
+pub fn oauth_state_key(state: &str) -> String {
+    format!("oauth_state:{}", state)
+}
+
+pub fn oauth_link_key(provider: &str, external_id: &str) -> String {
+    format!("{}{}:{}", OAUTH_LINK_PREFIX, provider, external_id)
+}
+
+pub fn tombstone_key(user_id: &str) -> String {
+    format!("tombstone:{}", user_id)
+}
+
+// Username of a deleted account -> its ID, so the name stays taken while the tombstone exists
+pub fn retired_username_key(username: &str) -> String {
+    format!("retired_username:{}", username)
+}
+
+pub fn warnings_key(user_id: &str) -> String {
+    format!("warnings:{}", user_id)
+}
+
+pub fn last_post_key(user_id: &str) -> String {
+    format!("last_post:{}", user_id)
+}
+
+pub fn rate_limit_key(scope: &str, subject: &str) -> String {
+    format!("ratelimit:{}:{}", scope, subject)
+}
+
+pub fn login_failures_key(subject: &str) -> String {
+    format!("login_failures:{}", subject)
+}
+
+pub fn profile_page_key(username: &str, locale: &str) -> String {
+    format!("profile_html:{}:{}", username, locale)
+}
+
+// Stored response for an Idempotency-Key, per route and caller
+pub fn idempotency_key_for(route: &str, caller: &str, key: &str) -> String {
+    format!("idempotency:{}:{}:{}", route, caller, key)
+}
+
+// Queued webhook delivery awaiting its next attempt
+pub fn webhook_delivery_key(delivery_id: &str) -> String {
+    format!("{}{}", WEBHOOK_DELIVERY_PREFIX, delivery_id)
+}
+
+// Post waiting for (or rejected in) moderator review
+pub fn held_post_key(id: &str) -> String {
+    format!("{}{}", HELD_POST_PREFIX, id)
+}
+
+// Rendered digest for an ISO week, e.g. "2026-W41", in one locale
+pub fn digest_key(week: &str, locale: &str) -> String {
+    format!("digest_html:{}:{}", week, locale)
+}
+
+pub fn follower_count_key(user_id: &str) -> String {
+    format!("follower_count:{}", user_id)
+}
+
+pub fn following_count_key(user_id: &str) -> String {
+    format!("following_count:{}", user_id)
+}
+
+// Posts a user has published, for the new-account gates
+pub fn post_count_key(user_id: &str) -> String {
+    format!("post_count:{}", user_id)
+}
+
+/// Daily impression counter, `day` as YYYY-MM-DD (UTC)
+pub fn impressions_key(post_id: &str, day: &str) -> String {
+    format!("{}{}", post_impressions_prefix(post_id), day)
+}
+
+pub fn post_impressions_prefix(post_id: &str) -> String {
+    format!("impressions:{}:", post_id)
+}
+
 pub fn followings_key(user_id: &str) -> String {
     format!("followings:{}", user_id)
 }
 
+// Reverse index of followings_key: who follows this user
+pub fn followers_key(user_id: &str) -> String {
+    format!("followers:{}", user_id)
+}
+
+// Chunk count of a value split by core::chunked
+pub fn chunk_manifest_key(key: &str) -> String {
+    format!("chunks:{}", key)
+}
+
+pub fn chunk_key(key: &str, index: usize) -> String {
+    format!("chunk:{}:{}", key, index)
+}
+
+// Marks a BORD_ADMIN_USERNAMES entry as promoted, so a later demotion sticks
+pub fn admin_promotion_key(username: &str) -> String {
+    format!("admin_promotion:{}", username)
+}
+
+// Marks a one-time data migration as applied
+pub fn migration_key(name: &str) -> String {
+    format!("migration:{}", name)
+}
+
@@ -0,0 +1,79 @@
+use spin_sdk::http::{Request, Response};
+use crate::core::errors::ApiError;
+use crate::config::{max_body_bytes, MAX_JSON_DEPTH, MAX_JSON_FIELDS};
+
+/// Nesting depth and total object member count of a JSON document, from a
+/// single pass over the bytes so hostile input is never handed to serde
+fn json_shape(body: &[u8]) -> (usize, usize) {
+    let (mut depth, mut max_depth, mut members) = (0usize, 0, 0);
+    let (mut in_string, mut escaped) = (false, false);
+    for &byte in body {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                max_depth = max_depth.max(depth);
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            b':' => members += 1,
+            _ => {}
+        }
+    }
+    (max_depth, members)
+}
+
+/// Reject bodies the handlers can't use before any of them run: too large
+/// (413), not JSON (415), or too deeply nested, too many fields or malformed
+/// JSON (400). Empty bodies pass, since several endpoints take none.
+pub fn check_body(req: &Request) -> Option<Response> {
+    let body = req.body();
+    if body.is_empty() {
+        return None;
+    }
+    if body.len() > max_body_bytes() {
+        return Some(ApiError::PayloadTooLarge.into());
+    }
+
+    let is_json = req.header("Content-Type")
+        .and_then(|h| h.as_str())
+        .and_then(|ct| ct.split(';').next())
+        .map(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+        .unwrap_or(false);
+    if !is_json {
+        return Some(ApiError::UnsupportedMediaType.into());
+    }
+
+    let (depth, members) = json_shape(body);
+    if depth > MAX_JSON_DEPTH {
+        return Some(ApiError::BadRequest("json_too_deep", format!("JSON nested deeper than {} levels", MAX_JSON_DEPTH)).into());
+    }
+    if members > MAX_JSON_FIELDS {
+        return Some(ApiError::BadRequest("json_too_many_fields", format!("JSON has more than {} fields", MAX_JSON_FIELDS)).into());
+    }
+
+    if serde_json::from_slice::<serde::de::IgnoredAny>(body).is_err() {
+        return Some(ApiError::BadRequest("malformed_json", "Malformed JSON body".to_string()).into());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_shape_ignores_brackets_in_strings() {
+        assert_eq!(json_shape(br#"{"content": "[[{:}]]"}"#), (1, 1));
+        assert_eq!(json_shape(br#"{"a": {"b": [1, {"c": "\"{"}]}}"#), (4, 3));
+        assert_eq!(json_shape(b"[[[[]]]]"), (4, 0));
+    }
+}
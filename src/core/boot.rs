@@ -0,0 +1,104 @@
+//! One-time setup ahead of request handling: data migrations, the sample data
+//! when `BORD_SEED_DATA` is on, and promoting `BORD_ADMIN_USERNAMES`. What has been
+//! done is recorded under `BOOT_KEY`, so once an instance is set up for this
+//! build each request pays a single read instead of re-checking every step.
+
+use serde::{Deserialize, Serialize};
+use spin_sdk::http::{Request, Response};
+use crate::core::db::BordStore;
+use crate::core::errors::ApiError;
+use crate::core::helpers::store;
+use crate::core::middleware::Next;
+use crate::core::migrations::{migration_names, run_migrations};
+use crate::core::seed::seed_fixture_data;
+use crate::core::permissions::promote_listed_admins;
+use crate::config::*;
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+struct BootState {
+    /// Names of the migrations applied
+    #[serde(default)]
+    migrations: Vec<String>,
+    /// Whether the seed fixture has been applied
+    #[serde(default)]
+    seeded: bool,
+    /// The BORD_ADMIN_USERNAMES list last promoted
+    #[serde(default)]
+    admins: Vec<String>,
+}
+
+impl BootState {
+    /// The state of a store fully set up for this build and configuration
+    fn wanted() -> Self {
+        BootState {
+            migrations: migration_names(),
+            seeded: seed_data_enabled(),
+            admins: admin_usernames(),
+        }
+    }
+}
+
+/// Run whichever setup steps the recorded boot state says are outstanding
+pub fn ensure_booted(store: &dyn BordStore) -> anyhow::Result<()> {
+    let wanted = BootState::wanted();
+    let recorded: BootState = store.get_json(BOOT_KEY)?.unwrap_or_default();
+    if recorded == wanted {
+        return Ok(());
+    }
+
+    let mut done = recorded.clone();
+    if done.migrations != wanted.migrations {
+        run_migrations(store)?;
+        done.migrations = wanted.migrations;
+    }
+    if wanted.seeded && !done.seeded {
+        // Sample data is best effort; a failed seed is retried next request
+        done.seeded = seed_fixture_data(store).is_ok();
+    }
+    done.seeded &= wanted.seeded;
+    if done.admins != wanted.admins {
+        promote_listed_admins(store)?;
+        done.admins = wanted.admins;
+    }
+
+    if done != recorded {
+        store.set_json(BOOT_KEY, &done)?;
+    }
+    Ok(())
+}
+
+/// `ensure_booted` as a middleware layer, inside `errors` so a store outage is
+/// a 503. Without a token secret nothing can work, so that is a 503 too.
+pub fn boot(req: Request, next: Next) -> anyhow::Result<Response> {
+    if jwt_secret().is_none() {
+        eprintln!("{}", JWT_SECRET_MISSING);
+        return Ok(ApiError::NotConfigured(JWT_SECRET_MISSING).into());
+    }
+    ensure_booted(&store()?)?;
+    next(req)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::MemoryStore;
+
+    #[test]
+    fn setup_runs_until_recorded() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+
+        ensure_booted(store).unwrap();
+        let first = migration_key(&migration_names()[0]);
+        assert!(store.exists(&first).unwrap());
+
+        // Recorded as done, so the migrations aren't looked at again
+        store.delete(&first).unwrap();
+        ensure_booted(store).unwrap();
+        assert!(!store.exists(&first).unwrap());
+
+        store.delete(BOOT_KEY).unwrap();
+        ensure_booted(store).unwrap();
+        assert!(store.exists(&first).unwrap());
+    }
+}
@@ -0,0 +1,77 @@
+use spin_sdk::http::Request;
+use serde::Deserialize;
+use crate::config::{signup_retry_score, signup_block_score};
+
+// Signal weights; API clients without the form fields are not penalised
+const HONEYPOT_WEIGHT: u32 = 100;
+const FAST_SUBMIT_WEIGHT: u32 = 50;
+const MISSING_USER_AGENT_WEIGHT: u32 = 25;
+const MISSING_ACCEPT_LANGUAGE_WEIGHT: u32 = 15;
+
+// Humans take at least this long to fill in the signup form
+const MIN_FORM_ELAPSED_MS: u64 = 1500;
+
+/// There is no CAPTCHA to solve: a borderline signup is refused with the
+/// `signup_retry_required` problem code, and a human resubmitting the form
+/// at a normal pace gets through
+#[derive(Debug, PartialEq)]
+pub enum SignupVerdict {
+    Allow,
+    Retry,
+    Block,
+}
+
+/// Signup form fields that only matter as bot signals
+#[derive(Deserialize, Default)]
+pub struct FormSignals {
+    /// Hidden honeypot field, only bots fill it in
+    #[serde(default)]
+    pub website: Option<String>,
+    /// Time the signup form was open before submit
+    #[serde(default)]
+    pub form_elapsed_ms: Option<u64>,
+}
+
+fn header_missing(req: &Request, name: &str) -> bool {
+    req.header(name)
+        .and_then(|h| h.as_str())
+        .map(|v| v.trim().is_empty())
+        .unwrap_or(true)
+}
+
+/// Score bot signals on a signup request; higher means more bot-like
+///
+/// - the `FormSignals` fields
+/// - missing `User-Agent` / `Accept-Language` headers
+pub fn score_signup(req: &Request, form: &FormSignals) -> u32 {
+    let mut score = 0;
+
+    if form.website.as_deref().map(|v| !v.is_empty()).unwrap_or(false) {
+        score += HONEYPOT_WEIGHT;
+    }
+    if let Some(elapsed) = form.form_elapsed_ms {
+        if elapsed < MIN_FORM_ELAPSED_MS {
+            score += FAST_SUBMIT_WEIGHT;
+        }
+    }
+    if header_missing(req, "User-Agent") {
+        score += MISSING_USER_AGENT_WEIGHT;
+    }
+    if header_missing(req, "Accept-Language") {
+        score += MISSING_ACCEPT_LANGUAGE_WEIGHT;
+    }
+
+    score
+}
+
+/// Map a signup score onto the configured thresholds
+pub fn signup_verdict(req: &Request, form: &FormSignals) -> SignupVerdict {
+    let score = score_signup(req, form);
+    if score >= signup_block_score() {
+        SignupVerdict::Block
+    } else if score >= signup_retry_score() {
+        SignupVerdict::Retry
+    } else {
+        SignupVerdict::Allow
+    }
+}
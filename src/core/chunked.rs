@@ -0,0 +1,75 @@
+use serde::de::DeserializeOwned;
+use crate::core::db::BordStore;
+use crate::config::*;
+
+/// JSON lists written before the ID sets, which may have outgrown the
+/// backend's per-value size limit.
+///
+/// Small values were stored as-is under their key. Larger ones were split
+/// into `chunk:{key}:{n}` entries with a `chunks:{key}` manifest holding the
+/// chunk count. Only the migrations still read these, then delete them.
+pub trait LargeValues {
+    fn get_large_json<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>>;
+    fn delete_large(&self, key: &str) -> anyhow::Result<()>;
+}
+
+impl LargeValues for dyn BordStore + '_ {
+    fn get_large_json<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        if let Some(bytes) = self.get(key)? {
+            return Ok(Some(serde_json::from_slice(&bytes)?));
+        }
+
+        let count = match self.get_json::<usize>(&chunk_manifest_key(key))? {
+            Some(count) => count,
+            None => return Ok(None),
+        };
+
+        let mut bytes = Vec::new();
+        for index in 0..count {
+            match self.get(&chunk_key(key, index))? {
+                Some(chunk) => bytes.extend_from_slice(&chunk),
+                None => anyhow::bail!("Missing chunk {} of {}", index, key),
+            }
+        }
+
+        Ok(Some(serde_json::from_slice(&bytes)?))
+    }
+
+    fn delete_large(&self, key: &str) -> anyhow::Result<()> {
+        self.delete(key)?;
+        let count = self.get_json::<usize>(&chunk_manifest_key(key))?.unwrap_or(0);
+        delete_chunks(self, key, count)
+    }
+}
+
+/// Remove a chunked value's manifest and all of its chunks
+fn delete_chunks(store: &dyn BordStore, key: &str, count: usize) -> anyhow::Result<()> {
+    store.delete(&chunk_manifest_key(key))?;
+    for index in 0..count {
+        store.delete(&chunk_key(key, index))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::MemoryStore;
+
+    #[test]
+    fn chunked_values_read_back_and_delete() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+
+        store.set(&chunk_key("feed", 0), b"[\"a\",").unwrap();
+        store.set(&chunk_key("feed", 1), b"\"b\"]").unwrap();
+        store.set_json(&chunk_manifest_key("feed"), &2).unwrap();
+        assert_eq!(store.get_large_json::<Vec<String>>("feed").unwrap().unwrap(), ["a", "b"]);
+
+        store.delete_large("feed").unwrap();
+        assert!(store.scan("").unwrap().is_empty());
+
+        store.set_json("feed", &["c"]).unwrap();
+        assert_eq!(store.get_large_json::<Vec<String>>("feed").unwrap().unwrap(), ["c"]);
+    }
+}
@@ -1,186 +1,415 @@
+use serde::de::DeserializeOwned;
+use serde::{Serialize, Deserialize};
 use spin_sdk::key_value::Store;
-use crate::models::models::{User, Post};
-use crate::core::helpers::{hash_password, now_iso as helpers_now_iso};
+use spin_sdk::wit::wasi::keyvalue::{atomics, store as wasi_kv};
+use std::rc::Rc;
+use crate::models::models::User;
+use crate::core::feed_index::{feed_ids, clear as clear_feed};
+use crate::core::i18n;
+use crate::moderation::clear_held_posts;
 use crate::config::*;
-use uuid::Uuid;
-
-fn now_iso() -> String {
-    helpers_now_iso()
-}
-
-pub fn init_test_data(store: &Store) -> anyhow::Result<()> {
-    // Check if test users already exist
-     let users: Vec<String> = store.get_json(USERS_LIST_KEY)?.unwrap_or_default();
-     let mut has_test = false;
-     let mut has_alice = false;
-     let mut has_bob = false;
-     let mut test_user_id = String::new();
-     let mut bob_user_id = String::new();
-     
-     for id in &users {
-         if let Some(u) = store.get_json::<User>(&user_key(id))? {
-             if u.username == "test" {
-                 has_test = true;
-                 test_user_id = id.clone();
-             }
-             if u.username == "alice" {
-                 has_alice = true;
-             }
-             if u.username == "bob" {
-                 has_bob = true;
-                 bob_user_id = id.clone();
-             }
-         }
-     }
-     
-     if has_test && has_alice && has_bob {
-         return Ok(()); // Already initialized
-     }
-     
-     let mut users = users;
-     let mut feed: Vec<String> = store.get_json(FEED_KEY)?.unwrap_or_default();
-    
-    // Create first test user if not exists
-    if !has_test {
-        let user_id = Uuid::new_v4().to_string();
-        let user = User {
-            id: user_id.clone(),
-            username: "test".to_string(),
-            password: hash_password("test")?,
-            bio: Some("Test user bio".to_string()),
-        };
-        
-        store.set_json(&user_key(&user_id), &user)?;
-        users.push(user_id.clone());
-        test_user_id = user_id.clone();
-        
-        // Create test post
-        let post_id = Uuid::new_v4().to_string();
-        let post = Post {
-            id: post_id.clone(),
-            user_id,
-            content: "This is my first post on Bord!".to_string(),
-            created_at: now_iso(),
-            updated_at: None,
-        };
-        
-        store.set_json(&post_key(&post_id), &post)?;
-        feed.insert(0, post_id);
+
+/// Key-value backend the handlers talk to.
+///
+/// Spin's KV store and Redis are the production backends; others (SQLite, an
+/// in-memory map for tests) implement these methods and get the JSON and
+/// chunked-value helpers for free.
+pub trait BordStore {
+    fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+    fn set(&self, key: &str, value: &[u8]) -> anyhow::Result<()>;
+    fn delete(&self, key: &str) -> anyhow::Result<()>;
+    fn exists(&self, key: &str) -> anyhow::Result<bool>;
+    /// All keys starting with `prefix`, in no particular order. Walks the
+    /// whole keyspace on most backends, so keep it off request paths.
+    fn scan(&self, prefix: &str) -> anyhow::Result<Vec<String>>;
+    /// Add `members` to the set stored at `key` in one atomic step; returns how
+    /// many weren't in it yet. Concurrent writers never drop each other's members.
+    fn add_to_set(&self, key: &str, members: &[&str]) -> anyhow::Result<usize>;
+    /// Remove `members` from the set at `key` in one atomic step; returns how many were in it
+    fn remove_from_set(&self, key: &str, members: &[&str]) -> anyhow::Result<usize>;
+    /// Members of the set at `key`, in no particular order
+    fn set_members(&self, key: &str) -> anyhow::Result<Vec<String>>;
+}
+
+impl dyn BordStore + '_ {
+    pub fn get_json<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        match self.get(key)? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
     }
-    
-    // Create second test user if not exists
-    if !has_alice {
-        let user_id = Uuid::new_v4().to_string();
-        let user = User {
-            id: user_id.clone(),
-            username: "alice".to_string(),
-            password: hash_password("alice")?,
-            bio: Some("Hello, I'm Alice!".to_string()),
-        };
-        
-        store.set_json(&user_key(&user_id), &user)?;
-        users.push(user_id.clone());
-        
-        // Create first post for alice
-        let post_id_1 = Uuid::new_v4().to_string();
-        let post_1 = Post {
-            id: post_id_1.clone(),
-            user_id: user_id.clone(),
-            content: "Welcome to my board! Excited to share thoughts here.".to_string(),
-            created_at: now_iso(),
-            updated_at: None,
-        };
-        
-        store.set_json(&post_key(&post_id_1), &post_1)?;
-        feed.insert(0, post_id_1);
-        
-        // Create second post for alice
-        let post_id_2 = Uuid::new_v4().to_string();
-        let post_2 = Post {
-            id: post_id_2.clone(),
-            user_id: user_id.clone(),
-            content: "Just finished an amazing project. Feeling productive today!".to_string(),
-            created_at: now_iso(),
-            updated_at: None,
-        };
-        
-        store.set_json(&post_key(&post_id_2), &post_2)?;
-        feed.insert(0, post_id_2);
+
+    pub fn set_json<T: Serialize>(&self, key: &str, value: &T) -> anyhow::Result<()> {
+        self.set(key, &serde_json::to_vec(value)?)
     }
-    
-    // Create third test user if not exists
-    if !has_bob {
-        let user_id = Uuid::new_v4().to_string();
-        let user = User {
-            id: user_id.clone(),
-            username: "bob".to_string(),
-            password: hash_password("bob")?,
-            bio: Some("Bob's corner of the internet".to_string()),
-        };
-        
-        store.set_json(&user_key(&user_id), &user)?;
-        users.push(user_id.clone());
-        bob_user_id = user_id.clone();
-        
-        // Create post for bob
-        let post_id = Uuid::new_v4().to_string();
-        let post = Post {
-            id: post_id.clone(),
-            user_id,
-            content: "Hey everyone! Just joined Bord, looking forward to connecting with you all.".to_string(),
-            created_at: now_iso(),
-            updated_at: None,
+
+    /// Store a value that reads as absent once `ttl_seconds` have passed; the
+    /// KV store has no native expiry. `sweep_expired` removes it for good.
+    pub fn set_json_ttl<T: Serialize>(&self, key: &str, value: &T, ttl_seconds: i64) -> anyhow::Result<()> {
+        let expires_at = chrono::Utc::now().timestamp() + ttl_seconds;
+        self.set_json(key, &Expiring { expires_at, value })?;
+        self.set_json(&ttl_key(key), &expires_at)
+    }
+
+    /// Read a value written by `set_json_ttl`, deleting it if it has expired
+    pub fn get_json_ttl<T: DeserializeOwned>(&self, key: &str) -> anyhow::Result<Option<T>> {
+        let bytes = match self.get(key)? {
+            Some(bytes) => bytes,
+            None => return Ok(None),
         };
-        
-        store.set_json(&post_key(&post_id), &post)?;
-        feed.insert(0, post_id);
+
+        match serde_json::from_slice::<Expiring<T>>(&bytes) {
+            Ok(entry) if entry.expires_at > chrono::Utc::now().timestamp() => Ok(Some(entry.value)),
+            // Expired, or written before the key had a TTL: these are all short-lived
+            _ => {
+                self.delete_ttl(key)?;
+                Ok(None)
+            }
+        }
     }
-    
-    // Add "test" following "bob" relationship
-    if !test_user_id.is_empty() && !bob_user_id.is_empty() {
-        let mut followings: Vec<String> = store.get_json(&followings_key(&test_user_id))?.unwrap_or_default();
-        if !followings.contains(&bob_user_id) {
-            followings.push(bob_user_id);
-            store.set_json(&followings_key(&test_user_id), &followings)?;
+
+    /// Delete a value written by `set_json_ttl` along with its expiry marker
+    pub fn delete_ttl(&self, key: &str) -> anyhow::Result<()> {
+        self.delete(key)?;
+        self.delete(&ttl_key(key))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Expiring<T> {
+    expires_at: i64,
+    value: T,
+}
+
+/// Delete every `set_json_ttl` value past its expiry; returns how many were removed
+pub fn sweep_expired(store: &dyn BordStore) -> anyhow::Result<usize> {
+    let now = chrono::Utc::now().timestamp();
+    let mut removed = 0;
+
+    for marker in store.scan(TTL_PREFIX)? {
+        let expires_at = store.get_json::<i64>(&marker)?.unwrap_or_default();
+        if expires_at <= now {
+            store.delete(&marker[TTL_PREFIX.len()..])?;
+            store.delete(&marker)?;
+            removed += 1;
         }
     }
-    
-    store.set_json(USERS_LIST_KEY, &users)?;
-    store.set_json(FEED_KEY, &feed)?;
-    
+    Ok(removed)
+}
+
+/// `helpers::store()?` hands out a shared backend; it can be passed wherever a `&dyn BordStore` is expected
+impl<T: BordStore + ?Sized> BordStore for Rc<T> {
+    fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        (**self).get(key)
+    }
+
+    fn set(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        (**self).set(key, value)
+    }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        (**self).delete(key)
+    }
+
+    fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        (**self).exists(key)
+    }
+
+    fn scan(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        (**self).scan(prefix)
+    }
+
+    fn add_to_set(&self, key: &str, members: &[&str]) -> anyhow::Result<usize> {
+        (**self).add_to_set(key, members)
+    }
+
+    fn remove_from_set(&self, key: &str, members: &[&str]) -> anyhow::Result<usize> {
+        (**self).remove_from_set(key, members)
+    }
+
+    fn set_members(&self, key: &str) -> anyhow::Result<Vec<String>> {
+        (**self).set_members(key)
+    }
+}
+
+impl BordStore for Store {
+    fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(Store::get(self, key)?)
+    }
+
+    fn set(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        Ok(Store::set(self, key, value)?)
+    }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        Ok(Store::delete(self, key)?)
+    }
+
+    fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        Ok(Store::exists(self, key)?)
+    }
+
+    fn scan(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        let mut keys = self.get_keys()?;
+        keys.retain(|k| k.starts_with(prefix));
+        Ok(keys)
+    }
+
+    fn add_to_set(&self, key: &str, members: &[&str]) -> anyhow::Result<usize> {
+        swap_kv_set(key, |current| add_missing(current, members))
+    }
+
+    fn remove_from_set(&self, key: &str, members: &[&str]) -> anyhow::Result<usize> {
+        swap_kv_set(key, |current| remove_present(current, members))
+    }
+
+    fn set_members(&self, key: &str) -> anyhow::Result<Vec<String>> {
+        Ok((self as &dyn BordStore).get_json(key)?.unwrap_or_default())
+    }
+}
+
+/// Append the `members` not in `current` yet; returns how many were added
+fn add_missing(current: &mut Vec<String>, members: &[&str]) -> usize {
+    let before = current.len();
+    for member in members {
+        if !current.iter().any(|m| m == member) {
+            current.push(member.to_string());
+        }
+    }
+    current.len() - before
+}
+
+/// Drop `members` from `current`; returns how many were there
+fn remove_present(current: &mut Vec<String>, members: &[&str]) -> usize {
+    let before = current.len();
+    current.retain(|m| !members.contains(&m.as_str()));
+    before - current.len()
+}
+
+/// Apply `change` to a JSON list of set members under compare-and-swap,
+/// starting over whenever another writer got in first. `change` returns how
+/// many members it added or removed. Spin's `key_value::Store` has no CAS,
+/// so this goes through `wasi:keyvalue` on the same default store.
+fn swap_kv_set(key: &str, change: impl Fn(&mut Vec<String>) -> usize) -> anyhow::Result<usize> {
+    let bucket = wasi_kv::open("default")?;
+    for _ in 0..SET_SWAP_ATTEMPTS {
+        let cas = atomics::Cas::new(&bucket, key)?;
+        let mut members: Vec<String> = match cas.current()? {
+            Some(bytes) => serde_json::from_slice(&bytes)?,
+            None => Vec::new(),
+        };
+        let changed = change(&mut members);
+        if changed == 0 {
+            return Ok(0);
+        }
+        let bytes = serde_json::to_vec(&members)?;
+        if bytes.len() > MAX_KV_VALUE_BYTES {
+            anyhow::bail!("Set {} would outgrow the {} byte value limit", key, MAX_KV_VALUE_BYTES);
+        }
+        match atomics::swap(cas, &bytes) {
+            Ok(()) => return Ok(changed),
+            Err(atomics::CasError::CasFailed(_)) => continue,
+            Err(atomics::CasError::StoreError(e)) => return Err(e.into()),
+        }
+    }
+    anyhow::bail!("Gave up updating set {} after {} conflicting writes", key, SET_SWAP_ATTEMPTS)
+}
+
+/// Shared ID sets (`users_list`, `tokens_list`, each user's sessions) live in
+/// one index key per set, changed only through the backend's atomic set ops.
+/// Concurrent requests can't drop each other's entries the way a plain
+/// read-modify-write of one list does, and listing a set is a single read
+/// rather than a walk over the keyspace.
+pub fn add_member(store: &dyn BordStore, set: &str, member: &str) -> anyhow::Result<()> {
+    add_members(store, set, &[member])
+}
+
+pub fn remove_member(store: &dyn BordStore, set: &str, member: &str) -> anyhow::Result<()> {
+    remove_members(store, set, &[member])
+}
+
+/// Add several members with one atomic write
+pub fn add_members(store: &dyn BordStore, set: &str, members: &[&str]) -> anyhow::Result<()> {
+    if !members.is_empty() {
+        store.add_to_set(&set_key(set), members)?;
+    }
+    Ok(())
+}
+
+/// Remove several members with one atomic write
+pub fn remove_members(store: &dyn BordStore, set: &str, members: &[&str]) -> anyhow::Result<()> {
+    if !members.is_empty() {
+        store.remove_from_set(&set_key(set), members)?;
+    }
     Ok(())
 }
 
-pub fn reset_db_data(store: &Store) -> anyhow::Result<()> {
+/// Members of a set, in no particular order
+pub fn members(store: &dyn BordStore, set: &str) -> anyhow::Result<Vec<String>> {
+    store.set_members(&set_key(set))
+}
+
+/// Drop a whole set
+pub fn delete_set(store: &dyn BordStore, set: &str) -> anyhow::Result<()> {
+    store.delete(&set_key(set))
+}
+
+/// In-memory backend for unit tests
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryStore(std::cell::RefCell<std::collections::HashMap<String, Vec<u8>>>);
+
+#[cfg(test)]
+impl BordStore for MemoryStore {
+    fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self.0.borrow().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        self.0.borrow_mut().insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.0.borrow_mut().remove(key);
+        Ok(())
+    }
+
+    fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        Ok(self.0.borrow().contains_key(key))
+    }
+
+    fn scan(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        Ok(self.0.borrow().keys().filter(|k| k.starts_with(prefix)).cloned().collect())
+    }
+
+    fn add_to_set(&self, key: &str, members: &[&str]) -> anyhow::Result<usize> {
+        let mut current = self.set_members(key)?;
+        let added = add_missing(&mut current, members);
+        self.set(key, &serde_json::to_vec(&current)?)?;
+        Ok(added)
+    }
+
+    fn remove_from_set(&self, key: &str, members: &[&str]) -> anyhow::Result<usize> {
+        let mut current = self.set_members(key)?;
+        let removed = remove_present(&mut current, members);
+        self.set(key, &serde_json::to_vec(&current)?)?;
+        Ok(removed)
+    }
+
+    fn set_members(&self, key: &str) -> anyhow::Result<Vec<String>> {
+        match self.get(key)? {
+            Some(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+pub fn reset_db_data(store: &dyn BordStore) -> anyhow::Result<()> {
     // Clear all data
-    let users: Vec<String> = store.get_json(USERS_LIST_KEY)?.unwrap_or_default();
+    let users = members(store, USERS_LIST_KEY)?;
     
     // Delete all users
     for id in &users {
-        store.delete(&user_key(&id))?;
+        if let Some(user) = store.get_json::<User>(&user_key(id))? {
+            store.delete(&login_failures_key(&format!("user:{}", user.username)))?;
+            for locale in i18n::locales() {
+                store.delete(&profile_page_key(&user.username, locale))?;
+            }
+            store.delete(&username_key(&user.username))?;
+        }
+        store.delete(&last_post_key(id))?;
+        store.delete(&user_key(id))?;
+        delete_set(store, &user_tokens_set(id))?;
     }
+    delete_set(store, USERS_LIST_KEY)?;
     
     // Delete all posts
-    let posts: Vec<String> = store.get_json(FEED_KEY)?.unwrap_or_default();
-    for id in posts {
+    for id in feed_ids(store)? {
         store.delete(&post_key(&id))?;
+        store.delete(&post_source_key(&id))?;
     }
 
     // Delete all followings (iterate through all users to find followings keys)
     for user_id in &users {
         store.delete(&followings_key(user_id))?;
+        store.delete(&followers_key(user_id))?;
+        store.delete(&follower_count_key(user_id))?;
+        store.delete(&following_count_key(user_id))?;
+        store.delete(&post_count_key(user_id))?;
+        delete_set(store, &user_posts_set(user_id))?;
     }
 
-    // Delete all tokens - need to track them, so check tokens_list if it exists
-    let tokens: Vec<String> = store.get_json(TOKENS_LIST_KEY)?.unwrap_or_default();
-    for token in tokens {
-        store.delete(&token_key(&token))?;
+    // Delete all token sessions and denylist entries - tracked by token ID in tokens_list
+    for jti in members(store, TOKENS_LIST_KEY)? {
+        store.delete(&token_key(&jti))?;
+        store.delete(&session_activity_key(&jti))?;
+        store.delete(&revoked_token_key(&jti))?;
     }
+    delete_set(store, TOKENS_LIST_KEY)?;
     
+    clear_held_posts(store)?;
+
     // Delete metadata
-    store.delete(USERS_LIST_KEY)?;
-    store.delete(FEED_KEY)?;
-    store.delete(TOKENS_LIST_KEY)?;
+    clear_feed(store)?;
+    store.delete(INSTANCE_SETTINGS_KEY)?;
+    store.delete(SYSTEM_ACCOUNT_KEY)?;
+    store.delete(SEED_MARKER_KEY)?;
+    // So the next request runs setup again
+    store.delete(BOOT_KEY)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn members_are_kept_per_set() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+
+        add_member(store, USERS_LIST_KEY, "a").unwrap();
+        add_member(store, USERS_LIST_KEY, "b").unwrap();
+        add_member(store, TOKENS_LIST_KEY, "t").unwrap();
+        remove_member(store, USERS_LIST_KEY, "a").unwrap();
+
+        assert_eq!(members(store, USERS_LIST_KEY).unwrap(), ["b"]);
+        assert_eq!(members(store, TOKENS_LIST_KEY).unwrap(), ["t"]);
+        assert!(store.scan(&member_key(USERS_LIST_KEY, "")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn sets_add_and_remove_members_once() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+
+        assert_eq!(store.add_to_set("s", &["a"]).unwrap(), 1);
+        assert_eq!(store.add_to_set("s", &["a", "b", "c"]).unwrap(), 2);
+        assert_eq!(store.remove_from_set("s", &["a", "c", "x"]).unwrap(), 2);
+        assert_eq!(store.remove_from_set("s", &["a"]).unwrap(), 0);
+        assert_eq!(store.set_members("s").unwrap(), ["b"]);
+        assert!(store.set_members("missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn ttl_values_expire_and_get_swept() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+
+        store.set_json_ttl("live", &1, 60).unwrap();
+        store.set_json_ttl("stale", &2, -1).unwrap();
+        store.set_json_ttl("unread", &3, -1).unwrap();
+        store.set_json("legacy", &4).unwrap();
+
+        assert_eq!(store.get_json_ttl::<i32>("live").unwrap(), Some(1));
+        assert_eq!(store.get_json_ttl::<i32>("stale").unwrap(), None);
+        assert!(!store.exists("stale").unwrap());
+        assert_eq!(store.get_json_ttl::<i32>("legacy").unwrap(), None);
+
+        assert_eq!(sweep_expired(store).unwrap(), 1);
+        assert!(!store.exists("unread").unwrap());
+        assert!(store.exists("live").unwrap());
+    }
+}
@@ -1,71 +1,201 @@
+use serde::Serialize;
 use spin_sdk::http::Response;
 use std::fmt;
 
+/// Error responses, sent as RFC 7807 `application/problem+json`. Variants with
+/// a message also carry a stable snake_case `code` (e.g. `post_not_found`)
+/// that clients should branch on instead of the human-readable detail.
 #[derive(Debug)]
 pub enum ApiError {
-    BadRequest(String),
+    BadRequest(&'static str, String),
     Unauthorized,
     Forbidden,
-    NotFound(String),
-    Conflict(String),
+    NotFound(&'static str, String),
+    Conflict(&'static str, String),
+    /// Several request fields failed validation; all of them are reported
+    Invalid(Vec<FieldError>),
+    /// Request body over the size cap
+    PayloadTooLarge,
+    /// Request body that isn't JSON
+    UnsupportedMediaType,
+    /// Seconds until the client may retry
+    TooManyRequests(u64),
+    /// Account temporarily locked; seconds until it unlocks
+    Locked(u64),
     InternalError(String),
+    /// The storage backend can't be reached
+    ServiceUnavailable,
+    /// Required configuration is missing; what to set
+    NotConfigured(&'static str),
 }
 
-impl fmt::Display for ApiError {
+/// `helpers::store()?` failed to open the backend; the errors middleware
+/// answers it with `ApiError::ServiceUnavailable` instead of a 500
+#[derive(Debug)]
+pub struct StoreUnavailable(pub String);
+
+impl fmt::Display for StoreUnavailable {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Storage unavailable: {}", self.0)
+    }
+}
+
+impl std::error::Error for StoreUnavailable {}
+
+/// One invalid field in an `ApiError::Invalid`, listed under `errors`
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &'static str, code: &'static str, message: impl Into<String>) -> Self {
+        FieldError { field, code, message: message.into() }
+    }
+}
+
+impl ApiError {
+    pub fn status(&self) -> u16 {
+        match self {
+            ApiError::BadRequest(..) | ApiError::Invalid(_) => 400,
+            ApiError::Unauthorized => 401,
+            ApiError::Forbidden => 403,
+            ApiError::NotFound(..) => 404,
+            ApiError::Conflict(..) => 409,
+            ApiError::PayloadTooLarge => 413,
+            ApiError::UnsupportedMediaType => 415,
+            ApiError::Locked(_) => 423,
+            ApiError::TooManyRequests(_) => 429,
+            ApiError::InternalError(_) => 500,
+            ApiError::ServiceUnavailable | ApiError::NotConfigured(_) => 503,
+        }
+    }
+
+    /// Machine-readable error code, stable across releases
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(code, _) | ApiError::NotFound(code, _) | ApiError::Conflict(code, _) => code,
+            ApiError::Invalid(_) => "validation_failed",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::Forbidden => "forbidden",
+            ApiError::PayloadTooLarge => "body_too_large",
+            ApiError::UnsupportedMediaType => "unsupported_media_type",
+            ApiError::TooManyRequests(_) => "rate_limited",
+            ApiError::Locked(_) => "account_locked",
+            ApiError::InternalError(_) => "internal_error",
+            ApiError::ServiceUnavailable => "service_unavailable",
+            ApiError::NotConfigured(_) => "not_configured",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(..) | ApiError::Invalid(_) => "Bad Request",
+            ApiError::Unauthorized => "Unauthorized",
+            ApiError::Forbidden => "Forbidden",
+            ApiError::NotFound(..) => "Not Found",
+            ApiError::Conflict(..) => "Conflict",
+            ApiError::PayloadTooLarge => "Payload Too Large",
+            ApiError::UnsupportedMediaType => "Unsupported Media Type",
+            ApiError::Locked(_) => "Locked",
+            ApiError::TooManyRequests(_) => "Too Many Requests",
+            ApiError::InternalError(_) => "Internal Server Error",
+            ApiError::ServiceUnavailable | ApiError::NotConfigured(_) => "Service Unavailable",
+        }
+    }
+
+    fn detail(&self) -> String {
         match self {
-            ApiError::BadRequest(msg) => write!(f, "Bad Request: {}", msg),
-            ApiError::Unauthorized => write!(f, "Unauthorized"),
-            ApiError::Forbidden => write!(f, "Forbidden"),
-            ApiError::NotFound(msg) => write!(f, "Not Found: {}", msg),
-            ApiError::Conflict(msg) => write!(f, "Conflict: {}", msg),
-            ApiError::InternalError(msg) => write!(f, "Internal Error: {}", msg),
+            ApiError::BadRequest(_, msg) | ApiError::NotFound(_, msg) | ApiError::Conflict(_, msg) => msg.clone(),
+            ApiError::InternalError(msg) => msg.clone(),
+            ApiError::Invalid(_) => "Some fields are invalid".to_string(),
+            ApiError::Unauthorized => "Unauthorized".to_string(),
+            ApiError::Forbidden => "Forbidden".to_string(),
+            ApiError::PayloadTooLarge => "Request body too large".to_string(),
+            ApiError::UnsupportedMediaType => "Expected application/json".to_string(),
+            ApiError::TooManyRequests(_) => "Too many requests".to_string(),
+            ApiError::Locked(_) => "Account temporarily locked".to_string(),
+            ApiError::ServiceUnavailable => "Storage is temporarily unavailable, try again shortly".to_string(),
+            ApiError::NotConfigured(what) => what.to_string(),
         }
     }
+
+    /// The problem document sent as the response body
+    pub fn problem(&self) -> serde_json::Value {
+        let mut body = serde_json::json!({
+            "type": "about:blank",
+            "title": self.title(),
+            "status": self.status(),
+            "code": self.code(),
+            "detail": self.detail(),
+        });
+        match self {
+            ApiError::TooManyRequests(secs) | ApiError::Locked(secs) => body["retry_after"] = (*secs).into(),
+            ApiError::Invalid(fields) => body["errors"] = serde_json::json!(fields),
+            _ => {}
+        }
+        body
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.title(), self.code(), self.detail())
+    }
 }
 
 impl From<ApiError> for Response {
     fn from(err: ApiError) -> Self {
-        match err {
-            ApiError::BadRequest(msg) => Response::builder()
-                .status(400)
-                .header("Content-Type", "application/json")
-                .body(serde_json::to_vec(&serde_json::json!({"error": msg})).unwrap())
-                .build(),
-            ApiError::Unauthorized => Response::builder()
-                .status(401)
-                .header("Content-Type", "application/json")
-                .body(serde_json::to_vec(&serde_json::json!({"error": "Unauthorized"})).unwrap())
-                .build(),
-            ApiError::Forbidden => Response::builder()
-                .status(403)
-                .header("Content-Type", "application/json")
-                .body(serde_json::to_vec(&serde_json::json!({"error": "Forbidden"})).unwrap())
-                .build(),
-            ApiError::NotFound(msg) => Response::builder()
-                .status(404)
-                .header("Content-Type", "application/json")
-                .body(serde_json::to_vec(&serde_json::json!({"error": msg})).unwrap())
-                .build(),
-            ApiError::Conflict(msg) => Response::builder()
-                .status(409)
-                .header("Content-Type", "application/json")
-                .body(serde_json::to_vec(&serde_json::json!({"error": msg})).unwrap())
-                .build(),
-            ApiError::InternalError(msg) => Response::builder()
-                .status(500)
-                .header("Content-Type", "application/json")
-                .body(serde_json::to_vec(&serde_json::json!({"error": msg})).unwrap())
-                .build(),
+        let mut builder = Response::builder();
+        builder
+            .status(err.status())
+            .header("Content-Type", "application/problem+json");
+        if let ApiError::TooManyRequests(secs) | ApiError::Locked(secs) = &err {
+            builder.header("Retry-After", secs.to_string());
         }
+        builder.body(serde_json::to_vec(&err.problem()).unwrap()).build()
     }
 }
 
 impl std::error::Error for ApiError {}
 
+/// Whether a response carries a problem document (see `ApiError::problem`)
+pub fn is_problem(resp: &Response) -> bool {
+    resp.header("Content-Type")
+        .and_then(|h| h.as_str())
+        .is_some_and(|ct| ct.starts_with("application/problem+json"))
+}
+
 // Implement conversion from anyhow::Error to ApiError for internal errors
 impl From<anyhow::Error> for ApiError {
     fn from(err: anyhow::Error) -> Self {
+        if err.downcast_ref::<StoreUnavailable>().is_some() {
+            return ApiError::ServiceUnavailable;
+        }
         ApiError::InternalError(err.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn problem_documents_carry_code_and_fields() {
+        let not_found = ApiError::NotFound("post_not_found", "Post not found".to_string()).problem();
+        assert_eq!(not_found["status"], 404);
+        assert_eq!(not_found["code"], "post_not_found");
+        assert_eq!(not_found["detail"], "Post not found");
+
+        let invalid = ApiError::Invalid(vec![
+            FieldError::new("url", "url_invalid", "URL must start with http:// or https://"),
+            FieldError::new("events", "events_required", "At least one event is required"),
+        ]).problem();
+        assert_eq!(invalid["code"], "validation_failed");
+        assert_eq!(invalid["errors"][1]["field"], "events");
+
+        assert_eq!(ApiError::TooManyRequests(7).problem()["retry_after"], 7);
+    }
+}
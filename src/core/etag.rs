@@ -0,0 +1,59 @@
+use spin_sdk::http::{Request, Response};
+use sha2::{Digest, Sha256};
+
+/// Weak validator for a response body: the same bytes always get the same tag
+pub fn weak_etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    let hex: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("W/\"{}\"", hex)
+}
+
+/// Whether an `If-None-Match` header value names `etag`, using weak comparison
+pub fn none_match(header: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    header.trim() == "*" || header.split(',').any(|tag| opaque(tag) == opaque(etag))
+}
+
+/// A 200 JSON response tagged with an ETag, or an empty 304 when the client
+/// already holds this exact body. `no-cache` makes browsers revalidate each poll.
+pub fn json_with_etag(req: &Request, body: Vec<u8>) -> Response {
+    let etag = weak_etag(&body);
+    let fresh = req.header("If-None-Match")
+        .and_then(|h| h.as_str())
+        .is_some_and(|header| none_match(header, &etag));
+
+    if fresh {
+        return Response::builder()
+            .status(304)
+            .header("ETag", etag)
+            .header("Cache-Control", "no-cache")
+            .build();
+    }
+
+    Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .header("ETag", etag)
+        .header("Cache-Control", "no-cache")
+        .body(body)
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn if_none_match_uses_weak_comparison() {
+        let etag = weak_etag(b"[]");
+        assert_eq!(etag, weak_etag(b"[]"));
+        assert_ne!(etag, weak_etag(b"[{}]"));
+
+        let strong = etag.trim_start_matches("W/");
+        assert!(none_match(&etag, &etag));
+        assert!(none_match(strong, &etag));
+        assert!(none_match(&format!("\"other\", {}", etag), &etag));
+        assert!(none_match("*", &etag));
+        assert!(!none_match("\"other\"", &etag));
+    }
+}
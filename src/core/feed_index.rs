@@ -0,0 +1,308 @@
+//! The global feed is stored as day buckets (the `feed:{YYYY-MM-DD}` ID sets)
+//! listed in the `feed_days` set, plus the small `feed_journal` set of posts
+//! not bucketed yet. Every member is `{publish micros:020}:{post_id}`, so
+//! members sorted newest first are in publish order. All writes are atomic
+//! set adds and removes, so concurrent publishes and compactions can't drop
+//! each other's entries. Publishing only adds to the journal; the publisher
+//! that takes it to FEED_JOURNAL_COMPACT_AT entries folds it into the buckets
+//! with one write per day. Readers only load as many buckets as they need.
+//!
+//! Each author's entries are also kept in their own `user_posts:{id}` set,
+//! so profiles page through that user's posts without walking the feed.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::core::db::{add_members, delete_set, members, remove_members, BordStore};
+use crate::core::chunked::LargeValues;
+use crate::models::models::Post;
+use crate::config::*;
+
+/// Journal or bucket member for a post
+fn entry(micros: i64, post_id: &str) -> String {
+    format!("{:020}:{}", micros, post_id)
+}
+
+fn entry_post_id(entry: &str) -> &str {
+    entry.split_once(':').map(|(_, id)| id).unwrap_or_default()
+}
+
+/// UTC day an entry was published on
+fn entry_day(entry: &str) -> String {
+    let micros = entry
+        .split(':')
+        .next()
+        .and_then(|m| m.parse::<i64>().ok())
+        .unwrap_or_default();
+    chrono::DateTime::from_timestamp_micros(micros)
+        .unwrap_or_default()
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+/// Microseconds since the epoch of a post's `created_at`
+pub fn publish_micros(created_at: &str) -> i64 {
+    chrono::DateTime::parse_from_rfc3339(created_at)
+        .map(|t| t.timestamp_micros())
+        .unwrap_or_default()
+}
+
+/// Members of a feed set, newest first
+fn newest_first(store: &dyn BordStore, set: &str) -> anyhow::Result<Vec<String>> {
+    let mut entries = members(store, set)?;
+    entries.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(entries)
+}
+
+fn feed_days(store: &dyn BordStore) -> anyhow::Result<Vec<String>> {
+    newest_first(store, FEED_DAYS_KEY)
+}
+
+fn post_entry(post: &Post) -> String {
+    entry(publish_micros(&post.created_at), &post.id)
+}
+
+/// Record a newly published post, compacting the journal once it's full
+pub fn append(store: &dyn BordStore, post: &Post) -> anyhow::Result<()> {
+    let entry = post_entry(post);
+    add_members(store, &user_posts_set(&post.user_id), &[&entry])?;
+    add_members(store, FEED_JOURNAL_KEY, &[&entry])?;
+
+    if members(store, FEED_JOURNAL_KEY)?.len() >= FEED_JOURNAL_COMPACT_AT {
+        compact(store)?;
+    }
+    Ok(())
+}
+
+/// Visit post IDs newest first until `visit` returns false. Day buckets are
+/// only loaded once the IDs before them have been visited.
+pub fn for_each_feed_id(
+    store: &dyn BordStore,
+    mut visit: impl FnMut(&str) -> anyhow::Result<bool>,
+) -> anyhow::Result<()> {
+    let journal = newest_first(store, FEED_JOURNAL_KEY)?;
+    for entry in &journal {
+        if !visit(entry_post_id(entry))? {
+            return Ok(());
+        }
+    }
+
+    // A compaction running alongside can leave an entry in both places
+    let journaled: HashSet<&str> = journal.iter().map(|e| entry_post_id(e)).collect();
+    for day in feed_days(store)? {
+        for entry in newest_first(store, &feed_bucket_key(&day))? {
+            let id = entry_post_id(&entry);
+            if journaled.contains(id) {
+                continue;
+            }
+            if !visit(id)? {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// All post IDs in the feed, newest first
+pub fn feed_ids(store: &dyn BordStore) -> anyhow::Result<Vec<String>> {
+    let mut ids = Vec::new();
+    for_each_feed_id(store, |id| {
+        ids.push(id.to_string());
+        Ok(true)
+    })?;
+    Ok(ids)
+}
+
+/// A user's post IDs, newest first
+pub fn user_post_ids(store: &dyn BordStore, user_id: &str) -> anyhow::Result<Vec<String>> {
+    Ok(newest_first(store, &user_posts_set(user_id))?
+        .iter()
+        .map(|e| entry_post_id(e).to_string())
+        .collect())
+}
+
+/// Add an existing post to its author's set (user_posts migration)
+pub fn index_user_post(store: &dyn BordStore, post: &Post) -> anyhow::Result<()> {
+    add_members(store, &user_posts_set(&post.user_id), &[&post_entry(post)])
+}
+
+/// Drop posts from the feed and their authors' sets. Their entries are
+/// rebuilt from `created_at`, so only the journal and the posts' own day
+/// buckets are touched. Emptied days stay listed, since pruning them could
+/// race a compaction filling them again.
+pub fn remove(store: &dyn BordStore, posts: &[Post]) -> anyhow::Result<()> {
+    if posts.is_empty() {
+        return Ok(());
+    }
+
+    let entries: Vec<String> = posts.iter().map(post_entry).collect();
+    let mut by_day: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    let mut by_user: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (post, entry) in posts.iter().zip(&entries) {
+        by_day.entry(entry_day(entry)).or_default().push(entry);
+        by_user.entry(&post.user_id).or_default().push(entry);
+    }
+    for (user_id, user_entries) in &by_user {
+        remove_members(store, &user_posts_set(user_id), user_entries)?;
+    }
+
+    let all: Vec<&str> = entries.iter().map(String::as_str).collect();
+    remove_members(store, FEED_JOURNAL_KEY, &all)?;
+    for (day, day_entries) in &by_day {
+        remove_members(store, &feed_bucket_key(day), day_entries)?;
+    }
+    Ok(())
+}
+
+/// Add entries to their day buckets, one write per day
+fn add_to_buckets(store: &dyn BordStore, entries: &[String]) -> anyhow::Result<()> {
+    let mut by_day: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for entry in entries {
+        by_day.entry(entry_day(entry)).or_default().push(entry);
+    }
+    for (day, day_entries) in &by_day {
+        add_members(store, &feed_bucket_key(day), day_entries)?;
+    }
+    let days: Vec<&str> = by_day.keys().map(String::as_str).collect();
+    add_members(store, FEED_DAYS_KEY, &days)
+}
+
+/// Fold journal entries into their day buckets; returns how many were moved.
+/// Safe to run from several callers at once.
+pub fn compact(store: &dyn BordStore) -> anyhow::Result<usize> {
+    let journal = members(store, FEED_JOURNAL_KEY)?;
+    add_to_buckets(store, &journal)?;
+
+    // Only after the buckets are written, so readers never miss an entry
+    let moved: Vec<&str> = journal.iter().map(String::as_str).collect();
+    remove_members(store, FEED_JOURNAL_KEY, &moved)?;
+    Ok(journal.len())
+}
+
+/// Move an older feed layout's post IDs into the day buckets; `published_at`
+/// gives each post's `created_at`, or `None` for posts that no longer exist
+pub fn bucket_legacy_feed(
+    store: &dyn BordStore,
+    ids: Vec<String>,
+    published_at: impl Fn(&str) -> anyhow::Result<Option<String>>,
+) -> anyhow::Result<()> {
+    let mut entries = Vec::with_capacity(ids.len());
+    for id in ids {
+        if let Some(created_at) = published_at(&id)? {
+            entries.push(entry(publish_micros(&created_at), &id));
+        }
+    }
+    add_to_buckets(store, &entries)
+}
+
+/// Move journal entries kept as one `feed_journal:{micros}:{id}` key each into the journal set
+pub fn index_legacy_journal(store: &dyn BordStore) -> anyhow::Result<()> {
+    for key in store.scan(FEED_JOURNAL_PREFIX)? {
+        add_members(store, FEED_JOURNAL_KEY, &[&key[FEED_JOURNAL_PREFIX.len()..]])?;
+        store.delete(&key)?;
+    }
+    Ok(())
+}
+
+/// Delete the whole feed index (database reset)
+pub fn clear(store: &dyn BordStore) -> anyhow::Result<()> {
+    delete_set(store, FEED_JOURNAL_KEY)?;
+    for day in feed_days(store)? {
+        delete_set(store, &feed_bucket_key(&day))?;
+    }
+    delete_set(store, FEED_DAYS_KEY)?;
+    store.delete_large(FEED_KEY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::MemoryStore;
+
+    const DAY: &str = "1970-01-02T00:00:00Z";
+
+    fn at(seconds: u32) -> String {
+        format!("1970-01-01T00:00:{:02}Z", seconds)
+    }
+
+    fn post(id: &str, created_at: &str) -> Post {
+        Post {
+            id: id.to_string(),
+            user_id: "u".to_string(),
+            content: String::new(),
+            created_at: created_at.to_string(),
+            updated_at: None,
+            hidden: false,
+            render_version: 0,
+            moderation: None,
+        }
+    }
+
+    #[test]
+    fn journal_entries_read_newest_first_and_compact_into_days() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+
+        append(store, &post("a", &at(1))).unwrap();
+        append(store, &post("b", &at(2))).unwrap();
+        append(store, &post("c", DAY)).unwrap();
+        assert_eq!(feed_ids(store).unwrap(), ["c", "b", "a"]);
+
+        assert_eq!(compact(store).unwrap(), 3);
+        assert!(members(store, FEED_JOURNAL_KEY).unwrap().is_empty());
+        assert_eq!(feed_days(store).unwrap(), ["1970-01-02", "1970-01-01"]);
+        assert_eq!(feed_ids(store).unwrap(), ["c", "b", "a"]);
+
+        append(store, &post("d", DAY)).unwrap();
+        let posts: Vec<Post> = ["d", "c"]
+            .iter()
+            .map(|id| post(id, DAY))
+            .collect();
+        remove(store, &posts).unwrap();
+        assert_eq!(feed_ids(store).unwrap(), ["b", "a"]);
+        assert_eq!(user_post_ids(store, "u").unwrap(), ["b", "a"]);
+    }
+
+    #[test]
+    fn publishing_compacts_a_full_journal() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+
+        for n in 0..FEED_JOURNAL_COMPACT_AT {
+            append(store, &post(&format!("p{}", n), &at(1))).unwrap();
+        }
+        assert!(members(store, FEED_JOURNAL_KEY).unwrap().is_empty());
+        assert_eq!(feed_ids(store).unwrap().len(), FEED_JOURNAL_COMPACT_AT);
+    }
+
+    #[test]
+    fn removing_only_reads_the_posts_own_day() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+        append(store, &post("new", DAY)).unwrap();
+        append(store, &post("old", &at(0))).unwrap();
+        compact(store).unwrap();
+
+        // An unreadable other day doesn't matter to the removal
+        store.set(&set_key(&feed_bucket_key("1970-01-01")), b"not json").unwrap();
+        remove(store, &[post("new", DAY)]).unwrap();
+        assert!(members(store, &feed_bucket_key("1970-01-02")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn readers_stop_before_loading_older_days() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+        append(store, &post("new", DAY)).unwrap();
+        append(store, &post("old", &at(0))).unwrap();
+        compact(store).unwrap();
+
+        // Corrupt the older bucket: a reader that stops early never notices
+        store.set(&set_key(&feed_bucket_key("1970-01-01")), b"not json").unwrap();
+        let mut seen = Vec::new();
+        for_each_feed_id(store, |id| {
+            seen.push(id.to_string());
+            Ok(false)
+        }).unwrap();
+        assert_eq!(seen, ["new"]);
+    }
+}
@@ -1,13 +1,38 @@
-use spin_sdk::http::Response;
+use spin_sdk::http::{Fields, Method, OutgoingBody, OutgoingRequest, Response, Scheme};
+use spin_sdk::wit::wasi::http0_2_0::outgoing_handler;
+use spin_sdk::wit::wasi::http0_2_0::types::RequestOptions;
 use spin_sdk::key_value::Store;
+use crate::core::db::BordStore;
+use crate::core::redis_store::RedisStore;
+use crate::config::redis_url;
 use argon2::{Argon2, PasswordHasher, PasswordVerifier};
 use argon2::password_hash::SaltString;
 use rand::rngs::OsRng;
 use uuid::Uuid;
-use crate::core::errors::ApiError;
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::core::errors::{ApiError, StoreUnavailable};
 
-pub fn store() -> Store {
-    Store::open_default().expect("KV store must exist")
+thread_local! {
+    static STORE: RefCell<Option<Rc<dyn BordStore>>> = const { RefCell::new(None) };
+}
+
+/// The configured backend: Redis when `BORD_REDIS_URL` is set, else Spin's default KV store.
+/// Opened on first use and shared for the rest of the request. If it can't be
+/// opened the error is a `StoreUnavailable`, which the errors middleware answers with a 503.
+pub fn store() -> anyhow::Result<Rc<dyn BordStore>> {
+    STORE.with(|cached| {
+        if let Some(store) = cached.borrow().as_ref() {
+            return Ok(store.clone());
+        }
+
+        let store: Rc<dyn BordStore> = match redis_url() {
+            Some(url) => Rc::new(RedisStore::open(&url).map_err(|e| StoreUnavailable(format!("Redis: {:#}", e)))?),
+            None => Rc::new(Store::open_default().map_err(|e| StoreUnavailable(format!("KV store: {}", e)))?),
+        };
+        *cached.borrow_mut() = Some(store.clone());
+        Ok(store)
+    })
 }
 
 pub fn now_iso() -> String {
@@ -44,3 +69,51 @@ pub fn verify_password(password: &str, hash: &str) -> bool {
 pub fn validate_uuid(id: &str) -> bool {
     Uuid::parse_str(id).is_ok()
 }
+
+/// POST `body` and return the response status, waiting at most `timeout_ms` to
+/// connect and as long again for the response. `spin_sdk::http::send` can't
+/// take a timeout, so this goes through `wasi:http` with request options.
+pub fn post_with_timeout(url: &str, headers: &[(&str, String)], body: &[u8], timeout_ms: u64) -> anyhow::Result<u16> {
+    let (scheme, rest) = url.split_once("://").ok_or_else(|| anyhow::anyhow!("Invalid URL {}", url))?;
+    let rest = rest.split('#').next().unwrap_or_default();
+    let (authority, path) = match rest.find(['/', '?']) {
+        Some(i) => (&rest[..i], rest[i..].to_string()),
+        None => (rest, "/".to_string()),
+    };
+    let path = if path.starts_with('?') { format!("/{}", path) } else { path };
+
+    let fields: Vec<(String, Vec<u8>)> = headers
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.clone().into_bytes()))
+        .collect();
+    let request = OutgoingRequest::new(Fields::from_list(&fields)?);
+    let scheme = if scheme.eq_ignore_ascii_case("https") { Scheme::Https } else { Scheme::Http };
+    let invalid = |part: &str| anyhow::anyhow!("Invalid {} in {}", part, url);
+    request.set_method(&Method::Post).map_err(|_| invalid("method"))?;
+    request.set_scheme(Some(&scheme)).map_err(|_| invalid("scheme"))?;
+    request.set_authority(Some(authority)).map_err(|_| invalid("host"))?;
+    request.set_path_with_query(Some(&path)).map_err(|_| invalid("path"))?;
+
+    let timeout = Some(timeout_ms * 1_000_000);
+    let options = RequestOptions::new();
+    options.set_connect_timeout(timeout).map_err(|_| anyhow::anyhow!("Connect timeout not supported"))?;
+    options.set_first_byte_timeout(timeout).map_err(|_| anyhow::anyhow!("Response timeout not supported"))?;
+
+    let outgoing = request.body().map_err(|_| anyhow::anyhow!("Request body already taken"))?;
+    let response = outgoing_handler::handle(request, Some(options))?;
+    {
+        let stream = outgoing.write().map_err(|_| anyhow::anyhow!("Request body not writable"))?;
+        // One blocking write takes at most 4096 bytes
+        for chunk in body.chunks(4096) {
+            stream.blocking_write_and_flush(chunk)?;
+        }
+    }
+    OutgoingBody::finish(outgoing, None)?;
+
+    response.subscribe().block();
+    match response.get() {
+        Some(Ok(Ok(incoming))) => Ok(incoming.status()),
+        Some(Ok(Err(e))) => Err(e.into()),
+        _ => anyhow::bail!("No response from {}", url),
+    }
+}
@@ -0,0 +1,186 @@
+//! Message catalogs for server-rendered pages and error details. Each locale
+//! is a flat JSON object in `locales/`, e.g. `"post.views": "{count} views"`;
+//! keys a locale lacks fall back to the default locale.
+
+use spin_sdk::http::{Request, Response};
+use rust_embed::RustEmbed;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+use crate::core::etag::json_with_etag;
+use crate::core::query_params::{parse_query_params, get_string};
+use crate::config::DEFAULT_LOCALE;
+
+#[derive(RustEmbed)]
+#[folder = "locales"]
+struct Catalogs;
+
+type Catalog = BTreeMap<String, String>;
+
+fn parse_catalog(locale: &str) -> Catalog {
+    Catalogs::get(&format!("{}.json", locale))
+        .and_then(|file| serde_json::from_slice(&file.data).ok())
+        .unwrap_or_default()
+}
+
+/// Every embedded catalog, each already merged over the default locale
+fn catalogs() -> &'static HashMap<String, Catalog> {
+    static CATALOGS: OnceLock<HashMap<String, Catalog>> = OnceLock::new();
+    CATALOGS.get_or_init(|| {
+        let fallback = parse_catalog(DEFAULT_LOCALE);
+        Catalogs::iter()
+            .filter_map(|name| name.strip_suffix(".json").map(str::to_string))
+            .map(|locale| {
+                let mut catalog = fallback.clone();
+                catalog.extend(parse_catalog(&locale));
+                (locale, catalog)
+            })
+            .collect()
+    })
+}
+
+/// Locales with a catalog, sorted
+pub fn locales() -> Vec<&'static str> {
+    let mut locales: Vec<&str> = catalogs().keys().map(String::as_str).collect();
+    locales.sort();
+    locales
+}
+
+/// The catalog for `locale`, or the default one
+pub fn messages(locale: &str) -> &'static Catalog {
+    let catalogs = catalogs();
+    catalogs.get(locale)
+        .or_else(|| catalogs.get(DEFAULT_LOCALE))
+        .expect("the default locale has a catalog")
+}
+
+/// The best available locale for an `Accept-Language` header, e.g.
+/// `ro-RO,ro;q=0.9,en;q=0.8`; region tags match their language's catalog
+pub fn negotiate(accept_language: Option<&str>) -> &'static str {
+    let mut ranges: Vec<(&str, f32)> = accept_language.unwrap_or_default()
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let tag = parts.next()?.trim();
+            let q = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(1.0, |q| q.trim().parse().unwrap_or(0.0));
+            (!tag.is_empty() && q > 0.0).then_some((tag, q))
+        })
+        .collect();
+    // Stable, so equal weights keep the client's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let catalogs = catalogs();
+    for (tag, _) in ranges {
+        let language = tag.split('-').next().unwrap_or(tag).to_ascii_lowercase();
+        if let Some((locale, _)) = catalogs.get_key_value(&language) {
+            return locale;
+        }
+    }
+    DEFAULT_LOCALE
+}
+
+/// The locale a request's pages and errors are rendered in
+pub fn request_locale(req: &Request) -> &'static str {
+    negotiate(req.header("Accept-Language").and_then(|h| h.as_str()))
+}
+
+/// Fill `{name}` placeholders in a message
+pub fn interpolate(message: &str, args: &[(&str, String)]) -> String {
+    args.iter().fold(message.to_string(), |message, (name, value)| {
+        message.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+/// `key` in `locale` with its placeholders filled; `None` if no catalog has it
+pub fn translate(locale: &str, key: &str, args: &[(&str, String)]) -> Option<String> {
+    messages(locale).get(key).map(|message| interpolate(message, args))
+}
+
+/// `key` in `locale`, or the key itself when no catalog has it
+pub fn text(locale: &str, key: &str, args: &[(&str, String)]) -> String {
+    translate(locale, key, args).unwrap_or_else(|| key.to_string())
+}
+
+/// Replace the English title and details of a problem document with their
+/// `status.*` and `error.*` translations, where the catalog has them.
+/// Numbers in the document (e.g. `retry_after`) can be used as placeholders.
+pub fn localize_problem(locale: &str, problem: &mut serde_json::Value) {
+    let numbers: Vec<(String, String)> = problem.as_object()
+        .map(|fields| fields.iter()
+            .filter(|(_, v)| v.is_number())
+            .map(|(k, v)| (k.clone(), v.to_string()))
+            .collect())
+        .unwrap_or_default();
+    let args: Vec<(&str, String)> = numbers.iter().map(|(k, v)| (k.as_str(), v.clone())).collect();
+    let lookup = |key: String| translate(locale, &key, &args);
+
+    if let Some(title) = lookup(format!("status.{}", problem["status"])) {
+        problem["title"] = title.into();
+    }
+    if let Some(detail) = problem["code"].as_str().and_then(|code| lookup(format!("error.{}", code))) {
+        problem["detail"] = detail.into();
+    }
+    if let Some(errors) = problem.get_mut("errors").and_then(|e| e.as_array_mut()) {
+        for error in errors {
+            if let Some(message) = error["code"].as_str().and_then(|code| lookup(format!("error.{}", code))) {
+                error["message"] = message.into();
+            }
+        }
+    }
+}
+
+/// `GET /i18n?locale=ro`: the merged catalog for the frontend, in the requested
+/// locale or else the one `Accept-Language` picks
+pub fn get_catalog(req: Request) -> anyhow::Result<Response> {
+    let locale = get_string(&parse_query_params(req.uri()), "locale", None)
+        .and_then(|locale| catalogs().get_key_value(&locale).map(|(locale, _)| locale.as_str()))
+        .unwrap_or_else(|| request_locale(&req));
+    let body = serde_json::to_vec(&serde_json::json!({
+        "locale": locale,
+        "locales": locales(),
+        "messages": messages(locale),
+    }))?;
+
+    let mut resp = json_with_etag(&req, body);
+    resp.set_header("content-language", locale);
+    resp.set_header("vary", "Accept-Language");
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_language_picks_the_best_catalog() {
+        assert_eq!(negotiate(None), "en");
+        assert_eq!(negotiate(Some("ro-RO,ro;q=0.9,en;q=0.8")), "ro");
+        assert_eq!(negotiate(Some("fr, en;q=0.5, ro;q=0.7")), "ro");
+        assert_eq!(negotiate(Some("ro;q=0, en")), "en");
+        assert_eq!(negotiate(Some("de-DE")), "en");
+    }
+
+    #[test]
+    fn every_catalog_has_the_default_keys() {
+        let default = messages(DEFAULT_LOCALE);
+        for locale in locales() {
+            for key in default.keys() {
+                assert!(parse_catalog(locale).contains_key(key), "{} is missing {}", locale, key);
+            }
+        }
+    }
+
+    #[test]
+    fn problems_are_translated_by_code() {
+        let mut problem = crate::core::errors::ApiError::TooManyRequests(7).problem();
+        localize_problem("ro", &mut problem);
+        assert_eq!(problem["title"], "Prea multe cereri");
+        assert_eq!(problem["detail"], "Prea multe cereri, încearcă din nou în 7 s");
+
+        let mut problem = crate::core::errors::ApiError::NotFound("no_such_code", "Gone".to_string()).problem();
+        localize_problem("ro", &mut problem);
+        assert_eq!(problem["detail"], "Gone");
+        assert_eq!(interpolate("{a} and {b}", &[("a", "1".to_string()), ("b", "2".to_string())]), "1 and 2");
+    }
+}
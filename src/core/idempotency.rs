@@ -0,0 +1,217 @@
+use serde::{Serialize, Deserialize};
+use spin_sdk::http::{Request, Response};
+use sha2::{Digest, Sha256};
+use crate::core::db::{add_member, delete_set, members, remove_members, BordStore};
+use crate::core::helpers::store;
+use crate::core::errors::ApiError;
+use crate::core::rate_limit::client_ip;
+use crate::core::router::strip_mount;
+use crate::auth::validate_token;
+use crate::config::*;
+
+/// First successful response to an `Idempotency-Key`, replayed on retries
+#[derive(Serialize, Deserialize)]
+struct StoredResponse {
+    /// Hash of the request body, so a key can't be reused for a different request
+    fingerprint: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+fn fingerprint(body: &[u8]) -> String {
+    Sha256::digest(body).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let mut builder = Response::builder();
+    builder.status(stored.status);
+    for (name, value) in &stored.headers {
+        builder.header(name.as_str(), value.as_str());
+    }
+    builder.header("Idempotent-Replayed", "true");
+    builder.body(stored.body).build()
+}
+
+fn load(store: &dyn BordStore, key: &str) -> anyhow::Result<Option<StoredResponse>> {
+    store.get_json_ttl(key)
+}
+
+/// The request's `Idempotency-Key`, trimmed
+fn header_key(req: &Request) -> Option<String> {
+    req.header(IDEMPOTENCY_HEADER).and_then(|h| h.as_str()).map(|k| k.trim().to_string())
+}
+
+fn key_valid(idempotency_key: &str) -> bool {
+    !idempotency_key.is_empty() && idempotency_key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH
+}
+
+/// Where the response to an `Idempotency-Key` is stored, scoped to the route
+/// and the caller, or their IP before they have an account
+fn storage_key(req: &Request, caller: Option<&str>, idempotency_key: &str) -> String {
+    let caller = caller.map(str::to_string).unwrap_or_else(|| client_ip(req));
+    let route = strip_mount(req.path(), API_PREFIX).unwrap_or(req.path());
+    idempotency_key_for(route, &caller, idempotency_key)
+}
+
+/// Run `handler` once per `Idempotency-Key`. A retry with the same key gets
+/// the stored response instead of e.g. a duplicate post. Failed requests
+/// aren't stored, so they can be retried with the same key.
+pub fn run_once(req: Request, handler: impl FnOnce(Request) -> anyhow::Result<Response>) -> anyhow::Result<Response> {
+    let idempotency_key = match header_key(&req) {
+        Some(k) => k,
+        None => return handler(req),
+    };
+    if !key_valid(&idempotency_key) {
+        return Ok(ApiError::BadRequest("invalid_idempotency_key", format!("{} must be 1-{} characters", IDEMPOTENCY_HEADER, MAX_IDEMPOTENCY_KEY_LENGTH)).into());
+    }
+
+    let user_id = validate_token(&req);
+    let key = storage_key(&req, user_id.as_deref(), &idempotency_key);
+    run_stored(&store()?, &key, user_id.as_deref(), req, handler)
+}
+
+/// Count a request's stored response as `user_id`'s, for responses to callers
+/// that had no account yet, e.g. signups
+pub fn attribute_to(store: &dyn BordStore, req: &Request, user_id: &str) -> anyhow::Result<()> {
+    match header_key(req).filter(|k| key_valid(k)) {
+        Some(idempotency_key) => remember(store, user_id, &storage_key(req, None, &idempotency_key)),
+        None => Ok(()),
+    }
+}
+
+/// Add a stored response to its user's set, dropping expired ones once it grows
+fn remember(store: &dyn BordStore, user_id: &str, key: &str) -> anyhow::Result<()> {
+    let set = user_idempotency_set(user_id);
+    add_member(store, &set, key)?;
+
+    let keys = members(store, &set)?;
+    if keys.len() > USER_IDEMPOTENCY_PRUNE_AT {
+        let mut expired = Vec::new();
+        for stored in &keys {
+            if load(store, stored)?.is_none() {
+                expired.push(stored.as_str());
+            }
+        }
+        remove_members(store, &set, &expired)?;
+    }
+    Ok(())
+}
+
+/// Delete every stored response that belongs to a user (account deletion)
+pub fn forget_user(store: &dyn BordStore, user_id: &str) -> anyhow::Result<()> {
+    let set = user_idempotency_set(user_id);
+    for key in members(store, &set)? {
+        store.delete_ttl(&key)?;
+    }
+    delete_set(store, &set)
+}
+
+/// `run_once` for a resolved storage key, owned by `user_id` if signed in
+fn run_stored(store: &dyn BordStore, key: &str, user_id: Option<&str>, req: Request, handler: impl FnOnce(Request) -> anyhow::Result<Response>) -> anyhow::Result<Response> {
+    let request_fingerprint = fingerprint(req.body());
+    if let Some(stored) = load(store, key)? {
+        if stored.fingerprint != request_fingerprint {
+            return Ok(ApiError::Conflict("idempotency_key_reused", format!("{} was already used for a different request", IDEMPOTENCY_HEADER)).into());
+        }
+        return Ok(replay(stored));
+    }
+
+    let resp = handler(req)?;
+    if (200..300).contains(resp.status()) {
+        let stored = StoredResponse {
+            fingerprint: request_fingerprint,
+            status: *resp.status(),
+            headers: resp.headers()
+                .filter_map(|(name, value)| Some((name.to_string(), value.as_str()?.to_string())))
+                .collect(),
+            body: resp.body().to_vec(),
+        };
+        store.set_json_ttl(key, &stored, IDEMPOTENCY_KEY_TTL_HOURS * 3600)?;
+        if let Some(user_id) = user_id {
+            remember(store, user_id, key)?;
+        }
+    }
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::MemoryStore;
+    use spin_sdk::http::Method;
+    use std::cell::Cell;
+
+    fn post(body: &str) -> Request {
+        Request::builder().method(Method::Post).uri("/posts").body(body.as_bytes().to_vec()).build()
+    }
+
+    /// Handler answering `status`, counting how often it ran
+    fn handler(status: u16, calls: &Cell<u32>) -> impl FnOnce(Request) -> anyhow::Result<Response> + '_ {
+        move |_| {
+            calls.set(calls.get() + 1);
+            Ok(Response::builder().status(status).header("Content-Type", "application/json").body(b"{\"id\":1}".to_vec()).build())
+        }
+    }
+
+    #[test]
+    fn same_key_and_body_replays_the_first_response() {
+        let backend = MemoryStore::default();
+        let calls = Cell::new(0);
+
+        let first = run_stored(&backend, "idem", None, post("{\"content\":\"hi\"}"), handler(201, &calls)).unwrap();
+        let retry = run_stored(&backend, "idem", None, post("{\"content\":\"hi\"}"), handler(201, &calls)).unwrap();
+
+        assert_eq!(calls.get(), 1);
+        assert_eq!(*retry.status(), 201);
+        assert_eq!(retry.body(), first.body());
+        assert_eq!(retry.header("Idempotent-Replayed").and_then(|h| h.as_str()), Some("true"));
+        assert!(first.header("Idempotent-Replayed").is_none());
+    }
+
+    #[test]
+    fn same_key_with_another_body_conflicts() {
+        let backend = MemoryStore::default();
+        let calls = Cell::new(0);
+
+        run_stored(&backend, "idem", None, post("{\"content\":\"hi\"}"), handler(201, &calls)).unwrap();
+        let reused = run_stored(&backend, "idem", None, post("{\"content\":\"other\"}"), handler(201, &calls)).unwrap();
+
+        assert_eq!(calls.get(), 1);
+        assert_eq!(*reused.status(), 409);
+    }
+
+    #[test]
+    fn failed_responses_are_not_stored() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+        let calls = Cell::new(0);
+
+        let failed = run_stored(store, "idem", None, post("{}"), handler(400, &calls)).unwrap();
+        assert_eq!(*failed.status(), 400);
+        assert!(load(store, "idem").unwrap().is_none());
+
+        let retried = run_stored(store, "idem", None, post("{}"), handler(201, &calls)).unwrap();
+        assert_eq!(calls.get(), 2);
+        assert_eq!(*retried.status(), 201);
+    }
+
+    #[test]
+    fn stored_responses_round_trip() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+        let stored = StoredResponse {
+            fingerprint: fingerprint(b"{\"content\":\"hi\"}"),
+            status: 201,
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: b"{}".to_vec(),
+        };
+        store.set_json_ttl("idem", &stored, 60).unwrap();
+
+        let loaded = load(store, "idem").unwrap().unwrap();
+        assert_eq!(loaded.status, 201);
+        assert_eq!(loaded.fingerprint, fingerprint(b"{\"content\":\"hi\"}"));
+        assert_ne!(loaded.fingerprint, fingerprint(b"{\"content\":\"other\"}"));
+        assert_eq!(loaded.headers, stored.headers);
+    }
+}
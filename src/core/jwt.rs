@@ -0,0 +1,149 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::{Serialize, Deserialize};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const HEADER: &str = r#"{"alg":"HS256","typ":"JWT"}"#;
+
+#[derive(Serialize, Deserialize)]
+pub struct Claims {
+    /// User ID the token was issued to
+    pub sub: String,
+    /// Token ID, used for revocation and session tracking
+    pub jti: String,
+    /// Issued at (unix seconds)
+    pub iat: i64,
+    /// Expiry (unix seconds)
+    pub exp: i64,
+}
+
+/// Sign claims into a compact HS256 JWT
+pub fn encode(claims: &Claims, secret: &[u8]) -> anyhow::Result<String> {
+    let header = URL_SAFE_NO_PAD.encode(HEADER);
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?);
+    let signing_input = format!("{}.{}", header, payload);
+
+    let mut mac = HmacSha256::new_from_slice(secret)
+        .map_err(|e| anyhow::anyhow!("Invalid JWT secret: {}", e))?;
+    mac.update(signing_input.as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+    Ok(format!("{}.{}", signing_input, signature))
+}
+
+/// Verify signature and expiry, returning the claims if the token is valid
+pub fn decode(token: &str, secret: &[u8]) -> Option<Claims> {
+    let mut parts = token.split('.');
+    let header = parts.next()?;
+    let payload = parts.next()?;
+    let signature = parts.next()?;
+    if parts.next().is_some() {
+        return None;
+    }
+
+    // Only accept the algorithm we sign with
+    let header_json: serde_json::Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header).ok()?).ok()?;
+    if header_json["alg"] != "HS256" {
+        return None;
+    }
+
+    let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+    let mut mac = HmacSha256::new_from_slice(secret).ok()?;
+    mac.update(header.as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&signature).ok()?;
+
+    let claims: Claims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+    if claims.exp <= chrono::Utc::now().timestamp() {
+        return None;
+    }
+
+    Some(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const SECRET: &[u8] = b"test-secret-that-is-long-enough!";
+
+    fn claims(exp_in: i64) -> Claims {
+        let now = chrono::Utc::now().timestamp();
+        Claims { sub: "user-1".to_string(), jti: "jti-1".to_string(), iat: now, exp: now + exp_in }
+    }
+
+    #[test]
+    fn round_trips_with_the_same_secret() {
+        let token = encode(&claims(60), SECRET).unwrap();
+        let decoded = decode(&token, SECRET).expect("token should verify");
+        assert_eq!(decoded.sub, "user-1");
+        assert_eq!(decoded.jti, "jti-1");
+        assert!(decode(&token, b"some-other-secret-of-enough-length").is_none());
+    }
+
+    #[test]
+    fn rejects_tampered_payload_or_signature() {
+        let token = encode(&claims(60), SECRET).unwrap();
+        let parts: Vec<&str> = token.split('.').collect();
+
+        let forged_payload = URL_SAFE_NO_PAD.encode(r#"{"sub":"admin","jti":"x","iat":0,"exp":9999999999}"#);
+        let forged = format!("{}.{}.{}", parts[0], forged_payload, parts[2]);
+        assert!(decode(&forged, SECRET).is_none());
+
+        let mut signature = URL_SAFE_NO_PAD.decode(parts[2]).unwrap();
+        signature[0] ^= 1;
+        let tampered = format!("{}.{}.{}", parts[0], parts[1], URL_SAFE_NO_PAD.encode(signature));
+        assert!(decode(&tampered, SECRET).is_none());
+    }
+
+    #[test]
+    fn rejects_other_algorithms() {
+        let token = encode(&claims(60), SECRET).unwrap();
+        let parts: Vec<&str> = token.split('.').collect();
+
+        let unsigned = format!("{}.{}.", URL_SAFE_NO_PAD.encode(r#"{"alg":"none","typ":"JWT"}"#), parts[1]);
+        assert!(decode(&unsigned, SECRET).is_none());
+
+        // Even with a valid HS256 signature over it, a different alg header is refused
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"HS512","typ":"JWT"}"#);
+        let mut mac = HmacSha256::new_from_slice(SECRET).unwrap();
+        mac.update(format!("{}.{}", header, parts[1]).as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        assert!(decode(&format!("{}.{}.{}", header, parts[1], signature), SECRET).is_none());
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let token = encode(&claims(-1), SECRET).unwrap();
+        assert!(decode(&token, SECRET).is_none());
+    }
+
+    proptest! {
+        #[test]
+        fn decode_never_panics(token in "\\PC*") {
+            decode(&token, SECRET);
+        }
+
+        #[test]
+        fn decode_rejects_mangled_tokens(token in "[A-Za-z0-9_-]{0,40}(\\.[A-Za-z0-9_-]{0,40}){0,4}") {
+            prop_assert!(decode(&token, SECRET).is_none());
+        }
+
+        #[test]
+        fn encode_decode_round_trips(sub in "\\PC{1,40}", jti in "[a-f0-9-]{36}", ttl in 60i64..1_000_000) {
+            let now = chrono::Utc::now().timestamp();
+            let claims = Claims { sub, jti, iat: now, exp: now + ttl };
+            let token = encode(&claims, SECRET).unwrap();
+
+            let decoded = decode(&token, SECRET).expect("freshly signed token should decode");
+            prop_assert_eq!(decoded.sub, claims.sub);
+            prop_assert_eq!(decoded.jti, claims.jti);
+            prop_assert!(decode(&token, b"other-secret").is_none());
+        }
+    }
+}
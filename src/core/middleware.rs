@@ -0,0 +1,192 @@
+//! Cross-cutting request handling, applied to every request in the order
+//! passed to `run`. Each layer can answer early or call `next` and adjust
+//! the response; per-route auth lives in the router (`Router::authed`).
+
+use spin_sdk::http::{Request, Response};
+use crate::core::errors::{is_problem, ApiError, StoreUnavailable};
+use crate::core::{body, i18n, rate_limit};
+use crate::auth::validate_token;
+use crate::config::{cors_origins, request_log_enabled, request_log_sample_rate, request_log_static, DEFAULT_LOCALE};
+use std::time::Instant;
+
+/// The rest of the chain, ending in the router
+pub type Next<'a> = &'a dyn Fn(Request) -> anyhow::Result<Response>;
+pub type Middleware = fn(Request, Next) -> anyhow::Result<Response>;
+
+/// Pass `req` through `layers`, outermost first, then to `endpoint`
+pub fn run(req: Request, layers: &[Middleware], endpoint: Next) -> anyhow::Result<Response> {
+    match layers.split_first() {
+        Some((layer, rest)) => layer(req, &|req| run(req, rest, endpoint)),
+        None => endpoint(req),
+    }
+}
+
+/// Whether a path names a file, like the static assets do
+fn is_asset_path(path: &str) -> bool {
+    path.rsplit('/').next().is_some_and(|name| name.contains('.'))
+}
+
+/// One JSON line per request on stdout, when `BORD_LOG_REQUESTS` is on:
+/// method, path (without the query), final status, latency and the caller's
+/// user ID if a token validates
+pub fn request_log(req: Request, next: Next) -> anyhow::Result<Response> {
+    if !request_log_enabled() || (!request_log_static() && is_asset_path(req.path())) {
+        return next(req);
+    }
+
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+    let user_id = validate_token(&req);
+    let resp = next(req)?;
+
+    let status = *resp.status();
+    if status < 500 && rand::random::<f64>() >= request_log_sample_rate() {
+        return Ok(resp);
+    }
+    println!("{}", serde_json::json!({
+        "ts": chrono::Utc::now().to_rfc3339(),
+        "method": method,
+        "path": path,
+        "status": status,
+        "latency_ms": started.elapsed().as_secs_f64() * 1000.0,
+        "user_id": user_id,
+    }));
+    Ok(resp)
+}
+
+/// Error titles and details in the caller's `Accept-Language`, where the
+/// locale's catalog has them; codes and field names stay as they are
+pub fn localize(req: Request, next: Next) -> anyhow::Result<Response> {
+    let locale = i18n::request_locale(&req);
+    let mut resp = next(req)?;
+    if locale == DEFAULT_LOCALE || !is_problem(&resp) {
+        return Ok(resp);
+    }
+
+    if let Ok(mut problem) = serde_json::from_slice::<serde_json::Value>(resp.body()) {
+        i18n::localize_problem(locale, &mut problem);
+        *resp.body_mut() = serde_json::to_vec(&problem)?;
+        resp.set_header("content-language", locale);
+    }
+    Ok(resp)
+}
+
+/// Turn handler errors (storage failures, unparsable bodies) into a JSON 500,
+/// or a 503 when the store couldn't be opened at all
+pub fn errors(req: Request, next: Next) -> anyhow::Result<Response> {
+    let route = format!("{} {}", req.method(), req.path());
+    match next(req) {
+        Ok(resp) => Ok(resp),
+        Err(e) => {
+            eprintln!("{} failed: {:#}", route, e);
+            if e.downcast_ref::<StoreUnavailable>().is_some() {
+                return Ok(ApiError::ServiceUnavailable.into());
+            }
+            Ok(ApiError::InternalError("Something went wrong".to_string()).into())
+        }
+    }
+}
+
+/// Cross-origin access for the origins in `BORD_CORS_ORIGINS`; no headers otherwise
+pub fn cors(req: Request, next: Next) -> anyhow::Result<Response> {
+    let origin = match req.header("Origin").and_then(|h| h.as_str()) {
+        Some(origin) if cors_origins().iter().any(|o| o == origin || o == "*") => origin.to_string(),
+        _ => return next(req),
+    };
+
+    let is_preflight = req.method().to_string() == "OPTIONS"
+        && req.header("Access-Control-Request-Method").is_some();
+    let mut resp = if is_preflight {
+        Response::builder()
+            .status(204)
+            .header("Access-Control-Allow-Methods", "GET, POST, PUT, DELETE")
+            .header("Access-Control-Allow-Headers", "Authorization, Content-Type, Idempotency-Key, If-None-Match")
+            .header("Access-Control-Max-Age", "600")
+            .build()
+    } else {
+        next(req)?
+    };
+
+    // The builder stores header names lowercased but `set_header` keeps them as
+    // given, so lowercase names replace a header instead of adding a second one
+    resp.set_header("access-control-allow-origin", origin);
+    resp.set_header("access-control-expose-headers", "ETag, Retry-After, Idempotent-Replayed");
+    let vary = match resp.header("Vary").and_then(|h| h.as_str()) {
+        Some(vary) => format!("{}, Origin", vary),
+        None => "Origin".to_string(),
+    };
+    resp.set_header("vary", vary);
+    Ok(resp)
+}
+
+/// 429 once the caller's bucket for a rate-limited route is empty
+pub fn rate_limit(req: Request, next: Next) -> anyhow::Result<Response> {
+    match rate_limit::enforce(&req)? {
+        Some(resp) => Ok(resp),
+        None => next(req),
+    }
+}
+
+/// 413/415/400 for bodies no handler could use
+pub fn body_limit(req: Request, next: Next) -> anyhow::Result<Response> {
+    match body::check_body(&req) {
+        Some(resp) => Ok(resp),
+        None => next(req),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spin_sdk::http::Method;
+
+    fn trail(resp: &Response) -> String {
+        resp.header("X-Trail").and_then(|h| h.as_str()).unwrap_or_default().to_string()
+    }
+
+    fn outer(req: Request, next: Next) -> anyhow::Result<Response> {
+        let mut resp = next(req)?;
+        let trail = format!("{}outer", trail(&resp));
+        resp.set_header("x-trail", trail);
+        Ok(resp)
+    }
+
+    fn inner(req: Request, next: Next) -> anyhow::Result<Response> {
+        let mut resp = next(req)?;
+        let trail = format!("{}inner,", trail(&resp));
+        resp.set_header("x-trail", trail);
+        Ok(resp)
+    }
+
+    fn short_circuit(_: Request, _: Next) -> anyhow::Result<Response> {
+        Ok(Response::builder().status(429).build())
+    }
+
+    fn endpoint(_: Request) -> anyhow::Result<Response> {
+        Ok(Response::builder().status(200).build())
+    }
+
+    fn request() -> Request {
+        Request::builder().method(Method::Get).uri("/feed").build()
+    }
+
+    #[test]
+    fn asset_paths_have_an_extension() {
+        assert!(is_asset_path("/style.1a2b3c4d.css"));
+        assert!(is_asset_path("/favicon.ico"));
+        assert!(!is_asset_path("/api/v1/posts"));
+        assert!(!is_asset_path("/alice"));
+        assert!(!is_asset_path("/.well-known/nodeinfo"));
+    }
+
+    #[test]
+    fn layers_wrap_in_order_and_can_answer_early() {
+        let resp = run(request(), &[outer, inner], &endpoint).unwrap();
+        assert_eq!(trail(&resp), "inner,outer");
+
+        let resp = run(request(), &[outer, short_circuit, inner], &endpoint).unwrap();
+        assert_eq!(*resp.status(), 429);
+        assert_eq!(trail(&resp), "outer");
+    }
+}
@@ -0,0 +1,177 @@
+use crate::core::db::BordStore;
+use std::collections::BTreeMap;
+use crate::models::models::{User, Post, TokenData};
+use crate::core::helpers::now_iso;
+use crate::core::chunked::LargeValues;
+use crate::core::db::{add_member, members};
+use crate::core::feed_index::{bucket_legacy_feed, feed_ids, index_legacy_journal, index_user_post};
+use crate::config::*;
+
+type Migration = fn(&dyn BordStore) -> anyhow::Result<()>;
+
+const MIGRATIONS: &[(&str, Migration)] = &[
+    ("followers_index", build_followers_index),
+    ("username_index", build_username_index),
+    ("split_id_lists", split_id_lists),
+    ("bucket_feed", bucket_feed),
+    ("post_counts", count_posts),
+    ("index_id_sets", index_id_sets),
+    ("index_feed_sets", index_feed_sets),
+    ("index_user_posts", index_user_posts),
+    ("index_held_posts", index_held_posts),
+];
+
+/// Names of every migration this build knows, in the order they run
+pub fn migration_names() -> Vec<String> {
+    MIGRATIONS.iter().map(|(name, _)| name.to_string()).collect()
+}
+
+/// Apply pending one-time data migrations; each is recorded under `migration:{name}` once done
+pub fn run_migrations(store: &dyn BordStore) -> anyhow::Result<()> {
+    for (name, migrate) in MIGRATIONS {
+        let key = migration_key(name);
+        if !store.exists(&key)? {
+            migrate(store)?;
+            store.set_json(&key, &now_iso())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Build the `followers:{id}` reverse index from every user's followings list
+fn build_followers_index(store: &dyn BordStore) -> anyhow::Result<()> {
+    let users: Vec<String> = store.get_large_json(USERS_LIST_KEY)?.unwrap_or_default();
+    let mut followers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for id in &users {
+        let followings: Vec<String> = store.get_json(&followings_key(id))?.unwrap_or_default();
+        for followed in followings {
+            followers.entry(followed).or_default().push(id.clone());
+        }
+    }
+
+    for (user_id, mut ids) in followers {
+        // Keep anything written since, e.g. by a follow that raced the migration
+        let existing: Vec<String> = store.get_json(&followers_key(&user_id))?.unwrap_or_default();
+        for id in existing {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        store.set_json(&followers_key(&user_id), &ids)?;
+    }
+
+    Ok(())
+}
+
+/// Build the `username:{name}` index from the users list
+fn build_username_index(store: &dyn BordStore) -> anyhow::Result<()> {
+    let users: Vec<String> = store.get_large_json(USERS_LIST_KEY)?.unwrap_or_default();
+
+    for id in users {
+        if let Some(user) = store.get_json::<User>(&user_key(&id))? {
+            let key = username_key(&user.username);
+            if !store.exists(&key)? {
+                store.set_json(&key, &user.id)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Move `users_list` and `tokens_list` out of single JSON lists into their ID sets
+fn split_id_lists(store: &dyn BordStore) -> anyhow::Result<()> {
+    for set in [USERS_LIST_KEY, TOKENS_LIST_KEY] {
+        let ids: Vec<String> = store.get_large_json(set)?.unwrap_or_default();
+        for id in ids {
+            add_member(store, set, &id)?;
+        }
+        store.delete_large(set)?;
+    }
+
+    Ok(())
+}
+
+/// Split the single `feed` list into `feed:{day}` buckets by post date
+fn bucket_feed(store: &dyn BordStore) -> anyhow::Result<()> {
+    let ids: Vec<String> = store.get_large_json(FEED_KEY)?.unwrap_or_default();
+    bucket_legacy_feed(store, ids, |id| published_at(store, id))?;
+    store.delete_large(FEED_KEY)
+}
+
+fn published_at(store: &dyn BordStore, id: &str) -> anyhow::Result<Option<String>> {
+    Ok(store.get_json::<Post>(&post_key(id))?.map(|post| post.created_at))
+}
+
+/// Move the feed's JSON day buckets and one-key-per-entry journal into the
+/// feed ID sets, see core::feed_index
+fn index_feed_sets(store: &dyn BordStore) -> anyhow::Result<()> {
+    index_legacy_journal(store)?;
+
+    let days: Vec<String> = store.get_json(FEED_DAYS_KEY)?.unwrap_or_default();
+    for day in days {
+        let key = feed_bucket_key(&day);
+        let ids: Vec<String> = store.get_large_json(&key)?.unwrap_or_default();
+        bucket_legacy_feed(store, ids, |id| published_at(store, id))?;
+        store.delete_large(&key)?;
+    }
+    store.delete(FEED_DAYS_KEY)
+}
+
+/// Fold the one-key-per-ID layout of `users_list` and `tokens_list` into their
+/// set index keys, and build each user's session set from `tokens_list`
+fn index_id_sets(store: &dyn BordStore) -> anyhow::Result<()> {
+    for set in [USERS_LIST_KEY, TOKENS_LIST_KEY] {
+        let prefix = member_key(set, "");
+        for key in store.scan(&prefix)? {
+            add_member(store, set, &key[prefix.len()..])?;
+            store.delete(&key)?;
+        }
+    }
+
+    for jti in members(store, TOKENS_LIST_KEY)? {
+        if let Some(data) = store.get_json::<TokenData>(&token_key(&jti))? {
+            add_member(store, &user_tokens_set(&data.user_id), &jti)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Build each user's `user_posts:{id}` set from the feed
+fn index_user_posts(store: &dyn BordStore) -> anyhow::Result<()> {
+    for id in feed_ids(store)? {
+        if let Some(post) = store.get_json::<Post>(&post_key(&id))? {
+            index_user_post(store, &post)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// List the held posts stored so far in the `held_posts` set
+fn index_held_posts(store: &dyn BordStore) -> anyhow::Result<()> {
+    for key in store.scan(HELD_POST_PREFIX)? {
+        add_member(store, HELD_POSTS_KEY, &key[HELD_POST_PREFIX.len()..])?;
+    }
+
+    Ok(())
+}
+
+/// Fill the `post_count:{id}` counters from the feed, once
+fn count_posts(store: &dyn BordStore) -> anyhow::Result<()> {
+    let mut counts: BTreeMap<String, u64> = BTreeMap::new();
+    for id in feed_ids(store)? {
+        if let Some(post) = store.get_json::<Post>(&post_key(&id))? {
+            *counts.entry(post.user_id).or_default() += 1;
+        }
+    }
+
+    for (user_id, count) in counts {
+        store.set_json(&post_count_key(&user_id), &count)?;
+    }
+
+    Ok(())
+}
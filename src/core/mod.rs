@@ -3,3 +3,22 @@ pub mod helpers;
 pub mod static_server;
 pub mod errors;
 pub mod query_params;
+pub mod jwt;
+pub mod bot_signals;
+pub mod policy;
+pub mod permissions;
+pub mod rate_limit;
+pub mod migrations;
+pub mod boot;
+pub mod chunked;
+pub mod timezone;
+pub mod router;
+pub mod body;
+pub mod feed_index;
+pub mod seed;
+pub mod redis_store;
+pub mod etag;
+pub mod idempotency;
+pub mod middleware;
+pub mod validate;
+pub mod i18n;
@@ -0,0 +1,126 @@
+use spin_sdk::http::Request;
+use crate::core::db::BordStore;
+use crate::models::models::{User, Role};
+use crate::core::helpers::{store, now_iso};
+use crate::core::errors::ApiError;
+use crate::auth::validate_token;
+use crate::users::find_user_id_by_username;
+use crate::config::*;
+
+/// Scoped powers; each one is granted to a minimum role
+#[derive(Clone, Copy)]
+pub enum Permission {
+    /// Hide or delete other users' posts
+    ModeratePosts,
+    /// Issue warnings to users
+    WarnUsers,
+    /// Change roles and other account-level settings
+    ManageAccounts,
+    /// Change instance-wide settings
+    ManageInstance,
+}
+
+impl Permission {
+    fn min_role(&self) -> Role {
+        match self {
+            Permission::ModeratePosts | Permission::WarnUsers => Role::Moderator,
+            Permission::ManageAccounts | Permission::ManageInstance => Role::Admin,
+        }
+    }
+}
+
+/// Give the accounts listed in BORD_ADMIN_USERNAMES the stored admin role. Each
+/// name is promoted once, so an admin can demote it later; names without an
+/// account yet are promoted by `promote_if_listed` when it is registered.
+pub fn promote_listed_admins(store: &dyn BordStore) -> anyhow::Result<()> {
+    promote_once(store, &admin_usernames())
+}
+
+/// Promote a just-registered account if BORD_ADMIN_USERNAMES lists it
+pub fn promote_if_listed(store: &dyn BordStore, username: &str) -> anyhow::Result<()> {
+    let listed: Vec<String> = admin_usernames()
+        .into_iter()
+        .filter(|name| name.eq_ignore_ascii_case(username))
+        .collect();
+    promote_once(store, &listed)
+}
+
+fn promote_once(store: &dyn BordStore, usernames: &[String]) -> anyhow::Result<()> {
+    for username in usernames {
+        let marker = admin_promotion_key(username);
+        if store.exists(&marker)? {
+            continue;
+        }
+        let user_id = match find_user_id_by_username(store, username)? {
+            Some(id) => id,
+            None => continue,
+        };
+        if let Some(mut user) = store.get_json::<User>(&user_key(&user_id))? {
+            user.role = Role::Admin;
+            store.set_json(&user_key(&user_id), &user)?;
+            store.set_json(&marker, &now_iso())?;
+        }
+    }
+    Ok(())
+}
+
+pub fn user_has_permission(user: &User, permission: Permission) -> bool {
+    user.role >= permission.min_role()
+}
+
+/// Check a permission for a user ID (false if the user doesn't exist)
+pub fn has_permission(store: &dyn BordStore, user_id: &str, permission: Permission) -> anyhow::Result<bool> {
+    Ok(store
+        .get_json::<User>(&user_key(user_id))?
+        .map(|user| user_has_permission(&user, permission))
+        .unwrap_or(false))
+}
+
+/// Authenticate the request and require a permission, returning the acting user
+pub fn authorize(req: &Request, permission: Permission) -> Result<User, ApiError> {
+    let user_id = validate_token(req).ok_or(ApiError::Unauthorized)?;
+    let user = store()?
+        .get_json::<User>(&user_key(&user_id))?
+        .ok_or(ApiError::Unauthorized)?;
+
+    if !user_has_permission(&user, permission) {
+        return Err(ApiError::Forbidden);
+    }
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::MemoryStore;
+    use crate::users::register_user;
+
+    #[test]
+    fn listed_admins_are_promoted_only_once() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+        let user = User {
+            id: "u1".to_string(),
+            username: "root".to_string(),
+            password: String::new(),
+            bio: None,
+            created_at: None,
+            role: Role::User,
+            timezone: None,
+            email: None,
+        };
+        register_user(store, &user).unwrap();
+        let listed = ["root".to_string(), "later".to_string()];
+
+        promote_once(store, &listed).unwrap();
+        let mut promoted = store.get_json::<User>(&user_key("u1")).unwrap().unwrap();
+        assert_eq!(promoted.role, Role::Admin);
+        assert!(!store.exists(&admin_promotion_key("later")).unwrap());
+
+        // A demotion by another admin sticks across restarts
+        promoted.role = Role::User;
+        store.set_json(&user_key("u1"), &promoted).unwrap();
+        promote_once(store, &listed).unwrap();
+        assert_eq!(store.get_json::<User>(&user_key("u1")).unwrap().unwrap().role, Role::User);
+    }
+}
@@ -55,3 +55,38 @@ pub fn get_int(params: &HashMap<String, String>, key: &str, default: usize) -> u
         .unwrap_or(default)
         .max(1)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn parse_query_params_never_panics(uri in "\\PC*") {
+            parse_query_params(&uri);
+        }
+
+        #[test]
+        fn parse_query_params_round_trips(
+            params in prop::collection::btree_map("[a-z_]{1,10}", "\\PC{0,20}", 0..6)
+        ) {
+            let query: Vec<String> = params
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, urlencoding::encode(v)))
+                .collect();
+            let parsed = parse_query_params(&format!("/path?{}", query.join("&")));
+
+            for (key, value) in &params {
+                prop_assert_eq!(parsed.get(key), Some(value));
+            }
+        }
+
+        #[test]
+        fn get_int_is_at_least_one(value in "\\PC{0,10}", default in 0usize..100) {
+            let mut params = HashMap::new();
+            params.insert("page".to_string(), value);
+            prop_assert!(get_int(&params, "page", default) >= 1);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use serde::{Serialize, Deserialize};
+use spin_sdk::http::{Request, Response};
+use crate::core::db::BordStore;
+use crate::core::helpers::store;
+use crate::core::errors::ApiError;
+use crate::auth::validate_token;
+use crate::core::router::strip_mount;
+use crate::config::*;
+
+/// Token bucket state, shared across instances through the KV store
+#[derive(Serialize, Deserialize)]
+struct Bucket {
+    tokens: f64,
+    updated_ms: i64,
+}
+
+/// Client IP as reported by the Spin host (`ip:port`), without the port
+pub fn client_ip(req: &Request) -> String {
+    req.header("spin-client-addr")
+        .and_then(|h| h.as_str())
+        .map(|addr| addr.rsplit_once(':').map(|(ip, _)| ip).unwrap_or(addr).to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Take one token from a bucket; returns the seconds to wait if the bucket is empty
+pub fn take_token(store: &dyn BordStore, key: &str, (capacity, per_minute): (f64, f64)) -> anyhow::Result<Option<u64>> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let refill_per_ms = per_minute / 60_000.0;
+
+    let mut bucket = store.get_json_ttl::<Bucket>(key)?.unwrap_or(Bucket {
+        tokens: capacity,
+        updated_ms: now,
+    });
+    let elapsed = (now - bucket.updated_ms).max(0) as f64;
+    bucket.tokens = (bucket.tokens + elapsed * refill_per_ms).min(capacity);
+    bucket.updated_ms = now;
+
+    let retry_after = if bucket.tokens >= 1.0 {
+        bucket.tokens -= 1.0;
+        None
+    } else {
+        Some(((1.0 - bucket.tokens) / refill_per_ms / 1000.0).ceil() as u64)
+    };
+    // Once refilled from empty the bucket is the same as no bucket at all
+    let refill_seconds = (capacity / per_minute * 60.0).ceil() as i64;
+    store.set_json_ttl(key, &bucket, refill_seconds)?;
+
+    Ok(retry_after)
+}
+
+/// Apply the rate limit for this route, if it has one
+///
+/// Returns the 429 response to send instead of running the handler.
+pub fn enforce(req: &Request) -> anyhow::Result<Option<Response>> {
+    // Test instances are hammered by the test suites on purpose
+    if test_mode() {
+        return Ok(None);
+    }
+
+    let path = strip_mount(req.path(), API_PREFIX).unwrap_or(req.path());
+    let (scope, limit, subject) = match (req.method().to_string().as_str(), path) {
+        ("POST", "/posts") => ("posts", POST_RATE_LIMIT, validate_token(req).unwrap_or_else(|| client_ip(req))),
+        ("POST", "/users") => ("signup", SIGNUP_RATE_LIMIT, client_ip(req)),
+        ("POST", "/login") => ("login", LOGIN_RATE_LIMIT, client_ip(req)),
+        ("GET", "/export/public-posts") => ("export", EXPORT_RATE_LIMIT, client_ip(req)),
+        ("GET", "/profile/export") => ("export", EXPORT_RATE_LIMIT, validate_token(req).unwrap_or_else(|| client_ip(req))),
+        _ => return Ok(None),
+    };
+
+    match take_token(&store()?, &rate_limit_key(scope, &subject), limit)? {
+        Some(retry_after) => Ok(Some(ApiError::TooManyRequests(retry_after).into())),
+        None => Ok(None),
+    }
+}
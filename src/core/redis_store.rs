@@ -0,0 +1,87 @@
+use spin_sdk::redis::{Connection, RedisParameter, RedisResult};
+use crate::core::db::BordStore;
+use crate::config::REDIS_SCAN_COUNT;
+
+/// Redis-backed store, so several instances can share one data set.
+/// The address must be listed in the component's `allowed_outbound_hosts`.
+pub struct RedisStore(Connection);
+
+impl RedisStore {
+    pub fn open(address: &str) -> anyhow::Result<Self> {
+        Ok(Self(Connection::open(address)?))
+    }
+}
+
+fn text(result: RedisResult) -> Option<String> {
+    match result {
+        RedisResult::Binary(bytes) => String::from_utf8(bytes).ok(),
+        RedisResult::Status(text) => Some(text),
+        _ => None,
+    }
+}
+
+impl BordStore for RedisStore {
+    fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self.0.get(key)?)
+    }
+
+    fn set(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        Ok(self.0.set(key, value)?)
+    }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.0.del(&[key.to_string()])?;
+        Ok(())
+    }
+
+    fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        let result = self.0.execute("EXISTS", &[RedisParameter::Binary(key.as_bytes().to_vec())])?;
+        Ok(matches!(result.first(), Some(RedisResult::Int64(n)) if *n > 0))
+    }
+
+    /// Cursor-based `SCAN`, so the server never blocks on one big `KEYS`
+    fn scan(&self, prefix: &str) -> anyhow::Result<Vec<String>> {
+        // Escape glob metacharacters so the prefix matches literally
+        let mut pattern = String::new();
+        for c in prefix.chars() {
+            if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+                pattern.push('\\');
+            }
+            pattern.push(c);
+        }
+        pattern.push('*');
+
+        let mut keys = Vec::new();
+        let mut cursor = "0".to_string();
+        loop {
+            let reply = self.0.execute("SCAN", &[
+                RedisParameter::Binary(cursor.into_bytes()),
+                RedisParameter::Binary(b"MATCH".to_vec()),
+                RedisParameter::Binary(pattern.as_bytes().to_vec()),
+                RedisParameter::Binary(b"COUNT".to_vec()),
+                RedisParameter::Int64(REDIS_SCAN_COUNT),
+            ])?;
+            // The reply comes back flattened: the next cursor, then this batch's keys
+            let mut reply = reply.into_iter();
+            cursor = reply.next().and_then(text).unwrap_or_else(|| "0".to_string());
+            keys.extend(reply.filter_map(text));
+            if cursor == "0" {
+                return Ok(keys);
+            }
+        }
+    }
+
+    fn add_to_set(&self, key: &str, members: &[&str]) -> anyhow::Result<usize> {
+        let members: Vec<String> = members.iter().map(|m| m.to_string()).collect();
+        Ok(self.0.sadd(key, &members)? as usize)
+    }
+
+    fn remove_from_set(&self, key: &str, members: &[&str]) -> anyhow::Result<usize> {
+        let members: Vec<String> = members.iter().map(|m| m.to_string()).collect();
+        Ok(self.0.srem(key, &members)? as usize)
+    }
+
+    fn set_members(&self, key: &str) -> anyhow::Result<Vec<String>> {
+        Ok(self.0.smembers(key)?)
+    }
+}
@@ -0,0 +1,163 @@
+use spin_sdk::http::{Request, Response};
+use std::ops::Index;
+use crate::core::errors::ApiError;
+use crate::core::helpers::validate_uuid;
+use crate::auth::validate_token;
+
+/// Path parameters captured by a route pattern, e.g. `id` in `/posts/{id}`
+#[derive(Debug, Default)]
+pub struct Params(Vec<(&'static str, String)>);
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter().find(|(n, _)| *n == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Only for names the matched pattern declares, which are always present
+impl Index<&str> for Params {
+    type Output = str;
+
+    fn index(&self, name: &str) -> &str {
+        self.get(name).unwrap_or_else(|| panic!("route has no {{{}}} parameter", name))
+    }
+}
+
+pub type Handler = fn(Request, &Params) -> anyhow::Result<Response>;
+/// Handler for a route that needs a logged-in user; gets their ID
+pub type UserHandler = fn(Request, &Params, String) -> anyhow::Result<Response>;
+
+enum Endpoint {
+    Public(Handler),
+    User(UserHandler),
+}
+
+struct Route {
+    method: &'static str,
+    segments: Vec<&'static str>,
+    endpoint: Endpoint,
+}
+
+/// Parameters named `id` or `*_id` hold UUIDs; anything else is rejected with a 400
+fn is_id_param(name: &str) -> bool {
+    name == "id" || name.ends_with("_id")
+}
+
+enum Match {
+    No,
+    Yes(Params),
+    BadId(&'static str),
+}
+
+impl Route {
+    fn matches(&self, path: &str) -> Match {
+        let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+        if parts.len() != self.segments.len() {
+            return Match::No;
+        }
+
+        let mut params = Params::default();
+        let mut bad_id = None;
+        for (segment, part) in self.segments.iter().zip(parts) {
+            match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                Some(name) => {
+                    if part.is_empty() {
+                        return Match::No;
+                    }
+                    if is_id_param(name) && !validate_uuid(part) {
+                        bad_id = bad_id.or(Some(name));
+                    }
+                    params.0.push((name, part.to_string()));
+                }
+                None if *segment == part => {}
+                None => return Match::No,
+            }
+        }
+
+        match bad_id {
+            Some(name) => Match::BadId(name),
+            None => Match::Yes(params),
+        }
+    }
+}
+
+/// `path` with a leading `prefix` segment (e.g. `/api/v1`) removed, if it has one
+pub fn strip_mount<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    path.strip_prefix(prefix).filter(|rest| rest.starts_with('/'))
+}
+
+/// Method + path pattern dispatch, in registration order
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    mount: Option<&'static str>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also serve every route under `prefix`, e.g. `/api/v1/posts` next to `/posts`
+    pub fn mounted_at(mut self, prefix: &'static str) -> Self {
+        self.mount = Some(prefix);
+        self
+    }
+
+    fn add(mut self, method: &'static str, pattern: &'static str, endpoint: Endpoint) -> Self {
+        self.routes.push(Route {
+            method,
+            segments: pattern.trim_start_matches('/').split('/').collect(),
+            endpoint,
+        });
+        self
+    }
+
+    /// Register a handler for e.g. `("PUT", "/posts/{id}")`
+    pub fn route(self, method: &'static str, pattern: &'static str, handler: Handler) -> Self {
+        self.add(method, pattern, Endpoint::Public(handler))
+    }
+
+    /// Register a handler that only runs for a valid session; anyone else gets a 401
+    pub fn authed(self, method: &'static str, pattern: &'static str, handler: UserHandler) -> Self {
+        self.add(method, pattern, Endpoint::User(handler))
+    }
+
+    /// Run the first matching route, or give the request back if none matches
+    pub fn dispatch(&self, req: Request) -> Result<anyhow::Result<Response>, Box<Request>> {
+        let method = req.method().to_string();
+        let path = req.path();
+        let path = self.mount.and_then(|prefix| strip_mount(path, prefix)).unwrap_or(path);
+        for route in self.routes.iter().filter(|r| r.method == method) {
+            match route.matches(path) {
+                Match::No => continue,
+                Match::BadId(name) => {
+                    return Ok(Ok(ApiError::BadRequest("invalid_id", format!("Invalid {}", name)).into()));
+                }
+                Match::Yes(params) => {
+                    return Ok(match &route.endpoint {
+                        Endpoint::Public(handler) => handler(req, &params),
+                        Endpoint::User(handler) => match validate_token(&req) {
+                            Some(user_id) => handler(req, &params, user_id),
+                            None => Ok(ApiError::Unauthorized.into()),
+                        },
+                    });
+                }
+            }
+        }
+        Err(Box::new(req))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mount_prefix_must_end_at_a_segment() {
+        assert_eq!(strip_mount("/api/v1/posts", "/api/v1"), Some("/posts"));
+        assert_eq!(strip_mount("/api/v1", "/api/v1"), None);
+        assert_eq!(strip_mount("/api/v10/posts", "/api/v1"), None);
+        assert_eq!(strip_mount("/posts", "/api/v1"), None);
+    }
+}
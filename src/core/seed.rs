@@ -0,0 +1,113 @@
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::core::db::BordStore;
+use crate::core::helpers::{hash_password, now_iso};
+use crate::models::models::{User, Role};
+use crate::users::{find_user_id_by_username, register_user};
+use crate::posts::publish_post;
+use crate::follow::follow_user;
+use crate::config::*;
+
+/// Sample data for dev instances, used unless `BORD_SEED_FIXTURE` points elsewhere
+const DEFAULT_FIXTURE: &str = include_str!("../../fixtures/seed.json");
+
+#[derive(Deserialize)]
+struct Fixture {
+    #[serde(default)]
+    users: Vec<FixtureUser>,
+    #[serde(default)]
+    follows: Vec<FixtureFollow>,
+}
+
+#[derive(Deserialize)]
+struct FixtureUser {
+    username: String,
+    password: String,
+    #[serde(default)]
+    bio: Option<String>,
+    #[serde(default)]
+    role: Role,
+    /// Oldest first
+    #[serde(default)]
+    posts: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct FixtureFollow {
+    follower: String,
+    following: String,
+}
+
+fn load_fixture() -> anyhow::Result<Fixture> {
+    let json = match seed_fixture_path() {
+        Some(path) => std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("Failed to read seed fixture {}: {}", path, e))?,
+        None => DEFAULT_FIXTURE.to_string(),
+    };
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// Create the fixture's users, posts and follows once when `BORD_SEED_DATA` is on.
+///
+/// Done is recorded under `seeded`, so later requests cost a single lookup.
+/// Users that already exist are left alone, along with their posts, and
+/// follows are only applied when a user was created, so an interrupted run
+/// can be repeated and never undoes what people did on the instance.
+pub fn seed_fixture_data(store: &dyn BordStore) -> anyhow::Result<()> {
+    if !seed_data_enabled() || store.exists(SEED_MARKER_KEY)? {
+        return Ok(());
+    }
+
+    let fixture = load_fixture()?;
+    let mut created_any = false;
+
+    for seed in &fixture.users {
+        if find_user_id_by_username(store, &seed.username)?.is_some() {
+            continue;
+        }
+
+        let user = User {
+            id: Uuid::new_v4().to_string(),
+            username: seed.username.clone(),
+            password: hash_password(&seed.password)?,
+            bio: seed.bio.clone(),
+            created_at: Some(now_iso()),
+            role: seed.role,
+            timezone: None,
+            email: None,
+        };
+        register_user(store, &user)?;
+
+        for content in &seed.posts {
+            publish_post(store, &user.id, content, None)?;
+        }
+        created_any = true;
+    }
+
+    if created_any {
+        for edge in &fixture.follows {
+            let follower = find_user_id_by_username(store, &edge.follower)?;
+            let following = find_user_id_by_username(store, &edge.following)?;
+            if let (Some(follower), Some(following)) = (follower, following) {
+                follow_user(store, &follower, &following)?;
+            }
+        }
+    }
+
+    store.set_json(SEED_MARKER_KEY, &now_iso())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_fixture_parses() {
+        let fixture: Fixture = serde_json::from_str(DEFAULT_FIXTURE).unwrap();
+        assert!(fixture.users.iter().any(|u| u.username == "test"));
+        for edge in &fixture.follows {
+            assert!(fixture.users.iter().any(|u| u.username == edge.follower));
+            assert!(fixture.users.iter().any(|u| u.username == edge.following));
+        }
+    }
+}
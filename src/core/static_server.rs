@@ -1,26 +1,266 @@
-use spin_sdk::http::Response;
-use rust_embed::RustEmbed;
+use spin_sdk::http::{Request, Response};
+use rust_embed::{EmbeddedFile, RustEmbed};
+use chrono::{DateTime, Utc};
 use mime_guess::from_path;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use crate::core::etag::none_match;
+use crate::core::errors::ApiError;
+use crate::config::dev_static_dir;
 
 #[derive(RustEmbed)]
 #[folder = "static"]
 struct Assets;
 
-pub fn serve_static(path: &str) -> anyhow::Result<Response> {
-    let file_path = match path {
-        "/" => "index.html",
-        "/index.html" => "index.html",
-        _ => path.trim_start_matches('/'),
-    };
+/// `.br` and `.gz` copies of the text assets, made by `build.rs`
+#[derive(RustEmbed)]
+#[folder = "$OUT_DIR/compressed"]
+struct Compressed;
+
+// Fingerprinted URLs never change content, so they can be cached forever
+const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+const FINGERPRINT_LEN: usize = 8;
 
-    let file = Assets::get(file_path)
-        .ok_or_else(|| anyhow::anyhow!("File not found"))?;
+/// Content hash of an embedded file (computed by rust-embed at build time)
+fn fingerprint(file_path: &str) -> Option<String> {
+    let file = Assets::get(file_path)?;
+    let hash = file.metadata.sha256_hash();
+    Some(hash[..FINGERPRINT_LEN / 2].iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Map of embedded asset name -> fingerprinted URL, e.g. `style.css` -> `/style.1a2b3c4d.css`.
+/// Dev builds map to the plain name, since the file on disk may no longer match the hash.
+pub fn asset_manifest() -> &'static BTreeMap<String, String> {
+    static MANIFEST: OnceLock<BTreeMap<String, String>> = OnceLock::new();
+    MANIFEST.get_or_init(|| {
+        Assets::iter()
+            .filter(|name| !name.ends_with(".html"))
+            .filter_map(|name| {
+                if cfg!(feature = "dev") {
+                    return Some((name.to_string(), format!("/{}", name)));
+                }
+                let hash = fingerprint(&name)?;
+                let url = match name.rsplit_once('.') {
+                    Some((stem, ext)) => format!("/{}.{}.{}", stem, hash, ext),
+                    None => format!("/{}.{}", name, hash),
+                };
+                Some((name.to_string(), url))
+            })
+            .collect()
+    })
+}
 
-    let mime = from_path(file_path).first_or_octet_stream();
+/// Combined hash of every embedded asset; changes whenever any of them does
+pub fn assets_version() -> &'static str {
+    static VERSION: OnceLock<String> = OnceLock::new();
+    VERSION.get_or_init(|| {
+        let mut hasher = Sha256::new();
+        for name in Assets::iter() {
+            if let Some(file) = Assets::get(&name) {
+                hasher.update(name.as_bytes());
+                hasher.update(file.metadata.sha256_hash());
+            }
+        }
+        hasher.finalize()[..FINGERPRINT_LEN / 2].iter().map(|b| format!("{:02x}", b)).collect()
+    })
+}
 
+pub fn serve_manifest() -> anyhow::Result<Response> {
     Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .header("Cache-Control", "no-cache")
+        .body(serde_json::to_vec(asset_manifest())?)
+        .build())
+}
+
+/// Resolve `name.<hash>.ext` back to `name.ext`; the flag tells whether the hash is current
+fn resolve_fingerprinted(requested: &str) -> Option<(String, bool)> {
+    let (rest, ext) = requested.rsplit_once('.')?;
+    let (stem, hash) = rest.rsplit_once('.')?;
+    if hash.len() != FINGERPRINT_LEN || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let original = format!("{}.{}", stem, ext);
+    let current = fingerprint(&original)?;
+    Some((original, current == hash))
+}
+
+/// Strong validator from the content hash rust-embed computed at build time
+fn file_etag(file: &EmbeddedFile) -> String {
+    let hex: String = file.metadata.sha256_hash()[..16].iter().map(|b| format!("{:02x}", b)).collect();
+    format!("\"{}\"", hex)
+}
+
+/// Build time of the file, as an HTTP date
+fn last_modified(file: &EmbeddedFile) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(file.metadata.last_modified()? as i64, 0)
+}
+
+fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Whether the client's copy is current. `If-None-Match` wins over
+/// `If-Modified-Since` when both are sent.
+fn not_modified(req: &Request, etag: &str, modified: Option<DateTime<Utc>>) -> bool {
+    if let Some(header) = req.header("If-None-Match").and_then(|h| h.as_str()) {
+        return none_match(header, etag);
+    }
+    let since = req.header("If-Modified-Since")
+        .and_then(|h| h.as_str())
+        .and_then(|h| DateTime::parse_from_rfc2822(h).ok());
+    matches!((since, modified), (Some(since), Some(modified)) if modified <= since)
+}
+
+/// Whether `Accept-Encoding` allows `coding`, by name or through `*`
+fn accepts(header: &str, coding: &str) -> bool {
+    let mut wildcard = false;
+    for entry in header.split(',') {
+        let mut parts = entry.split(';');
+        let name = parts.next().unwrap_or_default().trim();
+        let allowed = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .all(|q| q.trim().parse::<f32>().map(|q| q > 0.0).unwrap_or(true));
+        if name.eq_ignore_ascii_case(coding) {
+            return allowed;
+        }
+        wildcard |= name == "*" && allowed;
+    }
+    wildcard
+}
+
+/// The smallest precompressed variant of `file_path` the client accepts, as
+/// (`Content-Encoding`, file suffix, variant)
+fn compressed_variant(req: &Request, file_path: &str) -> Option<(&'static str, &'static str, EmbeddedFile)> {
+    let header = req.header("Accept-Encoding").and_then(|h| h.as_str())?;
+    [("br", "br"), ("gzip", "gz")].into_iter()
+        .filter(|(coding, _)| accepts(header, coding))
+        .find_map(|(coding, suffix)| {
+            Compressed::get(&format!("{}.{}", file_path, suffix)).map(|file| (coding, suffix, file))
+        })
+}
+
+/// Whether an asset has precompressed variants, so responses differ by `Accept-Encoding`
+fn has_variants(file_path: &str) -> bool {
+    Compressed::get(&format!("{}.gz", file_path)).is_some()
+}
+
+/// Dev builds read assets from `static/` as mounted into the component
+/// (see spin.toml) on every request and never let them be cached, so CSS and
+/// JS edits show up on reload without rebuilding
+fn serve_from_disk(path: &str) -> anyhow::Result<Response> {
+    let requested = path.trim_start_matches('/');
+    let inside = !requested.is_empty() && requested.split('/').all(|part| !part.is_empty() && part != "..");
+    let data = if inside {
+        std::fs::read(std::path::Path::new(&dev_static_dir()).join(requested)).ok()
+    } else {
+        None
+    };
+    match data {
+        Some(data) => Ok(Response::builder()
+            .status(200)
+            .header("Content-Type", from_path(requested).first_or_octet_stream().as_ref())
+            .header("Cache-Control", "no-store")
+            .body(data)
+            .build()),
+        None => Ok(ApiError::NotFound("route_not_found", "No route found".to_string()).into()),
+    }
+}
+
+pub fn serve_static(req: &Request, path: &str) -> anyhow::Result<Response> {
+    if cfg!(feature = "dev") {
+        return serve_from_disk(path);
+    }
+
+    // Pages are rendered from `templates/`; only assets live here
+    let requested = path.trim_start_matches('/');
+
+    // Stale hashes (from an older deploy) still get the current file, just not cached forever
+    let (file_path, immutable) = match resolve_fingerprinted(requested) {
+        Some((original, current)) => (original, current),
+        None => (requested.to_string(), false),
+    };
+
+    let file = match Assets::get(&file_path) {
+        Some(file) => file,
+        None => return Ok(ApiError::NotFound("route_not_found", "No route found".to_string()).into()),
+    };
+
+    let mime = from_path(&file_path).first_or_octet_stream();
+    let cache_control = if immutable { IMMUTABLE_CACHE_CONTROL } else { "no-cache" };
+    let modified = last_modified(&file);
+
+    // Each encoding is its own representation, with its own ETag
+    let (encoding, etag, body) = match compressed_variant(req, &file_path) {
+        Some((coding, suffix, variant)) => {
+            let etag = file_etag(&file).replacen('"', &format!("\"{}-", suffix), 1);
+            (Some(coding), etag, variant.data)
+        }
+        None => (None, file_etag(&file), file.data),
+    };
+
+    let mut builder = Response::builder();
+    builder
+        .header("Cache-Control", cache_control)
+        .header("ETag", etag.as_str());
+    if let Some(modified) = modified {
+        builder.header("Last-Modified", http_date(modified));
+    }
+    if has_variants(&file_path) {
+        builder.header("Vary", "Accept-Encoding");
+    }
+
+    if not_modified(req, &etag, modified) {
+        return Ok(builder.status(304).build());
+    }
+
+    if let Some(coding) = encoding {
+        builder.header("Content-Encoding", coding);
+    }
+    Ok(builder
         .status(200)
         .header("Content-Type", mime.as_ref())
-        .body(file.data.to_vec())
+        .body(body.into_owned())
         .build())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprinted_names_resolve_to_embedded_files() {
+        let url = asset_manifest().get("style.css").unwrap();
+        let requested = url.trim_start_matches('/');
+        assert_eq!(resolve_fingerprinted(requested), Some(("style.css".to_string(), true)));
+        assert_eq!(resolve_fingerprinted("style.00000000.css"), Some(("style.css".to_string(), false)));
+        assert_eq!(resolve_fingerprinted("style.css"), None);
+        assert_eq!(resolve_fingerprinted("jquery.min.js"), None);
+    }
+
+    #[test]
+    fn accept_encoding_honours_q_zero_and_wildcards() {
+        assert!(accepts("gzip, deflate, br", "br"));
+        assert!(accepts("GZIP", "gzip"));
+        assert!(accepts("gzip;q=0.5, br;q=1.0", "gzip"));
+        assert!(!accepts("gzip, br;q=0", "br"));
+        assert!(accepts("*", "br"));
+        assert!(!accepts("*, br;q=0", "br"));
+        assert!(!accepts("identity", "gzip"));
+    }
+
+    #[test]
+    fn text_assets_have_compressed_variants() {
+        assert!(has_variants("style.css") && has_variants("api.js"));
+        assert!(!has_variants("B.png"));
+    }
+
+    #[test]
+    fn http_dates_use_gmt() {
+        let time = DateTime::from_timestamp(784111777, 0).unwrap();
+        assert_eq!(http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(DateTime::parse_from_rfc2822(&http_date(time)).unwrap(), time);
+    }
+}
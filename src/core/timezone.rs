@@ -0,0 +1,61 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+/// Parse an IANA time zone name such as `Europe/Bucharest`
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    name.parse::<Tz>().ok()
+}
+
+/// Render a stored RFC3339 timestamp with a strftime `format` in the given time zone.
+///
+/// Falls back to UTC when the user has no (valid) zone set. Only for
+/// human-facing output; the API keeps returning RFC3339 UTC.
+pub fn format_local(timestamp: &str, timezone: Option<&str>, format: &str) -> String {
+    match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(t) => {
+            let tz = timezone.and_then(parse_timezone).unwrap_or(Tz::UTC);
+            t.with_timezone(&tz).format(format).to_string()
+        }
+        Err(_) => timestamp.to_string(),
+    }
+}
+
+/// How long before `now` a post was made, in the steps relative times use
+#[derive(Debug, PartialEq)]
+pub enum Age {
+    JustNow,
+    Minutes(i64),
+    Hours(i64),
+    Days(i64),
+    /// A week or more; shown as the date instead
+    Older(DateTime<Utc>),
+}
+
+/// The `Age` of an RFC 3339 timestamp; `None` if it doesn't parse
+pub fn age(timestamp: &str, now: DateTime<Utc>) -> Option<Age> {
+    let t = DateTime::parse_from_rfc3339(timestamp).ok()?.with_timezone(&Utc);
+    let seconds = (now - t).num_seconds().max(0);
+    Some(match seconds {
+        0..=59 => Age::JustNow,
+        60..=3599 => Age::Minutes(seconds / 60),
+        3600..=86399 => Age::Hours(seconds / 3600),
+        86400..=604799 => Age::Days(seconds / 86400),
+        _ => Age::Older(t),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ages_step_up_to_a_date() {
+        let now = DateTime::parse_from_rfc3339("2024-03-10T12:00:00+00:00").unwrap().with_timezone(&Utc);
+        assert_eq!(age("2024-03-10T11:59:30+00:00", now), Some(Age::JustNow));
+        assert_eq!(age("2024-03-10T11:15:00+00:00", now), Some(Age::Minutes(45)));
+        assert_eq!(age("2024-03-10T14:00:00+02:00", now), Some(Age::JustNow));
+        assert_eq!(age("2024-03-09T12:00:00+00:00", now), Some(Age::Days(1)));
+        assert!(matches!(age("2024-02-01T08:00:00+00:00", now), Some(Age::Older(_))));
+        assert_eq!(age("not a time", now), None);
+    }
+}
@@ -0,0 +1,87 @@
+//! Typed request bodies. Handlers deserialize into a `#[derive(Deserialize)]`
+//! struct that implements `Validate`, so a bad request is answered with one
+//! 400 listing every invalid field instead of stopping at the first.
+
+use serde::{Deserialize, Deserializer};
+use serde::de::DeserializeOwned;
+use spin_sdk::http::Request;
+use crate::core::errors::{ApiError, FieldError};
+
+/// Field-level rules for a request body
+pub trait Validate {
+    /// Every problem with the request; empty when it can be used
+    fn validate(&self) -> Vec<FieldError>;
+}
+
+/// Deserialize and validate a JSON body. An empty body counts as `{}`, so
+/// missing required fields are reported like any other invalid field.
+pub fn parse_json<T: DeserializeOwned + Validate>(body: &[u8]) -> Result<T, ApiError> {
+    let body = if body.is_empty() { b"{}".as_slice() } else { body };
+    let value: T = serde_json::from_slice(body)
+        .map_err(|e| ApiError::BadRequest("invalid_body", format!("Invalid request body: {}", e)))?;
+
+    let problems = value.validate();
+    if problems.is_empty() {
+        Ok(value)
+    } else {
+        Err(ApiError::Invalid(problems))
+    }
+}
+
+/// For `Option<Option<T>>` fields with `#[serde(default, deserialize_with = "nullable")]`:
+/// `None` when the field is absent, `Some(None)` when it is `null`
+pub fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// `parse_json` on the request body
+pub fn parse_body<T: DeserializeOwned + Validate>(req: &Request) -> Result<T, ApiError> {
+    parse_json(req.body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Credentials {
+        #[serde(default)]
+        username: String,
+        #[serde(default)]
+        password: String,
+    }
+
+    impl Validate for Credentials {
+        fn validate(&self) -> Vec<FieldError> {
+            let mut problems = Vec::new();
+            if self.username.is_empty() {
+                problems.push(FieldError::new("username", "username_required", "Username is required"));
+            }
+            if self.password.is_empty() {
+                problems.push(FieldError::new("password", "password_required", "Password is required"));
+            }
+            problems
+        }
+    }
+
+    #[test]
+    fn every_invalid_field_is_reported() {
+        let creds: Credentials = parse_json(br#"{"username": "alice", "password": "pw"}"#).unwrap();
+        assert_eq!(creds.username, "alice");
+
+        match parse_json::<Credentials>(b"") {
+            Err(ApiError::Invalid(problems)) => {
+                let fields: Vec<&str> = problems.iter().map(|p| p.field).collect();
+                assert_eq!(fields, ["username", "password"]);
+            }
+            other => panic!("expected Invalid, got {:?}", other.err()),
+        }
+
+        let wrong_type = parse_json::<Credentials>(br#"{"username": 5}"#);
+        assert!(matches!(wrong_type, Err(ApiError::BadRequest("invalid_body", _))));
+    }
+}
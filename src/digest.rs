@@ -0,0 +1,144 @@
+use spin_sdk::http::{Request, Response};
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use regex::Regex;
+use minijinja::context;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use crate::core::db::BordStore;
+use crate::models::models::{User, Post, CachedPage};
+use crate::core::helpers::store;
+use crate::core::db::members;
+use crate::core::feed_index::feed_ids;
+use crate::core::i18n::request_locale;
+use crate::templates::{render_page, page_version, html_response};
+use crate::posts::load_post;
+use crate::users::is_reserved_username;
+use crate::config::*;
+
+fn hashtag_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    // Only after whitespace or at the start, so URL fragments don't count
+    REGEX.get_or_init(|| Regex::new(r"(?:^|\s)#([A-Za-z0-9_]{1,50})").expect("Regex should compile"))
+}
+
+/// Lowercased hashtags in a post, each once
+fn hashtags(content: &str) -> Vec<String> {
+    let mut tags: Vec<String> = hashtag_regex()
+        .captures_iter(content)
+        .map(|caps| caps[1].to_lowercase())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// UTC day of an RFC 3339 timestamp
+fn day_of(timestamp: &str) -> Option<NaiveDate> {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|t| t.with_timezone(&Utc).date_naive())
+}
+
+/// Monday of the last full week (UTC) and its ISO label
+fn last_full_week() -> (NaiveDate, String) {
+    let today = Utc::now().date_naive();
+    let start = today - Duration::days(today.weekday().num_days_from_monday() as i64 + 7);
+    let week = start.iso_week();
+    (start, format!("{}-W{:02}", week.year(), week.week()))
+}
+
+fn week_impressions(store: &dyn BordStore, post_id: &str, start: NaiveDate) -> anyhow::Result<u64> {
+    let mut total = 0;
+    for offset in 0..7 {
+        let day = (start + Duration::days(offset)).format("%Y-%m-%d").to_string();
+        total += store.get_json::<u64>(&impressions_key(post_id, &day))?.unwrap_or_default();
+    }
+    Ok(total)
+}
+
+fn render_digest(store: &dyn BordStore, start: NaiveDate, locale: &str) -> anyhow::Result<String> {
+    let end = start + Duration::days(7);
+    let in_week = |timestamp: &str| day_of(timestamp).is_some_and(|day| day >= start && day < end);
+
+    let mut usernames: HashMap<String, String> = HashMap::new();
+    let mut new_users = Vec::new();
+    for id in members(store, USERS_LIST_KEY)? {
+        if let Some(user) = store.get_json::<User>(&user_key(&id))? {
+            if user.created_at.as_deref().is_some_and(in_week) && !is_reserved_username(&user.username) {
+                new_users.push(user.username.clone());
+            }
+            usernames.insert(user.id, user.username);
+        }
+    }
+
+    let mut top: Vec<(u64, Post)> = Vec::new();
+    let mut tag_counts: HashMap<String, usize> = HashMap::new();
+    for id in feed_ids(store)? {
+        let post = match load_post(store, &id)? {
+            Some(post) if !post.hidden && in_week(&post.created_at) => post,
+            _ => continue,
+        };
+        for tag in hashtags(&post.content) {
+            *tag_counts.entry(tag).or_default() += 1;
+        }
+        top.push((week_impressions(store, &post.id, start)?, post));
+    }
+    top.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| b.1.created_at.cmp(&a.1.created_at)));
+
+    let mut tags: Vec<(String, usize)> = tag_counts.into_iter().collect();
+    tags.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let top_posts: Vec<_> = top.iter()
+        .take(DIGEST_TOP_POSTS)
+        .map(|(views, post)| context! {
+            author => usernames.get(&post.user_id).map(String::as_str).unwrap_or("[deleted]"),
+            post,
+            views,
+        })
+        .collect();
+
+    let tags: Vec<_> = tags.iter()
+        .take(DIGEST_TRENDING_TAGS)
+        .map(|(name, count)| context! { name, count })
+        .collect();
+
+    render_page(store, "digest.html", locale, context! {
+        start => start.format("%b %-d, %Y").to_string(),
+        end => (end - Duration::days(1)).format("%b %-d, %Y").to_string(),
+        top_posts,
+        tags,
+        new_users => new_users.iter().take(DIGEST_NEW_USERS).collect::<Vec<_>>(),
+    })
+}
+
+/// `GET /digest/latest`: top posts, trending tags and new members of the last full week.
+/// Built on the first request of each week and cached, since a past week doesn't change.
+pub fn render_latest_digest(req: &Request) -> anyhow::Result<Response> {
+    let store = store()?;
+    let locale = request_locale(req);
+    let (start, week) = last_full_week();
+
+    let cache_key = digest_key(&week, locale);
+    let version = page_version(&store)?;
+    if let Some(cached) = store.get_json::<CachedPage>(&cache_key)? {
+        if cached.version == version {
+            return Ok(html_response(cached.html, locale));
+        }
+    }
+
+    let html = render_digest(&store, start, locale)?;
+    store.set_json(&cache_key, &CachedPage { version, html: html.clone() })?;
+
+    Ok(html_response(html, locale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashtags_skip_url_fragments() {
+        let content = r#"#Rust is fun, #rust again <a href="https://example.com/#anchor">x</a> and #bord_dev"#;
+        assert_eq!(hashtags(content), ["bord_dev", "rust"]);
+    }
+}
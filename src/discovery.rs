@@ -0,0 +1,148 @@
+//! WebFinger and NodeInfo, so other servers and tools can discover accounts
+//! and instance stats without knowing the API.
+
+use spin_sdk::http::{Request, Response};
+use crate::core::db::{BordStore, members};
+use crate::models::models::User;
+use crate::core::helpers::store;
+use crate::core::errors::ApiError;
+use crate::core::query_params::{parse_query_params, get_string};
+use crate::core::feed_index::feed_ids;
+use crate::branding::current_branding;
+use crate::users::{find_user_id_by_username, is_reserved_username};
+use crate::config::*;
+
+const NODEINFO_SCHEMA: &str = "http://nodeinfo.diaspora.software/ns/schema/2.0";
+
+/// Scheme and host the request was made to, e.g. `https://bord.example`
+pub fn origin(req: &Request) -> String {
+    if let Some(full_url) = req.header("spin-full-url").and_then(|h| h.as_str()) {
+        if let Some((scheme, rest)) = full_url.split_once("://") {
+            let host = rest.split('/').next().unwrap_or_default();
+            return format!("{}://{}", scheme, host);
+        }
+    }
+    let host = req.header("Host").and_then(|h| h.as_str()).unwrap_or("localhost");
+    format!("http://{}", host)
+}
+
+/// Username in `acct:user@host` (or bare `user@host`), if `host` is this instance
+fn acct_username<'a>(resource: &'a str, host: &str) -> Option<&'a str> {
+    let acct = resource.strip_prefix("acct:").unwrap_or(resource);
+    let (username, domain) = acct.rsplit_once('@')?;
+    (domain.eq_ignore_ascii_case(host) && !username.is_empty()).then_some(username)
+}
+
+fn json_response(content_type: &str, body: &serde_json::Value) -> anyhow::Result<Response> {
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", content_type)
+        .header("Access-Control-Allow-Origin", "*")
+        .body(serde_json::to_vec(body)?)
+        .build())
+}
+
+/// `GET /.well-known/webfinger?resource=acct:user@host`
+pub fn webfinger(req: Request) -> anyhow::Result<Response> {
+    let params = parse_query_params(req.uri());
+    let resource = match get_string(&params, "resource", None) {
+        Some(r) => r,
+        None => return Ok(ApiError::BadRequest("resource_required", "resource is required".to_string()).into()),
+    };
+
+    let origin = origin(&req);
+    let host = origin.split_once("://").map(|(_, host)| host).unwrap_or_default();
+    let username = match acct_username(&resource, host) {
+        Some(u) => u,
+        None => return Ok(ApiError::NotFound("unknown_resource", "Unknown resource".to_string()).into()),
+    };
+
+    let store = store()?;
+    let user = match find_user_id_by_username(&store, username)? {
+        Some(id) => store.get_json::<User>(&user_key(&id))?,
+        None => None,
+    };
+    let user = match user {
+        Some(u) => u,
+        None => return Ok(ApiError::NotFound("user_not_found", "User not found".to_string()).into()),
+    };
+
+    // No ActivityPub actor yet: point at the profile page and the user API
+    let body = serde_json::json!({
+        "subject": format!("acct:{}@{}", user.username, host),
+        "aliases": [format!("{}/{}", origin, user.username)],
+        "links": [
+            {
+                "rel": "http://webfinger.net/rel/profile-page",
+                "type": "text/html",
+                "href": format!("{}/{}", origin, user.username),
+            },
+            {
+                "rel": "self",
+                "type": "application/json",
+                "href": format!("{}{}/users/{}", origin, API_PREFIX, user.id),
+            },
+        ],
+    });
+    json_response("application/jrd+json", &body)
+}
+
+/// `GET /.well-known/nodeinfo`: where the NodeInfo document lives
+pub fn nodeinfo_links(req: Request) -> anyhow::Result<Response> {
+    let body = serde_json::json!({
+        "links": [{
+            "rel": NODEINFO_SCHEMA,
+            "href": format!("{}/nodeinfo/2.0", origin(&req)),
+        }],
+    });
+    json_response("application/json", &body)
+}
+
+fn user_total(store: &dyn BordStore) -> anyhow::Result<usize> {
+    let mut total = 0;
+    for id in members(store, USERS_LIST_KEY)? {
+        if let Some(user) = store.get_json::<User>(&user_key(&id))? {
+            if !is_reserved_username(&user.username) {
+                total += 1;
+            }
+        }
+    }
+    Ok(total)
+}
+
+/// `GET /nodeinfo/2.0`: software, version and usage counts
+pub fn nodeinfo() -> anyhow::Result<Response> {
+    let store = store()?;
+    let body = serde_json::json!({
+        "version": "2.0",
+        "software": {
+            "name": "bord",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "protocols": [],
+        "services": { "inbound": [], "outbound": [] },
+        "openRegistrations": true,
+        "usage": {
+            "users": { "total": user_total(&store)? },
+            "localPosts": feed_ids(&store)?.len(),
+        },
+        "metadata": {
+            "nodeName": current_branding(&store)?.site_name,
+        },
+    });
+    json_response(&format!("application/json; profile=\"{}#\"", NODEINFO_SCHEMA), &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn acct_resource_must_name_this_host() {
+        assert_eq!(acct_username("acct:alice@bord.example", "bord.example"), Some("alice"));
+        assert_eq!(acct_username("alice@BORD.example", "bord.example"), Some("alice"));
+        assert_eq!(acct_username("acct:alice@elsewhere.example", "bord.example"), None);
+        assert_eq!(acct_username("acct:@bord.example", "bord.example"), None);
+        assert_eq!(acct_username("https://bord.example/alice", "bord.example"), None);
+    }
+}
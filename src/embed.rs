@@ -0,0 +1,118 @@
+//! Embeddable posts: an oEmbed provider (https://oembed.com) for post
+//! permalinks, and the small frameable page its `html` points at.
+
+use spin_sdk::http::{Request, Response};
+use minijinja::context;
+use crate::core::helpers::{store, validate_uuid};
+use crate::core::errors::ApiError;
+use crate::core::i18n::{self, request_locale};
+use crate::core::query_params::{parse_query_params, get_string, get_int};
+use crate::branding::current_branding;
+use crate::discovery::origin;
+use crate::posts::load_visible_post;
+use crate::users::author_name;
+use crate::templates::{render_page, html_response, excerpt};
+use crate::config::*;
+
+/// Post ID in a permalink (`https://host/posts/{id}`) on this instance's `host`
+fn permalink_post_id<'a>(url: &'a str, host: &str) -> Option<&'a str> {
+    let (_, rest) = url.split_once("://")?;
+    let (url_host, path) = rest.split_once('/')?;
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let id = path.strip_prefix("posts/")?.trim_end_matches('/');
+    (url_host.eq_ignore_ascii_case(host) && validate_uuid(id)).then_some(id)
+}
+
+/// `GET /oembed?url=<post permalink>`: a `rich` oEmbed response whose HTML
+/// frames `/embed/{id}`. Only JSON is offered, so `format=xml` gets a 501.
+pub fn oembed(req: Request) -> anyhow::Result<Response> {
+    let params = parse_query_params(req.uri());
+    if get_string(&params, "format", Some("json")).as_deref() != Some("json") {
+        return Ok(Response::builder().status(501).build());
+    }
+    let url = match get_string(&params, "url", None) {
+        Some(url) => url,
+        None => return Ok(ApiError::BadRequest("url_required", "url is required".to_string()).into()),
+    };
+
+    let origin = origin(&req);
+    let host = origin.split_once("://").map(|(_, host)| host).unwrap_or_default();
+    let store = store()?;
+    let post = match permalink_post_id(&url, host) {
+        Some(id) => load_visible_post(&store, id)?,
+        None => None,
+    };
+    let post = match post {
+        Some(post) => post,
+        None => return Ok(ApiError::NotFound("post_not_found", "Post not found".to_string()).into()),
+    };
+
+    let width = get_int(&params, "maxwidth", EMBED_WIDTH).min(EMBED_WIDTH);
+    let height = get_int(&params, "maxheight", EMBED_HEIGHT).min(EMBED_HEIGHT);
+    let author = author_name(&store, &post.user_id)?;
+    let embed_url = format!("{}/embed/{}", origin, post.id);
+    let html = format!(
+        r#"<iframe src="{}" width="{}" height="{}" style="border: 0; max-width: 100%;" loading="lazy" sandbox="allow-popups allow-popups-to-escape-sandbox"></iframe>"#,
+        embed_url, width, height,
+    );
+
+    let body = serde_json::json!({
+        "version": "1.0",
+        "type": "rich",
+        "title": excerpt(&post.content, 80),
+        "author_name": author,
+        "author_url": format!("{}/{}", origin, author),
+        "provider_name": current_branding(&store)?.site_name,
+        "provider_url": origin,
+        "cache_age": EMBED_CACHE_SECONDS,
+        "html": html,
+        "width": width,
+        "height": height,
+    });
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .header("Cache-Control", format!("public, max-age={}", EMBED_CACHE_SECONDS))
+        .body(serde_json::to_vec(&body)?)
+        .build())
+}
+
+/// `GET /embed/{id}`: one post with no site chrome, for other sites to frame
+pub fn render_embed(req: &Request, post_id: &str) -> anyhow::Result<Response> {
+    let store = store()?;
+    let post = match load_visible_post(&store, post_id)? {
+        Some(post) => post,
+        None => return Ok(ApiError::NotFound("post_not_found", "Post not found".to_string()).into()),
+    };
+    let locale = request_locale(req);
+    let author = author_name(&store, &post.user_id)?;
+    let names = [("username", author.clone()), ("site", current_branding(&store)?.site_name)];
+
+    let html = render_page(&store, "embed.html", locale, context! {
+        origin => origin(req),
+        title => i18n::text(locale, "share.post_title", &names),
+        author,
+        post,
+    })?;
+
+    let mut resp = html_response(html, locale);
+    resp.set_header("cache-control", format!("public, max-age={}", EMBED_CACHE_SECONDS));
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_permalinks_on_this_host_resolve() {
+        let id = "0b7e2e63-8f8a-4b55-9d1c-5e0e3b3a2f11";
+        let url = format!("https://bord.example/posts/{}", id);
+        assert_eq!(permalink_post_id(&url, "bord.example"), Some(id));
+        assert_eq!(permalink_post_id(&format!("{}?utm=x#top", url), "BORD.example"), Some(id));
+        assert_eq!(permalink_post_id(&url, "elsewhere.example"), None);
+        assert_eq!(permalink_post_id("https://bord.example/alice", "bord.example"), None);
+        assert_eq!(permalink_post_id("https://bord.example/posts/not-a-uuid", "bord.example"), None);
+    }
+}
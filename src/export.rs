@@ -0,0 +1,130 @@
+use spin_sdk::http::{Request, Response};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use crate::models::models::{Post, User, Warning, InstanceSettings};
+use crate::core::helpers::{store, now_iso};
+use crate::core::query_params::{parse_query_params, get_string};
+use crate::core::errors::ApiError;
+use crate::auth::user_sessions;
+use crate::follow::{get_followings, get_followers};
+use crate::posts::{posts_by_user, load_post, post_json};
+use crate::core::feed_index::feed_ids;
+use crate::config::*;
+
+/// Opaque cursor for the last post a page returned: base64url of `created_at|id`
+fn encode_cursor(post: &Post) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}|{}", post.created_at, post.id))
+}
+
+fn decode_cursor(cursor: &str) -> Option<(String, String)> {
+    let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+    let (created_at, id) = raw.split_once('|')?;
+    Some((created_at.to_string(), id.to_string()))
+}
+
+/// Page through every public post, oldest first.
+///
+/// Posts are ordered by `(created_at, id)` and the cursor records the last
+/// position returned, so a consumer can resume even if posts are added or
+/// deleted in between pages.
+pub fn export_public_posts(req: Request) -> anyhow::Result<Response> {
+    let store = store()?;
+    let settings: InstanceSettings = store.get_json(INSTANCE_SETTINGS_KEY)?.unwrap_or_default();
+    if !settings.public_export_enabled {
+        return Ok(ApiError::NotFound("export_disabled", "Public export is disabled".to_string()).into());
+    }
+
+    let params = parse_query_params(req.uri());
+    let after = match get_string(&params, "cursor", None) {
+        Some(cursor) => match decode_cursor(&cursor) {
+            Some(position) => Some(position),
+            None => return Ok(ApiError::BadRequest("invalid_cursor", "Invalid cursor".to_string()).into()),
+        },
+        None => None,
+    };
+
+    let feed = feed_ids(&store)?;
+    let mut posts = Vec::new();
+    for id in feed {
+        if let Some(p) = load_post(&store, &id)? {
+            if !p.hidden {
+                posts.push(p);
+            }
+        }
+    }
+    posts.sort_by(|a, b| (&a.created_at, &a.id).cmp(&(&b.created_at, &b.id)));
+
+    let page: Vec<Post> = posts.into_iter()
+        .filter(|p| match &after {
+            Some((created_at, id)) => (&p.created_at, &p.id) > (created_at, id),
+            None => true,
+        })
+        .take(EXPORT_PAGE_SIZE)
+        .collect();
+
+    // A full page means there may be more; an empty next page ends the export
+    let next_cursor = if page.len() == EXPORT_PAGE_SIZE {
+        page.last().map(encode_cursor)
+    } else {
+        None
+    };
+
+    let resp = serde_json::json!({
+        "posts": page.iter().map(post_json).collect::<Vec<_>>(),
+        "next_cursor": next_cursor,
+    });
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&resp)?)
+        .build())
+}
+
+/// Everything stored about the logged-in user, as a single JSON download.
+///
+/// Hidden posts and moderator warnings are included since they are still the
+/// user's data; the password hash and session token IDs are not.
+pub fn export_user_data(user_id: String) -> anyhow::Result<Response> {
+    let store = store()?;
+    let user = match store.get_json::<User>(&user_key(&user_id))? {
+        Some(u) => u,
+        None => return Ok(ApiError::NotFound("user_not_found", "User not found".to_string()).into()),
+    };
+
+    let sessions: Vec<serde_json::Value> = user_sessions(&store, &user_id)?
+        .into_iter()
+        .map(|(_, data)| serde_json::json!({
+            "created_at": data.created_at,
+            "last_used_at": data.last_used_at,
+            "user_agent": data.user_agent,
+            "ip": data.ip,
+            "last_ip": data.last_ip,
+        }))
+        .collect();
+    let warnings: Vec<Warning> = store.get_json(&warnings_key(&user_id))?.unwrap_or_default();
+
+    let archive = serde_json::json!({
+        "exported_at": now_iso(),
+        "profile": {
+            "id": user.id,
+            "username": user.username,
+            "bio": user.bio.clone().unwrap_or_default(),
+            "role": user.role,
+            "created_at": user.created_at,
+            "email": user.email,
+        },
+        "posts": posts_by_user(&store, &user_id)?,
+        "followings": get_followings(&store, &user_id)?,
+        "followers": get_followers(&store, &user_id)?,
+        "sessions": sessions,
+        "warnings": warnings,
+    });
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .header("Content-Disposition", format!("attachment; filename=\"bord-export-{}.json\"", user.username))
+        .body(serde_json::to_vec_pretty(&archive)?)
+        .build())
+}
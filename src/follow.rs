@@ -1,38 +1,77 @@
 use spin_sdk::http::{Request, Response};
-use spin_sdk::key_value::Store;
+use serde::Deserialize;
+use crate::core::db::BordStore;
 use crate::models::models::User;
 use crate::core::helpers::{store, validate_uuid};
-use crate::core::errors::ApiError;
-use crate::auth::validate_token;
+use crate::core::query_params::{parse_query_params, get_bool_flag, get_int};
+use crate::core::errors::{ApiError, FieldError};
+use crate::core::validate::{parse_body, Validate};
+use crate::core::policy::{passes_gate, GatedAction};
+use crate::templates::invalidate_profile_page_for;
+use crate::users::find_user_id_by_username;
+use crate::webhooks;
+use crate::avatars::avatar_url;
 use crate::config::*;
 
-pub fn follow_user(store: &Store, follower_id: &str, following_id: &str) -> anyhow::Result<()> {
+pub fn follow_user(store: &dyn BordStore, follower_id: &str, following_id: &str) -> anyhow::Result<()> {
     let followings_key = followings_key(follower_id);
     let mut followings: Vec<String> = store
         .get_json(&followings_key)?
         .unwrap_or_default();
     
     if !followings.contains(&following_id.to_string()) {
+        // Read (and backfill) the counters before the list changes
+        let following = following_count(store, follower_id)?;
+        let followers = follower_count(store, following_id)?;
+
         followings.push(following_id.to_string());
         store.set_json(&followings_key, &followings)?;
+
+        let followers_key = followers_key(following_id);
+        let mut follower_ids: Vec<String> = store.get_json(&followers_key)?.unwrap_or_default();
+        if !follower_ids.iter().any(|id| id == follower_id) {
+            follower_ids.push(follower_id.to_string());
+            store.set_json(&followers_key, &follower_ids)?;
+        }
+
+        set_count(store, &following_count_key(follower_id), follower_id, following + 1)?;
+        set_count(store, &follower_count_key(following_id), following_id, followers + 1)?;
+
+        webhooks::emit(store, "user.followed", serde_json::json!({
+            "follower_id": follower_id,
+            "following_id": following_id,
+        }))?;
     }
     
     Ok(())
 }
 
-pub fn unfollow_user(store: &Store, follower_id: &str, following_id: &str) -> anyhow::Result<()> {
+pub fn unfollow_user(store: &dyn BordStore, follower_id: &str, following_id: &str) -> anyhow::Result<()> {
     let followings_key = followings_key(follower_id);
     let mut followings: Vec<String> = store
         .get_json(&followings_key)?
         .unwrap_or_default();
     
-    followings.retain(|id| id != following_id);
-    store.set_json(&followings_key, &followings)?;
+    if followings.iter().any(|id| id == following_id) {
+        let following = following_count(store, follower_id)?;
+        let followers = follower_count(store, following_id)?;
+
+        followings.retain(|id| id != following_id);
+        store.set_json(&followings_key, &followings)?;
+
+        let followers_key = followers_key(following_id);
+        let mut follower_ids: Vec<String> = store.get_json(&followers_key)?.unwrap_or_default();
+        follower_ids.retain(|id| id != follower_id);
+        store.set_json(&followers_key, &follower_ids)?;
+
+        set_count(store, &following_count_key(follower_id), follower_id, following.saturating_sub(1))?;
+        set_count(store, &follower_count_key(following_id), following_id, followers.saturating_sub(1))?;
+    }
     
     Ok(())
 }
 
-pub fn get_followings(store: &Store, user_id: &str) -> anyhow::Result<Vec<String>> {
+pub fn get_followings(store: &dyn BordStore, user_id: &str) -> anyhow::Result<Vec<String>> {
     let followings_key = followings_key(user_id);
     let followings: Vec<String> = store
         .get_json(&followings_key)?
@@ -41,46 +80,183 @@ pub fn get_followings(store: &Store, user_id: &str) -> anyhow::Result<Vec<String
     Ok(followings)
 }
 
-pub fn get_followers(store: &Store, user_id: &str) -> anyhow::Result<Vec<String>> {
-    let users: Vec<String> = store.get_json(USERS_LIST_KEY)?.unwrap_or_default();
-    let mut followers = Vec::new();
-    
-    for id in users {
-        let followings_key = followings_key(&id);
-        if let Ok(Some(followings)) = store.get_json::<Vec<String>>(&followings_key) {
-            if followings.contains(&user_id.to_string()) {
-                followers.push(id);
-            }
-        }
-    }
+pub fn get_followers(store: &dyn BordStore, user_id: &str) -> anyhow::Result<Vec<String>> {
+    let followers: Vec<String> = store
+        .get_json(&followers_key(user_id))?
+        .unwrap_or_default();
     
     Ok(followers)
 }
 
-// === HTTP Handlers ===
+pub fn is_following(store: &dyn BordStore, follower_id: &str, following_id: &str) -> anyhow::Result<bool> {
+    Ok(get_followings(store, follower_id)?.iter().any(|id| id == following_id))
+}
 
-pub fn handle_follow(req: Request) -> anyhow::Result<Response> {
-    let user_id = match validate_token(&req) {
-        Some(uid) => uid,
-        None => return Ok(ApiError::Unauthorized.into()),
-    };
+/// Mutual follows: users this user follows who also follow them back
+pub fn get_friends(store: &dyn BordStore, user_id: &str) -> anyhow::Result<Vec<String>> {
+    let followers = get_followers(store, user_id)?;
+    Ok(get_followings(store, user_id)?
+        .into_iter()
+        .filter(|id| followers.contains(id))
+        .collect())
+}
 
-    let store = store();
-    let body = req.body();
-    let value: serde_json::Value = serde_json::from_slice(body)?;
-    let target_user_id = value["target_user_id"].as_str().unwrap_or_default();
+/// Follower count, kept in a counter that is backfilled from the followings lists on first read
+pub fn follower_count(store: &dyn BordStore, user_id: &str) -> anyhow::Result<u64> {
+    let key = follower_count_key(user_id);
+    if let Some(count) = store.get_json::<u64>(&key)? {
+        return Ok(count);
+    }
+    let count = get_followers(store, user_id)?.len() as u64;
+    store.set_json(&key, &count)?;
+    Ok(count)
+}
+
+/// Following count, kept in a counter that is backfilled from the followings list on first read
+pub fn following_count(store: &dyn BordStore, user_id: &str) -> anyhow::Result<u64> {
+    let key = following_count_key(user_id);
+    if let Some(count) = store.get_json::<u64>(&key)? {
+        return Ok(count);
+    }
+    let count = get_followings(store, user_id)?.len() as u64;
+    store.set_json(&key, &count)?;
+    Ok(count)
+}
 
+/// Store a counter and drop the cached profile page that renders it
+fn set_count(store: &dyn BordStore, key: &str, user_id: &str, count: u64) -> anyhow::Result<()> {
+    store.set_json(key, &count)?;
+    invalidate_profile_page_for(store, user_id)
+}
+
+/// Drop a user's followings and followers, on both sides of each relationship
+pub fn remove_user_follows(store: &dyn BordStore, user_id: &str) -> anyhow::Result<()> {
+    for followed in get_followings(store, user_id)? {
+        unfollow_user(store, user_id, &followed)?;
+    }
+    for follower in get_followers(store, user_id)? {
+        unfollow_user(store, &follower, user_id)?;
+    }
+
+    store.delete(&followings_key(user_id))?;
+    store.delete(&followers_key(user_id))?;
+    store.delete(&follower_count_key(user_id))?;
+    store.delete(&following_count_key(user_id))?;
+    
+    Ok(())
+}
+
+/// Why `user_id` may not follow `target_user_id`, if anything
+fn check_follow_target(store: &dyn BordStore, user_id: &str, target_user_id: &str) -> anyhow::Result<Option<ApiError>> {
     if target_user_id.is_empty() || !validate_uuid(target_user_id) || target_user_id == user_id {
-        return Ok(ApiError::BadRequest("Invalid target user".to_string()).into());
+        return Ok(Some(ApiError::BadRequest("invalid_target_user", "Invalid target user".to_string())));
     }
 
     // Verify target user exists
-    let target_key = user_key(target_user_id);
-    if store.get_json::<User>(&target_key)? .is_none() {
-        return Ok(ApiError::NotFound("Target user not found".to_string()).into());
+    if store.get_json::<User>(&user_key(target_user_id))?.is_none() {
+        return Ok(Some(ApiError::NotFound("target_user_not_found", "Target user not found".to_string())));
+    }
+
+    // Following lots of accounts is reserved for established accounts
+    if get_followings(store, user_id)?.len() >= mass_follow_threshold()
+        && !passes_gate(store, user_id, GatedAction::MassFollow)?
+    {
+        return Ok(Some(ApiError::Forbidden));
+    }
+
+    Ok(None)
+}
+
+/// Why a target user ID can't be used, if it can't
+fn target_problem(target_user_id: &str) -> Option<FieldError> {
+    (!validate_uuid(target_user_id))
+        .then(|| FieldError::new("target_user_id", "invalid_target_user", "Invalid target user"))
+}
+
+// === Request bodies ===
+
+/// Body of `POST /follow`: a user ID, or a username for clients that only
+/// know handles (e.g. from profile pages)
+#[derive(Deserialize)]
+struct FollowRequest {
+    #[serde(default)]
+    target_user_id: String,
+    target_username: Option<String>,
+}
+
+impl Validate for FollowRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        match &self.target_username {
+            Some(_) => Vec::new(),
+            None => target_problem(&self.target_user_id).into_iter().collect(),
+        }
     }
+}
 
-    follow_user(&store, &user_id, target_user_id)?;
+/// Body of `POST /unfollow`
+#[derive(Deserialize)]
+struct UnfollowRequest {
+    #[serde(default)]
+    target_user_id: String,
+}
+
+impl Validate for UnfollowRequest {
+    fn validate(&self) -> Vec<FieldError> {
+        target_problem(&self.target_user_id).into_iter().collect()
+    }
+}
+
+/// Body of `POST /follow/batch`; targets are checked one by one by the handler
+#[derive(Deserialize)]
+struct FollowBatch {
+    #[serde(default = "default_batch_action")]
+    action: String,
+    target_user_ids: Option<Vec<String>>,
+}
+
+fn default_batch_action() -> String {
+    "follow".to_string()
+}
+
+impl Validate for FollowBatch {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut problems = Vec::new();
+        if self.action != "follow" && self.action != "unfollow" {
+            problems.push(FieldError::new("action", "invalid_action", "action must be follow or unfollow"));
+        }
+        match &self.target_user_ids {
+            None => problems.push(FieldError::new("target_user_ids", "target_user_ids_required", "target_user_ids required")),
+            Some(ids) if ids.len() > MAX_FOLLOW_BATCH => {
+                problems.push(FieldError::new("target_user_ids", "too_many_targets", format!("At most {} targets per batch", MAX_FOLLOW_BATCH)));
+            }
+            Some(_) => {}
+        }
+        problems
+    }
+}
+
+// === HTTP Handlers ===
+
+pub fn handle_follow(req: Request, user_id: String) -> anyhow::Result<Response> {
+    let store = store()?;
+    let body: FollowRequest = match parse_body(&req) {
+        Ok(body) => body,
+        Err(e) => return Ok(e.into()),
+    };
+
+    let target_user_id = match &body.target_username {
+        Some(username) => match find_user_id_by_username(&store, username)? {
+            Some(id) => id,
+            None => return Ok(ApiError::NotFound("target_user_not_found", "Target user not found".to_string()).into()),
+        },
+        None => body.target_user_id,
+    };
+
+    if let Some(err) = check_follow_target(&store, &user_id, &target_user_id)? {
+        return Ok(err.into());
+    }
+
+    follow_user(&store, &user_id, &target_user_id)?;
 
     Ok(Response::builder()
         .status(200)
@@ -89,22 +265,14 @@ pub fn handle_follow(req: Request) -> anyhow::Result<Response> {
         .build())
 }
 
-pub fn handle_unfollow(req: Request) -> anyhow::Result<Response> {
-    let user_id = match validate_token(&req) {
-        Some(uid) => uid,
-        None => return Ok(ApiError::Unauthorized.into()),
+pub fn handle_unfollow(req: Request, user_id: String) -> anyhow::Result<Response> {
+    let store = store()?;
+    let body: UnfollowRequest = match parse_body(&req) {
+        Ok(body) => body,
+        Err(e) => return Ok(e.into()),
     };
 
-    let store = store();
-    let body = req.body();
-    let value: serde_json::Value = serde_json::from_slice(body)?;
-    let target_user_id = value["target_user_id"].as_str().unwrap_or_default();
-
-    if target_user_id.is_empty() || !validate_uuid(target_user_id) {
-        return Ok(ApiError::BadRequest("Invalid target user".to_string()).into());
-    }
-
-    unfollow_user(&store, &user_id, target_user_id)?;
+    unfollow_user(&store, &user_id, &body.target_user_id)?;
 
     Ok(Response::builder()
         .status(200)
@@ -113,36 +281,96 @@ pub fn handle_unfollow(req: Request) -> anyhow::Result<Response> {
         .build())
 }
 
-pub fn get_followings_list(path: &str) -> anyhow::Result<Response> {
-    let user_id = path.trim_start_matches("/followings/");
-    
-    if user_id.is_empty() || !validate_uuid(user_id) {
-        return Ok(ApiError::BadRequest("User ID required".to_string()).into());
+/// Follow or unfollow several users at once (e.g. onboarding suggestions).
+/// Each target is validated on its own and gets its own status in the results.
+pub fn handle_follow_batch(req: Request, user_id: String) -> anyhow::Result<Response> {
+    let store = store()?;
+    let body: FollowBatch = match parse_body(&req) {
+        Ok(body) => body,
+        Err(e) => return Ok(e.into()),
+    };
+    let unfollow = body.action == "unfollow";
+    let targets = body.target_user_ids.unwrap_or_default();
+
+    let mut results = Vec::new();
+    for target in &targets {
+        let status = if unfollow {
+            if validate_uuid(target) {
+                unfollow_user(&store, &user_id, target)?;
+                "unfollowed"
+            } else {
+                "invalid"
+            }
+        } else {
+            match check_follow_target(&store, &user_id, target)? {
+                None => {
+                    follow_user(&store, &user_id, target)?;
+                    "followed"
+                }
+                Some(ApiError::NotFound(..)) => "not_found",
+                Some(ApiError::Forbidden) => "forbidden",
+                Some(_) => "invalid",
+            }
+        };
+        results.push(serde_json::json!({"target_user_id": target, "status": status}));
     }
 
-    let store = store();
-    let followings = get_followings(&store, user_id)?;
-    
     Ok(Response::builder()
         .status(200)
         .header("Content-Type", "application/json")
-        .body(serde_json::to_vec(&followings)?)
+        .body(serde_json::to_vec(&serde_json::json!({"results": results}))?)
         .build())
 }
 
-pub fn get_followers_list(path: &str) -> anyhow::Result<Response> {
-    let user_id = path.trim_start_matches("/followers/");
-    
-    if user_id.is_empty() || !validate_uuid(user_id) {
-        return Ok(ApiError::BadRequest("User ID required".to_string()).into());
+/// Page of user summaries for a list of IDs; deleted accounts are skipped
+fn expand_users(store: &dyn BordStore, ids: &[String], page: usize) -> anyhow::Result<Vec<serde_json::Value>> {
+    let mut users = Vec::new();
+    for id in ids.iter().skip(page.saturating_sub(1).saturating_mul(USERS_PER_PAGE)).take(USERS_PER_PAGE) {
+        if let Some(u) = store.get_json::<User>(&user_key(id))? {
+            users.push(serde_json::json!({
+                "id": u.id,
+                "username": u.username,
+                "bio": u.bio.unwrap_or_default(),
+                "avatar_url": avatar_url(&u.id),
+            }));
+        }
     }
+    Ok(users)
+}
+
+/// Bare ID array by default; `?expand=true&page=N` returns paginated user summaries
+fn user_list_response(store: &dyn BordStore, req: &Request, ids: Vec<String>) -> anyhow::Result<Response> {
+    let params = parse_query_params(req.uri());
+    let body = if get_bool_flag(&params, "expand") {
+        serde_json::to_vec(&expand_users(store, &ids, get_int(&params, "page", 1))?)?
+    } else {
+        serde_json::to_vec(&ids)?
+    };
 
-    let store = store();
-    let followers = get_followers(&store, user_id)?;
-    
     Ok(Response::builder()
         .status(200)
         .header("Content-Type", "application/json")
-        .body(serde_json::to_vec(&followers)?)
+        .body(body)
         .build())
 }
+
+pub fn get_followings_list(req: Request, user_id: &str) -> anyhow::Result<Response> {
+    let store = store()?;
+    let followings = get_followings(&store, user_id)?;
+    
+    user_list_response(&store, &req, followings)
+}
+
+pub fn get_followers_list(req: Request, user_id: &str) -> anyhow::Result<Response> {
+    let store = store()?;
+    let followers = get_followers(&store, user_id)?;
+    
+    user_list_response(&store, &req, followers)
+}
+
+pub fn get_friends_list(req: Request, user_id: String) -> anyhow::Result<Response> {
+    let store = store()?;
+    let friends = get_friends(&store, &user_id)?;
+
+    user_list_response(&store, &req, friends)
+}
@@ -0,0 +1,61 @@
+use spin_sdk::http::{Request, Response};
+use crate::core::db::BordStore;
+use std::collections::BTreeSet;
+use crate::models::models::Post;
+use crate::core::helpers::{store, validate_uuid};
+use crate::core::errors::ApiError;
+use crate::config::*;
+
+/// Add a view to a post's counter for today
+fn increment_impressions(store: &dyn BordStore, post_id: &str) -> anyhow::Result<()> {
+    let day = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    let key = impressions_key(post_id, &day);
+    let current: u64 = store.get_json(&key)?.unwrap_or_default();
+    store.set_json(&key, &(current + 1))?;
+    Ok(())
+}
+
+/// Drop every daily counter of a deleted post
+pub fn delete_impressions(store: &dyn BordStore, post_id: &str) -> anyhow::Result<()> {
+    for key in store.scan(&post_impressions_prefix(post_id))? {
+        store.delete(&key)?;
+    }
+    Ok(())
+}
+
+/// Record a batch of viewed posts: `{"post_ids": [...]}`.
+/// Duplicates within a batch count once; unknown or hidden posts are skipped.
+pub fn record_impressions(req: Request) -> anyhow::Result<Response> {
+    let value: serde_json::Value = serde_json::from_slice(req.body())?;
+    let ids = match value["post_ids"].as_array() {
+        Some(ids) => ids,
+        None => return Ok(ApiError::BadRequest("post_ids_required", "post_ids required".to_string()).into()),
+    };
+
+    if ids.len() > MAX_IMPRESSIONS_BATCH {
+        return Ok(ApiError::BadRequest("too_many_post_ids", format!("Too many post IDs (max {})", MAX_IMPRESSIONS_BATCH)).into());
+    }
+
+    let ids: BTreeSet<&str> = ids.iter()
+        .filter_map(|id| id.as_str())
+        .filter(|id| validate_uuid(id))
+        .collect();
+
+    let store = store()?;
+    let mut recorded = 0;
+    for id in ids {
+        match store.get_json::<Post>(&post_key(id))? {
+            Some(post) if !post.hidden => {
+                increment_impressions(&store, id)?;
+                recorded += 1;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&serde_json::json!({"recorded": recorded}))?)
+        .build())
+}
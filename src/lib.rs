@@ -11,50 +11,169 @@ mod auth;
 mod users;
 mod posts;
 mod follow;
+mod oauth;
+mod admin;
+mod client_config;
+mod impressions;
+mod export;
+mod branding;
+mod digest;
+mod webhooks;
+mod discovery;
+mod avatars;
+mod embed;
+mod moderation;
+
+#[cfg(all(feature = "client", not(target_arch = "wasm32")))]
+pub mod client;
 
 use core::db;
-use core::helpers;
+use core::seed;
+use core::boot;
 use core::static_server;
+use core::idempotency;
+use core::i18n;
+use core::middleware::{self, Middleware};
 use core::errors::ApiError;
+use core::router::{self, Router};
+use std::sync::OnceLock;
+
+
+pub use db::reset_db_data;
+pub use seed::seed_fixture_data;
 
+/// JSON API routes, served under `/api/v1` and at their original unversioned paths.
+/// `{id}`/`{*_id}` parameters must be UUIDs or the request gets a 400.
+fn api_router() -> &'static Router {
+    static ROUTER: OnceLock<Router> = OnceLock::new();
+    ROUTER.get_or_init(|| {
+        Router::new()
+            .mounted_at(config::API_PREFIX)
+            .route("POST", "/users", |req, _| idempotency::run_once(req, users::create_user))
+            .route("POST", "/login", |req, _| auth::login_user(req))
+            .route("POST", "/logout", |req, _| auth::logout_user(req))
+            .route("POST", "/logout-all", |req, _| auth::logout_all(req))
+            .route("GET", "/sessions", |req, _| auth::list_sessions(req))
+            .route("DELETE", "/sessions/{id}", |req, p| auth::revoke_session(req, &p["id"]))
+            .route("POST", "/password/forgot", |req, _| auth::forgot_password(req))
+            .route("POST", "/password/reset", |req, _| auth::reset_password(req))
+            .route("GET", "/auth/{provider}/start", |req, p| oauth::start(req, &p["provider"]))
+            .route("GET", "/auth/{provider}/callback", |req, p| oauth::callback(req, &p["provider"]))
+            .authed("GET", "/profile", |_, _, uid| users::get_profile(uid))
+            .authed("GET", "/profile/export", |_, _, uid| export::export_user_data(uid))
+            .authed("PUT", "/profile", |req, _, uid| users::update_profile(req, uid))
+            .authed("DELETE", "/profile", |req, _, uid| users::delete_account(req, uid))
+            .authed("POST", "/posts", |req, _, uid| idempotency::run_once(req, |req| posts::create_post(req, uid)))
+            .route("GET", "/posts", |req, _| posts::list_posts(req))
+            .authed("GET", "/posts/held", |_, _, uid| moderation::list_own(uid))
+            .authed("PUT", "/posts/{id}", |req, p, uid| posts::edit_post(req, &p["id"], uid))
+            .authed("DELETE", "/posts/{id}", |_, p, uid| posts::delete_post(&p["id"], uid))
+            .authed("GET", "/feed", |req, _, uid| posts::get_feed(req, uid))
+            .route("POST", "/impressions", |req, _| impressions::record_impressions(req))
+            .authed("POST", "/follow", |req, _, uid| idempotency::run_once(req, |req| follow::handle_follow(req, uid)))
+            .authed("POST", "/unfollow", |req, _, uid| follow::handle_unfollow(req, uid))
+            .authed("POST", "/follow/batch", |req, _, uid| follow::handle_follow_batch(req, uid))
+            .authed("GET", "/friends", |req, _, uid| follow::get_friends_list(req, uid))
+            .route("GET", "/followings/{user_id}", |req, p| follow::get_followings_list(req, &p["user_id"]))
+            .route("GET", "/followers/{user_id}", |req, p| follow::get_followers_list(req, &p["user_id"]))
+            .route("POST", "/admin/posts/{id}/hide", |req, p| admin::hide_post(req, &p["id"]))
+            .route("POST", "/admin/posts/{id}/unhide", |req, p| admin::unhide_post(req, &p["id"]))
+            .route("GET", "/admin/moderation/queue", |req, _| moderation::list_queue(req))
+            .route("POST", "/admin/moderation/{id}/approve", |req, p| moderation::approve(req, &p["id"]))
+            .route("POST", "/admin/moderation/{id}/reject", |req, p| moderation::reject(req, &p["id"]))
+            .route("POST", "/admin/users/{id}/warn", |req, p| admin::warn_user(req, &p["id"]))
+            .route("GET", "/admin/users/{id}/warnings", |req, p| admin::list_warnings(req, &p["id"]))
+            .route("PUT", "/admin/users/{id}/role", |req, p| admin::set_user_role(req, &p["id"]))
+            .route("GET", "/admin/settings", |req, _| admin::get_settings(req))
+            .route("PUT", "/admin/settings", |req, _| admin::update_settings(req))
+            .route("PUT", "/admin/system-account", |req, _| admin::upsert_system_account(req))
+            .route("POST", "/admin/system-account/posts", |req, _| admin::create_system_post(req))
+            .route("POST", "/admin/maintenance/tokens", |req, _| admin::cleanup_tokens(req))
+            .route("POST", "/admin/maintenance/feed", |req, _| admin::compact_feed(req))
+            .route("POST", "/admin/maintenance/expired", |req, _| admin::sweep_expired_keys(req))
+            .route("POST", "/admin/maintenance/webhooks", |req, _| webhooks::retry_deliveries(req))
+            .route("POST", "/admin/webhooks", |req, _| webhooks::create_webhook(req))
+            .route("GET", "/admin/webhooks", |req, _| webhooks::list_webhooks(req))
+            .route("DELETE", "/admin/webhooks/{id}", |req, p| webhooks::delete_webhook(req, &p["id"]))
+            .route("GET", "/export/public-posts", |req, _| export::export_public_posts(req))
+            .route("GET", "/instance", |_, _| branding::get_instance())
+            .route("GET", "/client-config", |_, _| client_config::get_client_config())
+            .route("GET", "/i18n", |req, _| i18n::get_catalog(req))
+            .route("GET", "/asset-manifest.json", |_, _| static_server::serve_manifest())
+            .route("GET", "/users/available", |req, _| users::check_username_available(req))
+            .route("GET", "/users/{id}", |req, p| users::get_user_details(req, &p["id"]))
+    })
+}
 
-pub use db::{init_test_data, reset_db_data};
+/// HTML pages and discovery documents, which aren't versioned
+fn page_router() -> &'static Router {
+    static ROUTER: OnceLock<Router> = OnceLock::new();
+    ROUTER.get_or_init(|| {
+        Router::new()
+            .route("GET", "/.well-known/webfinger", |req, _| discovery::webfinger(req))
+            .route("GET", "/.well-known/nodeinfo", |req, _| discovery::nodeinfo_links(req))
+            .route("GET", "/nodeinfo/2.0", |_, _| discovery::nodeinfo())
+            .route("GET", "/digest/latest", |req, _| digest::render_latest_digest(&req))
+            .route("GET", "/timeline", |req, _| templates::render_timeline(&req))
+            .route("GET", "/posts/{id}", |req, p| templates::render_post_page(&req, &p["id"]))
+            .route("GET", "/avatars/{user_id}", |req, p| avatars::serve_avatar(&req, &p["user_id"]))
+            .route("GET", "/oembed", |req, _| embed::oembed(req))
+            .route("GET", "/embed/{id}", |req, p| embed::render_embed(&req, &p["id"]))
+            .route("GET", "/", |req, _| templates::render_index(&req))
+            .route("GET", "/index.html", |req, _| templates::render_index(&req))
+    })
+}
 
 // === Component entrypoint ===
 #[http_component]
 fn handle(req: Request) -> anyhow::Result<impl IntoResponse> {
-    let _ = db::init_test_data(&helpers::store()); // Initialize test data on first request
-    
-    let path = req.path();
-    let method = req.method();
+    middleware::run(req, MIDDLEWARE, &route)
+}
+
+/// Outermost first: errors from any later layer become JSON 500s, which are
+/// then localized like any other error, and shown as a page to browsers.
+/// The request log sees the final status.
+const MIDDLEWARE: &[Middleware] = &[
+    middleware::request_log,
+    templates::html_errors,
+    middleware::localize,
+    middleware::errors,
+    boot::boot,
+    middleware::cors,
+    middleware::rate_limit,
+    middleware::body_limit,
+];
 
-    match (method.to_string().as_str(), path) {
-        #[cfg(feature = "perf")]
+fn route(req: Request) -> anyhow::Result<spin_sdk::http::Response> {
+    #[cfg(feature = "perf")]
+    match (req.method().to_string().as_str(), req.path()) {
         ("POST", "/dev/ok") => {
-            Ok(spin_sdk::http::Response::builder().status(200).body(b"ok".to_vec()).build())
+            return Ok(spin_sdk::http::Response::builder().status(200).body(b"ok".to_vec()).build());
         },
-        #[cfg(feature = "perf")]
         ("POST", "/dev/reset") => {
-            db::reset_db_data(&helpers::store())?;
-            Ok(spin_sdk::http::Response::builder().status(200).body(b"DB reseted.".to_vec()).build())
+            db::reset_db_data(&core::helpers::store()?)?;
+            return Ok(spin_sdk::http::Response::builder().status(200).body(b"DB reseted.".to_vec()).build());
         },
-        ("POST", "/users") => users::create_user(req),
-        ("POST", "/login") => auth::login_user(req),
-        ("POST", "/logout") => auth::logout_user(req),
-        ("GET", "/profile") => users::get_profile(req),
-        ("PUT", "/profile") => users::update_profile(req),        
-        ("POST", "/posts") => posts::create_post(req),
-        ("GET", "/posts") => posts::list_posts(req),        
-        ("PUT", p) if p.starts_with("/posts/") => posts::edit_post(req),
-        ("DELETE", p) if p.starts_with("/posts/") => posts::delete_post(req),
-        ("GET", "/feed") => posts::get_feed(req),
-        ("POST", "/follow") => follow::handle_follow(req),
-        ("POST", "/unfollow") => follow::handle_unfollow(req),
-        ("GET", p) if p.starts_with("/followings/") => follow::get_followings_list(p),
-        ("GET", p) if p.starts_with("/followers/") => follow::get_followers_list(p),
-        ("GET", p) if p.starts_with("/users/") && p.len() > 7 => users::get_user_details(p),
-        ("GET", p) if !p.contains('.') && p.len() > 1 && p != "/" => templates::render_user_profile(&req, p),
-        ("GET", p) => static_server::serve_static(p),
-        _ => Ok(ApiError::NotFound("No route found".to_string()).into()),
+        _ => {}
+    }
+
+    let req = match api_router().dispatch(req) {
+        Ok(resp) => return resp,
+        Err(req) => *req,
+    };
+    let req = match page_router().dispatch(req) {
+        Ok(resp) => return resp,
+        Err(req) => *req,
+    };
+
+    // Everything else is a GET for a profile page or a static asset
+    let path = req.path();
+    match (req.method().to_string().as_str(), path) {
+        (_, p) if router::strip_mount(p, config::API_PREFIX).is_some() => {
+            Ok(ApiError::NotFound("route_not_found", "No route found".to_string()).into())
+        }
+        ("GET", p) if !p.contains('.') && p.len() > 1 => templates::render_user_profile(&req, p),
+        ("GET", p) => static_server::serve_static(&req, p),
+        _ => Ok(ApiError::NotFound("route_not_found", "No route found".to_string()).into()),
     }
 }
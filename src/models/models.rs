@@ -1,11 +1,31 @@
 use serde::{Serialize, Deserialize};
 
+/// Ordered from least to most privileged
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    Moderator,
+    Admin,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct User {
     pub id: String,
     pub username: String,
     pub password: String,
     pub bio: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub role: Role,
+    /// IANA time zone name, used when rendering times for this user
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Where password reset tokens are mailed; only shown to the user themselves
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -15,12 +35,172 @@ pub struct Post {
     pub content: String,
     pub created_at: String,
     pub updated_at: Option<String>,
+    #[serde(default)]
+    pub hidden: bool,
+    /// Renderer that produced `content`; older posts are re-rendered on read
+    #[serde(default)]
+    pub render_version: u32,
+    /// What the moderation filter reported when the post was submitted; only shown to moderators
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moderation: Option<ModerationScores>,
+}
+
+/// Scores forwarded by the moderation filter in `X-Moderation-*` headers
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct ModerationScores {
+    #[serde(default)]
+    pub sentiment: Option<f64>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// Which check produced the scores, e.g. "llm" or "wordlist"
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+/// Most recent recorded use of a session, overriding the `TokenData` fields
+#[derive(Serialize, Deserialize)]
+pub struct SessionActivity {
+    pub last_used_at: String,
+    pub last_ip: String,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct TokenData {
     pub user_id: String,
     pub created_at: String,
+    #[serde(default)]
+    pub last_used_at: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Client IP the session was created from
+    #[serde(default)]
+    pub ip: Option<String>,
+    /// Client IP of the most recent (recorded) use
+    #[serde(default)]
+    pub last_ip: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PasswordResetData {
+    pub user_id: String,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct OAuthState {
+    pub provider: String,
+    pub link_user_id: Option<String>,
+    pub created_at: String,
+}
+
+/// Failed login attempts for a username or IP within the current window
+#[derive(Serialize, Deserialize)]
+pub struct LoginFailures {
+    pub count: u32,
+    pub window_start: i64,
+    pub locked_until: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FooterLink {
+    pub label: String,
+    pub url: String,
+}
+
+/// Instance-wide settings changed at runtime by admins
+#[derive(Serialize, Deserialize, Default)]
+pub struct InstanceSettings {
+    /// Serve GET /export/public-posts (off unless an admin opts in)
+    #[serde(default)]
+    pub public_export_enabled: bool,
+    /// Branding overrides; unset fields fall back to the BORD_* environment defaults
+    #[serde(default)]
+    pub site_name: Option<String>,
+    #[serde(default)]
+    pub logo_url: Option<String>,
+    #[serde(default)]
+    pub accent_color: Option<String>,
+    #[serde(default)]
+    pub footer_links: Vec<FooterLink>,
+    /// Bumped on every change so cached pages rendered with old settings go stale
+    #[serde(default)]
+    pub revision: u64,
+}
+
+/// Rendered HTML cached in KV; stale once `version` differs from the current assets
+#[derive(Serialize, Deserialize)]
+pub struct CachedPage {
+    pub version: String,
+    pub html: String,
+}
+
+/// Left behind when an account is deleted so references to it still resolve
+#[derive(Serialize, Deserialize)]
+pub struct Tombstone {
+    pub id: String,
+    pub deleted_at: String,
+}
+
+/// Moderator warning issued to a user
+#[derive(Serialize, Deserialize)]
+pub struct Warning {
+    pub moderator_id: String,
+    pub reason: String,
+    pub created_at: String,
+}
+
+/// Where a held post is in review; approved posts are published and dropped from the queue
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HeldStatus {
+    Pending,
+    Rejected,
+}
+
+/// Post the moderation filter held for review instead of letting it publish
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HeldPost {
+    pub id: String,
+    pub user_id: String,
+    /// Raw content as submitted; it is sanitized when published
+    pub content: String,
+    pub created_at: String,
+    /// Why the filter held it, if it said
+    #[serde(default)]
+    pub reason: Option<String>,
+    pub status: HeldStatus,
+    /// Filter scores, kept for the post once it is approved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moderation: Option<ModerationScores>,
+    #[serde(default)]
+    pub moderator_id: Option<String>,
+    #[serde(default)]
+    pub rejection_reason: Option<String>,
+    #[serde(default)]
+    pub reviewed_at: Option<String>,
+}
+
+/// Outgoing webhook registered by an admin
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    pub events: Vec<String>,
+    /// HMAC-SHA256 key for the X-Bord-Signature header
+    pub secret: String,
+    pub created_at: String,
+}
+
+/// One event queued for one webhook
+#[derive(Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: String,
+    pub payload: String,
+    pub attempts: u32,
+    /// Unix seconds; a delivery is retried once this has passed
+    pub next_attempt_at: i64,
 }
 
 #[allow(dead_code)]
@@ -0,0 +1,317 @@
+//! Review queue for posts the moderation filter holds back. The filter in front
+//! of the app forwards such a `POST /posts` with `X-Moderation-Action: hold`;
+//! the post is stored as a `HeldPost` instead of published, and moderators
+//! approve (publish) or reject it. Rejected posts stay visible to their author
+//! under `GET /posts/held` and are announced with a `post.rejected` webhook.
+//!
+//! The filter's scores for a post (`X-Moderation-Sentiment`, `-Categories` and
+//! `-Source`) are stored on it whether it is held or not.
+//!
+//! The headers only count when the request also carries `X-Moderation-Secret`
+//! matching `BORD_MODERATION_FILTER_SECRET`, so clients can't forge them.
+
+use spin_sdk::http::{Request, Response};
+use serde::Deserialize;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+use crate::models::models::{HeldPost, HeldStatus, ModerationScores};
+use crate::core::db::{add_member, delete_set, members, remove_member, BordStore};
+use crate::core::helpers::{store, now_iso};
+use crate::core::errors::{ApiError, FieldError};
+use crate::core::validate::{parse_body, Validate};
+use crate::core::permissions::{authorize, Permission};
+use crate::posts::{publish_post, moderator_post_json};
+use crate::users::sanitize_text;
+use crate::webhooks;
+use crate::config::*;
+
+/// Constant-time check of the presented secret against the configured one
+fn secret_matches(secret: Option<&str>, presented: Option<&str>) -> bool {
+    let (Some(secret), Some(presented)) = (secret, presented) else {
+        return false;
+    };
+    let mac = |value: &str| {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(value.as_bytes());
+        mac
+    };
+    mac(presented).verify_slice(&mac(secret).finalize().into_bytes()).is_ok()
+}
+
+/// Whether this request was forwarded by the moderation filter
+fn from_filter(req: &Request) -> bool {
+    let presented = req.header(MODERATION_SECRET_HEADER).and_then(|h| h.as_str());
+    secret_matches(moderation_filter_secret().as_deref(), presented)
+}
+
+/// Whether the moderation filter asked for this request's post to be held
+pub fn is_held(req: &Request) -> bool {
+    from_filter(req) && req.header(MODERATION_ACTION_HEADER)
+        .and_then(|h| h.as_str())
+        .is_some_and(|action| action.trim().eq_ignore_ascii_case("hold"))
+}
+
+/// The filter's scores for the post in this request, if it sent any
+pub fn scores(req: &Request) -> Option<ModerationScores> {
+    if !from_filter(req) {
+        return None;
+    }
+    let header = |name: &str| req.header(name).and_then(|h| h.as_str());
+    parse_scores(
+        header(MODERATION_SENTIMENT_HEADER),
+        header(MODERATION_CATEGORIES_HEADER),
+        header(MODERATION_SOURCE_HEADER),
+    )
+}
+
+/// Short lowercase identifier such as a category or source name
+fn label(value: &str) -> Option<String> {
+    let value = value.trim().to_ascii_lowercase();
+    let valid = !value.is_empty()
+        && value.len() <= MAX_MODERATION_LABEL_LENGTH
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    valid.then_some(value)
+}
+
+/// Header values that don't parse are dropped rather than failing the post
+fn parse_scores(sentiment: Option<&str>, categories: Option<&str>, source: Option<&str>) -> Option<ModerationScores> {
+    let scores = ModerationScores {
+        sentiment: sentiment.and_then(|s| s.trim().parse::<f64>().ok()).filter(|s| s.is_finite()),
+        categories: categories
+            .map(|list| list.split(',').filter_map(label).take(MAX_MODERATION_CATEGORIES).collect())
+            .unwrap_or_default(),
+        source: source.and_then(label),
+    };
+    (scores != ModerationScores::default()).then_some(scores)
+}
+
+/// Queue an already validated post for review
+pub fn hold_post(store: &dyn BordStore, user_id: &str, content: &str, reason: Option<&str>, moderation: Option<ModerationScores>) -> anyhow::Result<HeldPost> {
+    let held = HeldPost {
+        id: Uuid::new_v4().to_string(),
+        user_id: user_id.to_string(),
+        content: content.to_string(),
+        created_at: now_iso(),
+        reason: reason.map(|r| sanitize_text(r.trim())).filter(|r| !r.is_empty()),
+        status: HeldStatus::Pending,
+        moderation,
+        moderator_id: None,
+        rejection_reason: None,
+        reviewed_at: None,
+    };
+    store.set_json(&held_post_key(&held.id), &held)?;
+    add_member(store, HELD_POSTS_KEY, &held.id)?;
+    Ok(held)
+}
+
+/// The reason header the filter sent along with a hold, if any
+pub fn hold_reason(req: &Request) -> Option<String> {
+    if !from_filter(req) {
+        return None;
+    }
+    req.header(MODERATION_REASON_HEADER)
+        .and_then(|h| h.as_str())
+        .map(str::to_string)
+}
+
+/// A held post as its author sees it, without the filter's scores
+pub fn held_json(held: &HeldPost) -> serde_json::Value {
+    let mut json = serde_json::json!(held);
+    if let Some(fields) = json.as_object_mut() {
+        fields.remove("moderation");
+    }
+    json
+}
+
+/// Held posts matching `keep`, oldest first
+fn held_posts(store: &dyn BordStore, keep: impl Fn(&HeldPost) -> bool) -> anyhow::Result<Vec<HeldPost>> {
+    let mut posts = Vec::new();
+    for id in members(store, HELD_POSTS_KEY)? {
+        if let Some(held) = store.get_json::<HeldPost>(&held_post_key(&id))? {
+            if keep(&held) {
+                posts.push(held);
+            }
+        }
+    }
+    posts.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    Ok(posts)
+}
+
+/// Drop every held post of a deleted account
+pub fn delete_user_held_posts(store: &dyn BordStore, user_id: &str) -> anyhow::Result<()> {
+    for held in held_posts(store, |h| h.user_id == user_id)? {
+        store.delete(&held_post_key(&held.id))?;
+        remove_member(store, HELD_POSTS_KEY, &held.id)?;
+    }
+    Ok(())
+}
+
+/// Drop every held post, for a database reset
+pub fn clear_held_posts(store: &dyn BordStore) -> anyhow::Result<()> {
+    for id in members(store, HELD_POSTS_KEY)? {
+        store.delete(&held_post_key(&id))?;
+    }
+    delete_set(store, HELD_POSTS_KEY)
+}
+
+fn json_response(status: u16, body: &impl serde::Serialize) -> anyhow::Result<Response> {
+    Ok(Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(body)?)
+        .build())
+}
+
+/// A pending post, or the error to answer with
+fn pending_post(store: &dyn BordStore, id: &str) -> anyhow::Result<Result<HeldPost, ApiError>> {
+    Ok(match store.get_json::<HeldPost>(&held_post_key(id))? {
+        Some(held) if held.status == HeldStatus::Pending => Ok(held),
+        Some(_) => Err(ApiError::Conflict("already_reviewed", "Post was already reviewed".to_string())),
+        None => Err(ApiError::NotFound("held_post_not_found", "Held post not found".to_string())),
+    })
+}
+
+pub fn list_queue(req: Request) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ModeratePosts) {
+        return Ok(e.into());
+    }
+
+    let queue = held_posts(&store()?, |h| h.status == HeldStatus::Pending)?;
+    json_response(200, &queue)
+}
+
+/// Publish a held post as its author, as if it had never been held
+pub fn approve(req: Request, id: &str) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ModeratePosts) {
+        return Ok(e.into());
+    }
+
+    let store = store()?;
+    let held = match pending_post(&store, id)? {
+        Ok(held) => held,
+        Err(e) => return Ok(e.into()),
+    };
+
+    let post = publish_post(&store, &held.user_id, &held.content, held.moderation.clone())?;
+    store.delete(&held_post_key(id))?;
+    remove_member(&store, HELD_POSTS_KEY, id)?;
+
+    json_response(201, &moderator_post_json(&post))
+}
+
+/// Body of `POST /admin/moderation/{id}/reject`; the body itself is optional
+#[derive(Deserialize)]
+struct RejectBody {
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+impl RejectBody {
+    fn reason(&self) -> Option<&str> {
+        self.reason.as_deref().map(str::trim).filter(|r| !r.is_empty())
+    }
+}
+
+impl Validate for RejectBody {
+    fn validate(&self) -> Vec<FieldError> {
+        match self.reason() {
+            Some(r) if r.len() > MAX_REJECTION_REASON_LENGTH => vec![FieldError::new(
+                "reason",
+                "reason_too_long",
+                format!("Reason is limited to {} characters", MAX_REJECTION_REASON_LENGTH),
+            )],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Keep a held post from publishing; the author sees it, with the reason, under `GET /posts/held`
+pub fn reject(req: Request, id: &str) -> anyhow::Result<Response> {
+    let moderator = match authorize(&req, Permission::ModeratePosts) {
+        Ok(u) => u,
+        Err(e) => return Ok(e.into()),
+    };
+
+    let body: RejectBody = match parse_body(&req) {
+        Ok(body) => body,
+        Err(e) => return Ok(e.into()),
+    };
+    let reason = body.reason();
+
+    let store = store()?;
+    let mut held = match pending_post(&store, id)? {
+        Ok(held) => held,
+        Err(e) => return Ok(e.into()),
+    };
+
+    held.status = HeldStatus::Rejected;
+    held.moderator_id = Some(moderator.id);
+    held.rejection_reason = reason.map(sanitize_text);
+    held.reviewed_at = Some(now_iso());
+    store.set_json(&held_post_key(id), &held)?;
+
+    webhooks::emit(&store, "post.rejected", serde_json::json!({
+        "id": held.id,
+        "user_id": held.user_id,
+        "reason": held.rejection_reason,
+    }))?;
+
+    json_response(200, &held)
+}
+
+/// The caller's own held posts, pending and rejected
+pub fn list_own(user_id: String) -> anyhow::Result<Response> {
+    let posts: Vec<_> = held_posts(&store()?, |h| h.user_id == user_id)?.iter().map(held_json).collect();
+    json_response(200, &posts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::MemoryStore;
+
+    #[test]
+    fn held_posts_are_listed_oldest_first_per_author() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+
+        let first = hold_post(store, "u1", "first", Some("spam?"), None).unwrap();
+        let second = hold_post(store, "u1", "second", None, None).unwrap();
+        hold_post(store, "u2", "other", Some("  "), None).unwrap();
+
+        let own = held_posts(store, |h| h.user_id == "u1").unwrap();
+        let ids: Vec<&str> = own.iter().map(|h| h.id.as_str()).collect();
+        assert_eq!(ids, [first.id.as_str(), second.id.as_str()]);
+        assert_eq!(own[0].reason.as_deref(), Some("spam?"));
+
+        delete_user_held_posts(store, "u1").unwrap();
+        let rest = held_posts(store, |_| true).unwrap();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].reason, None);
+    }
+
+    #[test]
+    fn filter_secret_must_match() {
+        assert!(secret_matches(Some("filter-secret"), Some("filter-secret")));
+        assert!(!secret_matches(Some("filter-secret"), Some("filter-secreT")));
+        assert!(!secret_matches(Some("filter-secret"), Some("")));
+        assert!(!secret_matches(Some("filter-secret"), None));
+        assert!(!secret_matches(None, Some("filter-secret")));
+    }
+
+    #[test]
+    fn filter_scores_are_parsed_leniently() {
+        let scores = parse_scores(Some(" -0.82 "), Some("Spam, harassment,,bad label!"), Some("LLM")).unwrap();
+        assert_eq!(scores.sentiment, Some(-0.82));
+        assert_eq!(scores.categories, ["spam", "harassment"]);
+        assert_eq!(scores.source.as_deref(), Some("llm"));
+
+        let partial = parse_scores(Some("NaN"), None, Some("wordlist")).unwrap();
+        assert_eq!(partial.sentiment, None);
+        assert!(partial.categories.is_empty());
+
+        assert_eq!(parse_scores(None, None, None), None);
+        assert_eq!(parse_scores(Some("high"), Some(""), Some(" ")), None);
+    }
+}
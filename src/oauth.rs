@@ -0,0 +1,352 @@
+use spin_sdk::http::{Method, Request, Response};
+use crate::core::db::BordStore;
+use spin_sdk::variables;
+use uuid::Uuid;
+use crate::models::models::{User, Role, OAuthState};
+use crate::core::helpers::{store, hash_password, now_iso};
+use crate::core::query_params::{parse_query_params, get_string};
+use crate::core::errors::ApiError;
+use crate::auth::{validate_token, issue_token};
+use crate::users::{is_signup_blocked, register_user, username_in_use};
+use crate::config::*;
+
+struct Provider {
+    name: &'static str,
+    authorize_url: &'static str,
+    token_url: &'static str,
+    userinfo_url: &'static str,
+    scope: &'static str,
+}
+
+const PROVIDERS: &[Provider] = &[
+    Provider {
+        name: "github",
+        authorize_url: "https://github.com/login/oauth/authorize",
+        token_url: "https://github.com/login/oauth/access_token",
+        userinfo_url: "https://api.github.com/user",
+        scope: "read:user",
+    },
+    Provider {
+        name: "google",
+        authorize_url: "https://accounts.google.com/o/oauth2/v2/auth",
+        token_url: "https://oauth2.googleapis.com/token",
+        userinfo_url: "https://openidconnect.googleapis.com/v1/userinfo",
+        scope: "openid profile email",
+    },
+];
+
+fn find_provider(name: &str) -> Option<&'static Provider> {
+    PROVIDERS.iter().find(|p| p.name == name)
+}
+
+/// Provider credentials come from Spin variables, e.g. `github_client_id`;
+/// a provider with empty credentials is treated as disabled
+fn client_credentials(provider: &Provider) -> Option<(String, String)> {
+    let id = variables::get(&format!("{}_client_id", provider.name)).ok()?;
+    let secret = variables::get(&format!("{}_client_secret", provider.name)).ok()?;
+    if id.is_empty() || secret.is_empty() {
+        return None;
+    }
+    Some((id, secret))
+}
+
+/// Names of providers that have credentials configured
+pub fn enabled_providers() -> Vec<&'static str> {
+    PROVIDERS.iter()
+        .filter(|p| client_credentials(p).is_some())
+        .map(|p| p.name)
+        .collect()
+}
+
+fn redirect_uri(provider: &Provider) -> anyhow::Result<String> {
+    let base = variables::get("oauth_redirect_base")
+        .map_err(|e| anyhow::anyhow!("Missing oauth_redirect_base: {:?}", e))?;
+    Ok(format!("{}/auth/{}/callback", base.trim_end_matches('/'), provider.name))
+}
+
+fn send_json(request: Request) -> anyhow::Result<serde_json::Value> {
+    let resp: Response = spin_sdk::http::run(spin_sdk::http::send(request))
+        .map_err(|e| anyhow::anyhow!("OAuth request failed: {:?}", e))?;
+    if !(200..300).contains(resp.status()) {
+        anyhow::bail!("OAuth provider returned status {}", resp.status());
+    }
+    Ok(serde_json::from_slice(resp.body())?)
+}
+
+/// Exchange an authorization code for a provider access token
+fn exchange_code(provider: &Provider, code: &str) -> anyhow::Result<String> {
+    let (client_id, client_secret) = client_credentials(provider)
+        .ok_or_else(|| anyhow::anyhow!("OAuth provider {} not configured", provider.name))?;
+    let form = format!(
+        "client_id={}&client_secret={}&code={}&redirect_uri={}&grant_type=authorization_code",
+        urlencoding::encode(&client_id),
+        urlencoding::encode(&client_secret),
+        urlencoding::encode(code),
+        urlencoding::encode(&redirect_uri(provider)?),
+    );
+
+    let request = Request::builder()
+        .method(Method::Post)
+        .uri(provider.token_url)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Accept", "application/json")
+        .body(form.into_bytes())
+        .build();
+
+    let value = send_json(request)?;
+    value["access_token"]
+        .as_str()
+        .map(|t| t.to_string())
+        .ok_or_else(|| anyhow::anyhow!("No access token in OAuth response"))
+}
+
+/// Fetch the provider account as (external id, preferred username)
+fn fetch_identity(provider: &Provider, access_token: &str) -> anyhow::Result<(String, String)> {
+    let request = Request::builder()
+        .method(Method::Get)
+        .uri(provider.userinfo_url)
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("Accept", "application/json")
+        .header("User-Agent", "bord")
+        .build();
+
+    let value = send_json(request)?;
+
+    // GitHub uses a numeric `id` + `login`, OpenID Connect uses `sub` + `email`
+    let external_id = match (&value["id"], &value["sub"]) {
+        (serde_json::Value::Number(n), _) => n.to_string(),
+        (_, serde_json::Value::String(s)) => s.clone(),
+        _ => anyhow::bail!("No account id in OAuth profile"),
+    };
+    let username = value["login"].as_str()
+        .or_else(|| value["email"].as_str().and_then(|e| e.split('@').next()))
+        .unwrap_or(provider.name)
+        .to_string();
+
+    Ok((external_id, username))
+}
+
+/// Derive a free local username from the provider handle
+fn unique_username(store: &dyn BordStore, preferred: &str) -> anyhow::Result<String> {
+    let base: String = preferred
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        .take(MAX_USERNAME_LENGTH - 5)
+        .collect();
+    let base = if base.len() < MIN_USERNAME_LENGTH { format!("user_{}", base) } else { base };
+
+    let mut candidate = base.clone();
+    while is_signup_blocked(&candidate) || username_in_use(store, &candidate)? {
+        candidate = format!("{}_{}", base, &Uuid::new_v4().to_string()[0..4]);
+    }
+    Ok(candidate)
+}
+
+/// Unlink every provider account of a deleted user
+pub fn delete_user_links(store: &dyn BordStore, user_id: &str) -> anyhow::Result<()> {
+    for key in store.scan(OAUTH_LINK_PREFIX)? {
+        if store.get_json::<String>(&key)?.as_deref() == Some(user_id) {
+            store.delete(&key)?;
+        }
+    }
+    Ok(())
+}
+
+/// Create a local account for a first-time OAuth login
+fn create_oauth_user(store: &dyn BordStore, preferred_username: &str) -> anyhow::Result<String> {
+    let id = Uuid::new_v4().to_string();
+    let user = User {
+        id: id.clone(),
+        username: unique_username(store, preferred_username)?,
+        // Unguessable password; the user can set a real one via password reset
+        password: hash_password(&Uuid::new_v4().to_string())?,
+        bio: None,
+        created_at: Some(now_iso()),
+        role: Role::User,
+        timezone: None,
+        email: None,
+    };
+    register_user(store, &user)?;
+
+    Ok(id)
+}
+
+/// `Set-Cookie` value binding an OAuth round-trip to this browser. `SameSite=Lax`
+/// because the callback is a top-level navigation back from the provider.
+fn state_cookie(state: &str, max_age_seconds: i64) -> String {
+    format!(
+        "{}={}; Path=/auth; HttpOnly; Secure; SameSite=Lax; Max-Age={}",
+        OAUTH_STATE_COOKIE_NAME, state, max_age_seconds
+    )
+}
+
+/// Whether the callback's `state` is the one this browser was sent off with
+fn state_matches_cookie(cookies: Option<&str>, state: &str) -> bool {
+    cookies
+        .into_iter()
+        .flat_map(|c| c.split(';'))
+        .filter_map(|c| c.trim().split_once('='))
+        .find(|(name, _)| *name == OAUTH_STATE_COOKIE_NAME)
+        .is_some_and(|(_, value)| !value.is_empty() && value == state)
+}
+
+/// A stored state is only good for the provider it was issued for, within its window
+fn state_valid(data: &OAuthState, provider: &str, now: chrono::DateTime<chrono::Utc>) -> bool {
+    let expired = match chrono::DateTime::parse_from_rfc3339(&data.created_at) {
+        Ok(created) => (now - created.with_timezone(&chrono::Utc)).num_minutes() > OAUTH_STATE_EXPIRATION_MINUTES,
+        Err(_) => true,
+    };
+    !expired && data.provider == provider
+}
+
+pub fn start(req: Request, provider_name: &str) -> anyhow::Result<Response> {
+    let provider = match find_provider(provider_name) {
+        Some(p) => p,
+        None => return Ok(ApiError::NotFound("unknown_provider", "Unknown OAuth provider".to_string()).into()),
+    };
+    let client_id = match client_credentials(provider) {
+        Some((id, _)) => id,
+        None => return Ok(ApiError::NotFound("provider_not_configured", "OAuth provider not configured".to_string()).into()),
+    };
+
+    // A logged-in caller links the provider account to their existing user
+    let state = Uuid::new_v4().to_string();
+    let data = OAuthState {
+        provider: provider.name.to_string(),
+        link_user_id: validate_token(&req),
+        created_at: now_iso(),
+    };
+    store()?.set_json_ttl(&oauth_state_key(&state), &data, OAUTH_STATE_EXPIRATION_MINUTES * 60)?;
+
+    let location = format!(
+        "{}?client_id={}&redirect_uri={}&response_type=code&scope={}&state={}",
+        provider.authorize_url,
+        urlencoding::encode(&client_id),
+        urlencoding::encode(&redirect_uri(provider)?),
+        urlencoding::encode(provider.scope),
+        state,
+    );
+
+    Ok(Response::builder()
+        .status(302)
+        .header("Location", location)
+        .header("Set-Cookie", state_cookie(&state, OAUTH_STATE_EXPIRATION_MINUTES * 60))
+        .build())
+}
+
+pub fn callback(req: Request, provider_name: &str) -> anyhow::Result<Response> {
+    let provider = match find_provider(provider_name) {
+        Some(p) => p,
+        None => return Ok(ApiError::NotFound("unknown_provider", "Unknown OAuth provider".to_string()).into()),
+    };
+
+    let params = parse_query_params(req.uri());
+    let code = get_string(&params, "code", None).unwrap_or_default();
+    let state = get_string(&params, "state", None).unwrap_or_default();
+    if code.is_empty() || state.is_empty() {
+        return Ok(ApiError::BadRequest("missing_code_or_state", "Missing code or state".to_string()).into());
+    }
+
+    // A state that arrives in another browser than the one that started the login is
+    // a forged callback (login CSRF), not a round-trip
+    if !state_matches_cookie(req.header("Cookie").and_then(|h| h.as_str()), &state) {
+        return Ok(ApiError::BadRequest("invalid_oauth_state", "Invalid OAuth state".to_string()).into());
+    }
+
+    let store = store()?;
+
+    // State is single use and bound to the provider it was issued for
+    let state_key = oauth_state_key(&state);
+    let data = match store.get_json_ttl::<OAuthState>(&state_key)? {
+        Some(d) => d,
+        None => return Ok(ApiError::BadRequest("invalid_oauth_state", "Invalid OAuth state".to_string()).into()),
+    };
+    store.delete(&state_key)?;
+
+    if !state_valid(&data, provider.name, chrono::Utc::now()) {
+        return Ok(ApiError::BadRequest("invalid_oauth_state", "Invalid OAuth state".to_string()).into());
+    }
+
+    let access_token = exchange_code(provider, &code)?;
+    let (external_id, preferred_username) = fetch_identity(provider, &access_token)?;
+
+    let link_key = oauth_link_key(provider.name, &external_id);
+    let user_id = match store.get_json::<String>(&link_key)? {
+        Some(uid) if store.get_json::<User>(&user_key(&uid))?.is_some() => uid,
+        _ => {
+            let uid = match data.link_user_id {
+                Some(uid) => uid,
+                None => create_oauth_user(&store, &preferred_username)?,
+            };
+            store.set_json(&link_key, &uid)?;
+            uid
+        }
+    };
+
+    let token = issue_token(&store, &user_id, &req)?;
+    let resp = serde_json::json!({
+        "token": token,
+        "user_id": user_id
+    });
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .header("Set-Cookie", state_cookie("", 0))
+        .body(serde_json::to_vec(&resp)?)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::MemoryStore;
+
+    fn state(provider: &str, created: chrono::DateTime<chrono::Utc>) -> OAuthState {
+        OAuthState {
+            provider: provider.to_string(),
+            link_user_id: None,
+            created_at: created.to_rfc3339(),
+        }
+    }
+
+    #[test]
+    fn callback_state_must_match_the_browser_cookie() {
+        assert!(state_matches_cookie(Some("theme=dark; bord_oauth_state=abc"), "abc"));
+        assert!(!state_matches_cookie(Some("bord_oauth_state=abc"), "abd"));
+        assert!(!state_matches_cookie(Some("bord_oauth_state="), ""));
+        assert!(!state_matches_cookie(Some("other_state=abc"), "abc"));
+        assert!(!state_matches_cookie(None, "abc"));
+    }
+
+    #[test]
+    fn stored_state_is_bound_to_provider_and_window() {
+        let now = chrono::Utc::now();
+        let minutes_ago = |m| now - chrono::Duration::minutes(m);
+        assert!(state_valid(&state("github", minutes_ago(1)), "github", now));
+        assert!(!state_valid(&state("github", minutes_ago(1)), "google", now));
+        assert!(!state_valid(&state("github", minutes_ago(OAUTH_STATE_EXPIRATION_MINUTES + 1)), "github", now));
+
+        let mut garbled = state("github", now);
+        garbled.created_at = "yesterday".to_string();
+        assert!(!state_valid(&garbled, "github", now));
+    }
+
+    #[test]
+    fn unique_username_avoids_taken_and_reserved_names() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+
+        assert_eq!(unique_username(store, "alice.smith!").unwrap(), "alicesmith");
+        assert_eq!(unique_username(store, "al").unwrap(), "user_al");
+
+        store.set_json(&username_key("alice"), &"u1".to_string()).unwrap();
+        let taken = unique_username(store, "alice").unwrap();
+        assert!(taken.starts_with("alice_") && taken.len() == "alice_".len() + 4, "{}", taken);
+
+        let reserved = unique_username(store, SYSTEM_USERNAME).unwrap();
+        assert!(!is_signup_blocked(&reserved), "{}", reserved);
+
+        let long = unique_username(store, &"x".repeat(100)).unwrap();
+        assert!(long.len() <= MAX_USERNAME_LENGTH);
+    }
+}
@@ -1,72 +1,138 @@
 use spin_sdk::http::{Request, Response};
+use serde::Deserialize;
+use crate::core::db::{delete_set, BordStore};
 use uuid::Uuid;
 use regex::Regex;
 use html_escape::encode_double_quoted_attribute;
 use ammonia::Builder;
 use std::sync::OnceLock;
-use crate::models::models::User;
-use crate::models::models::Post;
-use crate::core::helpers::{store, now_iso, validate_uuid};
+use crate::models::models::{Post, ModerationScores};
+use crate::core::helpers::{store, now_iso};
 use crate::core::query_params::{parse_query_params, get_string, get_bool_flag, get_int};
-use crate::core::errors::ApiError;
+use crate::core::errors::{ApiError, FieldError};
+use crate::core::validate::{parse_body, Validate};
+use crate::core::policy::{adjust_post_count, passes_gate, GatedAction};
+use crate::core::permissions::{has_permission, Permission};
 use crate::auth::validate_token;
+use crate::users::find_user_id_by_username;
+use crate::core::feed_index::{self, for_each_feed_id, user_post_ids};
+use crate::core::etag::json_with_etag;
+use crate::templates::invalidate_profile_page_for;
+use crate::webhooks;
+use crate::moderation;
+use crate::impressions::delete_impressions;
+use crate::avatars::avatar_url;
 use crate::config::*;
 
-pub fn create_post(req: Request) -> anyhow::Result<Response> {
-    let user_id = match validate_token(&req) {
-        Some(uid) => uid,
-        None => return Ok(ApiError::Unauthorized.into()),
+/// Body of `POST /posts`, `PUT /posts/{id}` and system account posts
+#[derive(Deserialize)]
+pub struct PostContent {
+    #[serde(default)]
+    pub content: String,
+}
+
+impl Validate for PostContent {
+    fn validate(&self) -> Vec<FieldError> {
+        content_problem(&self.content).into_iter().collect()
+    }
+}
+
+pub fn create_post(req: Request, user_id: String) -> anyhow::Result<Response> {
+    let store = store()?;
+
+    let body: PostContent = match parse_body(&req) {
+        Ok(body) => body,
+        Err(e) => return Ok(e.into()),
     };
+    let content = body.content.as_str();
 
-    let store = store();
-    let body = req.body();
+    if let Some(remaining) = post_cooldown_remaining(&store, &user_id)? {
+        return Ok(ApiError::TooManyRequests(remaining).into());
+    }
 
-    let value: serde_json::Value = serde_json::from_slice(body)?;
-    let content = value["content"].as_str().unwrap_or_default();
-    let id = Uuid::new_v4().to_string();
+    // New accounts can't post links until they are established
+    if url_regex().is_match(content) && !passes_gate(&store, &user_id, GatedAction::LinkPost)? {
+        return Ok(ApiError::Forbidden.into());
+    }
 
-    // Add validation
-    if content.is_empty() || content.len() > MAX_POST_LENGTH {
-        return Ok(ApiError::BadRequest("Invalid content".to_string()).into());
+    // The moderation filter wants a moderator to look at this one first
+    let scores = moderation::scores(&req);
+    if moderation::is_held(&req) {
+        let held = moderation::hold_post(&store, &user_id, content, moderation::hold_reason(&req).as_deref(), scores)?;
+        store.set_json(&last_post_key(&user_id), &chrono::Utc::now().timestamp())?;
+        return Ok(Response::builder()
+            .status(202)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_vec(&moderation::held_json(&held))?)
+            .build());
     }
 
+    let post = publish_post(&store, &user_id, content, scores)?;
+    store.set_json(&last_post_key(&user_id), &chrono::Utc::now().timestamp())?;
+
+    Ok(Response::builder()
+        .status(201)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&post_json(&post))?)
+        .build())
+}
+
+/// Seconds until the user may post again, if they posted too recently.
+/// Skipped on test instances, like the rate limiter.
+fn post_cooldown_remaining(store: &dyn BordStore, user_id: &str) -> anyhow::Result<Option<u64>> {
+    let cooldown = post_cooldown_seconds();
+    if test_mode() || cooldown <= 0 {
+        return Ok(None);
+    }
+
+    let last_post: Option<i64> = store.get_json(&last_post_key(user_id))?;
+    let elapsed = last_post.map(|t| chrono::Utc::now().timestamp() - t);
+    Ok(elapsed
+        .filter(|&elapsed| elapsed < cooldown)
+        .map(|elapsed| (cooldown - elapsed) as u64))
+}
+
+/// Why post content can't be published, if it can't
+fn content_problem(content: &str) -> Option<FieldError> {
+    if content.is_empty() {
+        Some(FieldError::new("content", "content_required", "Post content is required"))
+    } else if content.len() > MAX_POST_LENGTH {
+        Some(FieldError::new("content", "content_too_long", format!("Posts are limited to {} characters", MAX_POST_LENGTH)))
+    } else {
+        None
+    }
+}
+
+/// Sanitize and store an already validated post, then add it to the global feed
+pub fn publish_post(store: &dyn BordStore, user_id: &str, content: &str, moderation: Option<ModerationScores>) -> anyhow::Result<Post> {
+    let id = Uuid::new_v4().to_string();
     let post = Post {
         id: id.clone(),
         user_id: user_id.to_string(),
         content: filter_post_content(content),
         created_at: now_iso(),
         updated_at: None,
+        hidden: false,
+        render_version: POST_RENDER_VERSION,
+        moderation,
     };
 
-    // Save post object
+    // Save post object, with its source for later re-rendering
     store.set_json(&post_key(&id), &post)?;
+    store.set_json(&post_source_key(&id), &content)?;
 
-    // Append to global feed (store IDs in a JSON list)
-    let mut feed: Vec<String> = store.get_json(FEED_KEY)?.unwrap_or_default();
-    feed.insert(0, id.clone()); // prepend newest
-    store.set_json(FEED_KEY, &feed)?;
-
-    Ok(Response::builder()
-        .status(201)
-        .header("Content-Type", "application/json")
-        .body(serde_json::to_vec(&post)?)
-        .build())
-}
-
-pub fn edit_post(req: Request) -> anyhow::Result<Response> {
-    let user_id = match validate_token(&req) {
-        Some(uid) => uid,
-        None => return Ok(ApiError::Unauthorized.into()),
-    };
+    // Journal it rather than rewriting the shared feed list
+    feed_index::append(store, &post)?;
+    adjust_post_count(store, user_id, 1)?;
+    invalidate_profile_page_for(store, user_id)?;
 
-    let path = req.path();
-    let post_id = path.split('/').last().unwrap_or("");
+    webhooks::emit(store, "post.created", post_json(&post))?;
 
-    if post_id.is_empty() || !validate_uuid(post_id) {
-        return Ok(ApiError::BadRequest("Post ID required".to_string()).into());
-    }
+    Ok(post)
+}
 
-    let store = store();
+pub fn edit_post(req: Request, post_id: &str, user_id: String) -> anyhow::Result<Response> {
+    let store = store()?;
     let post_key = post_key(post_id);
 
     // Check if post exists and belongs to user
@@ -75,12 +141,14 @@ pub fn edit_post(req: Request) -> anyhow::Result<Response> {
             return Ok(ApiError::Forbidden.into());
         }
 
-        let value: serde_json::Value = serde_json::from_slice(req.body())?;
-        let content = value["content"].as_str().unwrap_or_default();
+        let body: PostContent = match parse_body(&req) {
+            Ok(body) => body,
+            Err(e) => return Ok(e.into()),
+        };
+        let content = body.content.as_str();
 
-        // Validate content
-        if content.is_empty() || content.len() > MAX_POST_LENGTH {
-            return Ok(ApiError::BadRequest("Invalid content".to_string()).into());
+        if url_regex().is_match(content) && !passes_gate(&store, &user_id, GatedAction::LinkPost)? {
+            return Ok(ApiError::Forbidden.into());
         }
 
         // Skip update if content didn't change
@@ -89,26 +157,71 @@ pub fn edit_post(req: Request) -> anyhow::Result<Response> {
             return Ok(Response::builder()
                 .status(200)
                 .header("Content-Type", "application/json")
-                .body(serde_json::to_vec(&post)?)
+                .body(serde_json::to_vec(&post_json(&post))?)
                 .build());
         }
 
         // Update post
         post.content = filtered_content;
+        post.render_version = POST_RENDER_VERSION;
         post.updated_at = Some(now_iso());
 
         store.set_json(&post_key, &post)?;
+        store.set_json(&post_source_key(post_id), &content)?;
+        invalidate_profile_page_for(&store, &user_id)?;
 
         Ok(Response::builder()
             .status(200)
             .header("Content-Type", "application/json")
-            .body(serde_json::to_vec(&post)?)
+            .body(serde_json::to_vec(&post_json(&post))?)
             .build())
     } else {
-        Ok(ApiError::NotFound("Post not found".to_string()).into())
+        Ok(ApiError::NotFound("post_not_found", "Post not found".to_string()).into())
     }
 }
 
+/// A post that may be shown publicly: it exists and isn't hidden by a moderator
+pub fn load_visible_post(store: &dyn BordStore, id: &str) -> anyhow::Result<Option<Post>> {
+    Ok(load_post(store, id)?.filter(|post| !post.hidden))
+}
+
+/// A post as the API returns it, with its author's avatar. Filter scores are
+/// left out; moderator endpoints use `moderator_post_json`.
+pub fn post_json(post: &Post) -> serde_json::Value {
+    let mut json = serde_json::json!(post);
+    json["author_avatar_url"] = avatar_url(&post.user_id).into();
+    if let Some(fields) = json.as_object_mut() {
+        fields.remove("moderation");
+    }
+    json
+}
+
+/// `post_json` plus the moderation filter's scores
+pub fn moderator_post_json(post: &Post) -> serde_json::Value {
+    let mut json = post_json(post);
+    json["moderation"] = serde_json::json!(post.moderation);
+    json
+}
+
+/// Fetch a post, re-rendering its HTML if an older renderer produced it
+pub fn load_post(store: &dyn BordStore, id: &str) -> anyhow::Result<Option<Post>> {
+    let mut post = match store.get_json::<Post>(&post_key(id))? {
+        Some(post) => post,
+        None => return Ok(None),
+    };
+
+    if post.render_version < POST_RENDER_VERSION {
+        // Posts from before sources were kept can't be re-rendered; keep their HTML
+        if let Some(source) = store.get_json::<String>(&post_source_key(id))? {
+            post.content = filter_post_content(&source);
+        }
+        post.render_version = POST_RENDER_VERSION;
+        store.set_json(&post_key(id), &post)?;
+    }
+
+    Ok(Some(post))
+}
+
 fn url_regex() -> &'static Regex {
     static REGEX: OnceLock<Regex> = OnceLock::new();
     REGEX.get_or_init(|| {
@@ -131,114 +244,133 @@ fn filter_post_content(content: &str) -> String {
     }).to_string()
 }
 
-/// Fetch all posts from the global feed
-fn get_all_posts_from_feed() -> anyhow::Result<Vec<Post>> {
-    let store = store();
-    let feed: Vec<String> = store.get_json(FEED_KEY)?.unwrap_or_default();
+/// Visible feed posts matching `keep`, newest first. Stops reading the feed
+/// once pages 1 through `page` are filled, so older day buckets stay unread.
+fn feed_posts_through_page(page: usize, keep: impl Fn(&Post) -> bool) -> anyhow::Result<Vec<Post>> {
+    let store = store()?;
+    let wanted = page.saturating_mul(POSTS_PER_PAGE);
     let mut posts = Vec::new();
-    
-    for id in feed.iter() {
-        if let Some(p) = store.get_json::<Post>(&post_key(id))? {
-            posts.push(p);
-        }
-    }
-    
-    Ok(posts)
-}
 
-/// Filter posts by a single user_id
-fn filter_posts_by_user(user_id: &str) -> anyhow::Result<Vec<Post>> {
-    let store = store();
-    let feed: Vec<String> = store.get_json(FEED_KEY)?.unwrap_or_default();
-    let mut posts = Vec::new();
-    
-    for id in feed.iter() {
-        if let Some(p) = store.get_json::<Post>(&post_key(id))? {
-            if p.user_id == user_id {
+    for_each_feed_id(&store, |id| {
+        if let Some(p) = load_post(&store, id)? {
+            if !p.hidden && keep(&p) {
                 posts.push(p);
             }
         }
-    }
-    
+        Ok(posts.len() < wanted)
+    })?;
+
     Ok(posts)
 }
 
-/// Filter posts from multiple user_ids (e.g., followings)
-fn filter_posts_by_users(user_ids: &[String]) -> anyhow::Result<Vec<Post>> {
-    let store = store();
-    let feed: Vec<String> = store.get_json(FEED_KEY)?.unwrap_or_default();
+/// Fetch posts from the global feed
+fn get_all_posts_from_feed(page: usize) -> anyhow::Result<Vec<Post>> {
+    feed_posts_through_page(page, |_| true)
+}
+
+/// A user's visible posts through `page`, read from their own post index
+fn filter_posts_by_user(user_id: &str, page: usize) -> anyhow::Result<Vec<Post>> {
+    let store = store()?;
+    let wanted = page.saturating_mul(POSTS_PER_PAGE);
     let mut posts = Vec::new();
-    
-    for id in feed.iter() {
-        if let Some(p) = store.get_json::<Post>(&post_key(id))? {
-            if user_ids.contains(&p.user_id) {
+
+    for id in user_post_ids(&store, user_id)? {
+        if posts.len() >= wanted {
+            break;
+        }
+        if let Some(p) = load_post(&store, &id)? {
+            if !p.hidden {
                 posts.push(p);
             }
         }
     }
-    
+
     Ok(posts)
 }
 
-/// Look up a user by username
-fn get_user_by_username(username: &str) -> anyhow::Result<Option<String>> {
-    let store = store();
-    let users: Vec<String> = store.get_json(USERS_LIST_KEY)?.unwrap_or_default();
-    
-    for id in users {
-        if let Some(u) = store.get_json::<User>(&user_key(&id))? {
-            if u.username == username {
-                return Ok(Some(u.id));
-            }
-        }
-    }
-    
-    Ok(None)
+/// Filter posts from multiple user_ids (e.g., followings)
+fn filter_posts_by_users(user_ids: &[String], page: usize) -> anyhow::Result<Vec<Post>> {
+    feed_posts_through_page(page, |p| user_ids.contains(&p.user_id))
 }
 
 /// Apply pagination to a list of posts
 fn paginate_posts(posts: Vec<Post>, page: usize) -> Vec<Post> {
-    let start_idx = (page - 1) * POSTS_PER_PAGE;
+    let start_idx = page.saturating_sub(1).saturating_mul(POSTS_PER_PAGE);
     posts.into_iter()
         .skip(start_idx)
         .take(POSTS_PER_PAGE)
         .collect()
 }
 
+/// One page of the global feed, newest first, and whether older posts exist
+pub fn feed_page(page: usize) -> anyhow::Result<(Vec<Post>, bool)> {
+    let posts = get_all_posts_from_feed(page.saturating_add(1))?;
+    let has_more = posts.len() > page.saturating_mul(POSTS_PER_PAGE);
+    Ok((paginate_posts(posts, page), has_more))
+}
 
-pub fn delete_post(req: Request) -> anyhow::Result<Response> {
-     let user_id = match validate_token(&req) {
-         Some(uid) => uid,
-         None => return Ok(ApiError::Unauthorized.into()),
-     };
- 
-     let path = req.path();
-     let post_id = path.split('/').last().unwrap_or("");
-     
-     if post_id.is_empty() || !validate_uuid(post_id) {
-         return Ok(ApiError::BadRequest("Post ID required".to_string()).into());
-     }
- 
-     let store = store();
+/// One page of a user's visible posts, newest first, and whether older ones exist
+pub fn user_posts_page(user_id: &str, page: usize) -> anyhow::Result<(Vec<Post>, bool)> {
+    let posts = filter_posts_by_user(user_id, page.saturating_add(1))?;
+    let has_more = posts.len() > page.saturating_mul(POSTS_PER_PAGE);
+    Ok((paginate_posts(posts, page), has_more))
+}
+
+/// Every post authored by a user, hidden ones included (for data exports)
+pub fn posts_by_user(store: &dyn BordStore, user_id: &str) -> anyhow::Result<Vec<Post>> {
+    let mut posts = Vec::new();
+    for id in user_post_ids(store, user_id)? {
+        if let Some(p) = load_post(store, &id)? {
+            posts.push(p);
+        }
+    }
+
+    Ok(posts)
+}
+
+/// Delete every post authored by a user and drop them from the feed
+pub fn delete_user_posts(store: &dyn BordStore, user_id: &str) -> anyhow::Result<()> {
+    let mut removed = Vec::new();
+    for id in user_post_ids(store, user_id)? {
+        let key = post_key(&id);
+        if let Some(p) = store.get_json::<Post>(&key)? {
+            store.delete(&key)?;
+            store.delete(&post_source_key(&id))?;
+            delete_impressions(store, &id)?;
+            removed.push(p);
+        }
+    }
+    feed_index::remove(store, &removed)?;
+    delete_set(store, &user_posts_set(user_id))?;
+    store.delete(&post_count_key(user_id))?;
+    
+    Ok(())
+}
+
+pub fn delete_post(post_id: &str, user_id: String) -> anyhow::Result<Response> {
+     let store = store()?;
      let post_key = post_key(post_id);
      
      // Check if post exists and belongs to user
      if let Some(p) = store.get_json::<Post>(&post_key)? {
-         if p.user_id != user_id {
+         // Authors delete their own posts, moderators can remove anyone's
+         if p.user_id != user_id && !has_permission(&store, &user_id, Permission::ModeratePosts)? {
              return Ok(ApiError::Forbidden.into());
          }
      
          // Delete the post
              store.delete(&post_key)?;
+             store.delete(&post_source_key(post_id))?;
+             delete_impressions(&store, post_id)?;
          
              // Remove from feed
-             let mut feed: Vec<String> = store.get_json(FEED_KEY)?.unwrap_or_default();
-             feed.retain(|id| id != post_id);
-             store.set_json(FEED_KEY, &feed)?;
+             adjust_post_count(&store, &p.user_id, -1)?;
+             invalidate_profile_page_for(&store, &p.user_id)?;
+             feed_index::remove(&store, &[p])?;
          
              Ok(Response::builder().status(204).build())
      } else {
-         Ok(ApiError::NotFound("Post not found".to_string()).into())
+         Ok(ApiError::NotFound("post_not_found", "Post not found".to_string()).into())
      }
 }
 
@@ -264,36 +396,28 @@ pub fn list_posts(req: Request) -> anyhow::Result<Response> {
 
     let posts = if let Some(username) = filter_username {
         // Public query: get posts for specific username
-        if let Some(uid) = get_user_by_username(&username)? {
-            let user_posts = filter_posts_by_user(&uid)?;
+        if let Some(uid) = find_user_id_by_username(&store()?, &username)? {
+            let user_posts = filter_posts_by_user(&uid, page)?;
             paginate_posts(user_posts, page)
         } else {
             Vec::new()
         }
     } else if show_all {
         // Get paginated posts from the global feed
-        let all_posts = get_all_posts_from_feed()?;
+        let all_posts = get_all_posts_from_feed(page)?;
         paginate_posts(all_posts, page)
     } else {
         // Authenticated query: get paginated posts for current user
-        let user_posts = filter_posts_by_user(&user_id)?;
+        let user_posts = filter_posts_by_user(&user_id, page)?;
         paginate_posts(user_posts, page)
     };
 
-    Ok(Response::builder()
-        .status(200)
-        .header("Content-Type", "application/json")
-        .body(serde_json::to_vec(&posts)?)
-        .build())
+    let posts: Vec<_> = posts.iter().map(post_json).collect();
+    Ok(json_with_etag(&req, serde_json::to_vec(&posts)?))
 }
 
-pub fn get_feed(req: Request) -> anyhow::Result<Response> {
-    let user_id = match validate_token(&req) {
-        Some(uid) => uid,
-        None => return Ok(ApiError::Unauthorized.into()),
-    };
-
-    let store = store();
+pub fn get_feed(req: Request, user_id: String) -> anyhow::Result<Response> {
+    let store = store()?;
     let uri = req.uri();
     
     // Parse page parameter from query string
@@ -305,7 +429,7 @@ pub fn get_feed(req: Request) -> anyhow::Result<Response> {
         .unwrap_or_default();
     
     // Get posts from users they follow
-    let mut posts = filter_posts_by_users(&followings)?;
+    let mut posts = filter_posts_by_users(&followings, page)?;
     
     // Sort by created_at in descending order (newest first)
     posts.sort_by(|a, b| b.created_at.cmp(&a.created_at));
@@ -313,10 +437,74 @@ pub fn get_feed(req: Request) -> anyhow::Result<Response> {
     // Apply pagination
     let paginated_posts = paginate_posts(posts, page);
     
-    Ok(Response::builder()
-        .status(200)
-        .header("Content-Type", "application/json")
-        .body(serde_json::to_vec(&paginated_posts)?)
-        .build())
+    let posts: Vec<_> = paginated_posts.iter().map(post_json).collect();
+    Ok(json_with_etag(&req, serde_json::to_vec(&posts)?))
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::MemoryStore;
+    use proptest::prelude::*;
+
+    /// Fragments of markup that show up in XSS attempts, mixed with plain text
+    fn markup() -> impl Strategy<Value = String> {
+        let fragment = prop_oneof![
+            "[a-zA-Z0-9 .,!?]{0,20}",
+            Just("<script>alert(1)</script>".to_string()),
+            Just("<SCRIPT SRC=//evil.example></SCRIPT>".to_string()),
+            Just("<img src=x onerror=alert(1)>".to_string()),
+            Just("<a href=\"javascript:alert(1)\">x</a>".to_string()),
+            Just("<scr<script>ipt>".to_string()),
+            Just("https://example.com/?q=\"><script>".to_string()),
+            "[<>\"'=/ a-z]{0,20}",
+        ];
+        prop::collection::vec(fragment, 0..8).prop_map(|parts| parts.concat())
+    }
+
+    proptest! {
+        #[test]
+        fn filter_post_content_strips_scripts(content in markup()) {
+            let filtered = filter_post_content(&content).to_lowercase();
+            prop_assert!(!filtered.contains("<script"));
+
+            // Text is escaped, so any remaining `<` opens a real tag; check its attribute names
+            let unsafe_attr = Regex::new(r#"<[a-z]+(\s+[a-z-]+(="[^"]*")?)*\s+(on[a-z]+|href="javascript:)"#).unwrap();
+            prop_assert!(!unsafe_attr.is_match(&filtered), "unsafe attribute in {}", filtered);
+        }
+
+        #[test]
+        fn filter_post_content_never_panics(content in "\\PC*") {
+            filter_post_content(&content);
+        }
+
+        #[test]
+        fn paginate_posts_handles_any_page(page in prop_oneof![1usize..5, Just(usize::MAX)]) {
+            prop_assert!(paginate_posts(Vec::new(), page).is_empty());
+        }
+    }
+
+    #[test]
+    fn load_post_rerenders_stale_html() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+        let stale = Post {
+            id: "p1".to_string(),
+            user_id: "u1".to_string(),
+            content: "see https://example.com".to_string(),
+            created_at: now_iso(),
+            updated_at: None,
+            hidden: false,
+            render_version: 0,
+            moderation: None,
+        };
+        store.set_json(&post_key("p1"), &stale).unwrap();
+        store.set_json(&post_source_key("p1"), &"see https://example.com").unwrap();
+
+        let post = load_post(store, "p1").unwrap().unwrap();
+        assert!(post.content.contains(r#"<a href="https://example.com""#));
+        assert_eq!(post.render_version, POST_RENDER_VERSION);
+        assert_eq!(store.get_json::<Post>(&post_key("p1")).unwrap().unwrap().content, post.content);
+    }
+}
@@ -1,68 +1,439 @@
 use spin_sdk::http::{Request, Response};
+use crate::core::db::BordStore;
 use rust_embed::RustEmbed;
-use crate::models::models::User;
+use minijinja::{context, escape_formatter, AutoEscape, Environment, Error, ErrorKind, Output, State, Value};
+use minijinja::value::Kwargs;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use crate::models::models::{User, CachedPage, InstanceSettings};
 use crate::core::helpers::store;
-use crate::core::errors::ApiError;
+use crate::core::errors::{is_problem, ApiError};
+use crate::core::middleware::Next;
+use crate::core::router::strip_mount;
+use crate::core::static_server::{asset_manifest, assets_version};
+use crate::core::timezone::{format_local, age, Age};
+use crate::core::i18n::{self, request_locale};
+use crate::follow::{follower_count, following_count};
+use crate::branding::current_branding;
+use crate::core::query_params::{parse_query_params, get_int};
+use crate::users::{find_user_by_username, build_user_json, author_name};
+use crate::posts::{user_posts_page, feed_page, load_visible_post};
+use crate::discovery::origin;
 use crate::config::*;
 
 #[derive(RustEmbed)]
-#[folder = "static"]
-struct Assets;
+#[folder = "templates"]
+struct Templates;
 
-pub fn render_user_profile(_req: &Request, path: &str) -> anyhow::Result<Response> {
-    let username = path.trim_start_matches('/');
-    let store = store();
-    
-    // Find user by username
-    let users: Vec<String> = store.get_json(USERS_LIST_KEY)?.unwrap_or_default();
-    let mut target_user: Option<User> = None;
-    
-    for id in users {
-        if let Some(u) = store.get_json::<User>(&user_key(&id))? {
-            if u.username == username {
-                target_user = Some(u);
-                break;
+/// Drop a user's cached profile pages, in every locale; call whenever rendered fields change
+pub fn invalidate_profile_page(store: &dyn BordStore, username: &str) -> anyhow::Result<()> {
+    for locale in i18n::locales() {
+        store.delete(&profile_page_key(username, locale))?;
+    }
+    Ok(())
+}
+
+/// Same as `invalidate_profile_page`, for callers that only have the user ID
+pub fn invalidate_profile_page_for(store: &dyn BordStore, user_id: &str) -> anyhow::Result<()> {
+    if let Some(user) = store.get_json::<User>(&user_key(user_id))? {
+        invalidate_profile_page(store, &user.username)?;
+    }
+    Ok(())
+}
+
+/// Combined hash of every embedded template
+fn templates_version() -> &'static str {
+    static VERSION: OnceLock<String> = OnceLock::new();
+    VERSION.get_or_init(|| {
+        let mut hasher = Sha256::new();
+        for name in Templates::iter() {
+            if let Some(file) = Templates::get(&name) {
+                hasher.update(name.as_bytes());
+                hasher.update(file.metadata.sha256_hash());
+            }
+        }
+        hasher.finalize()[..4].iter().map(|b| format!("{:02x}", b)).collect()
+    })
+}
+
+/// Cached pages are valid for one build of the assets and templates and one revision of the instance settings
+pub fn page_version(store: &dyn BordStore) -> anyhow::Result<String> {
+    let settings: InstanceSettings = store.get_json(INSTANCE_SETTINGS_KEY)?.unwrap_or_default();
+    Ok(format!("{}.{}.{}", assets_version(), templates_version(), settings.revision))
+}
+
+/// `{{ asset("style.css") }}`: the fingerprinted URL of an embedded asset; other URLs pass through
+fn asset(url: String) -> String {
+    asset_manifest()
+        .get(url.trim_start_matches('/'))
+        .cloned()
+        .unwrap_or(url)
+}
+
+/// `{{ timestamp | local_time(timezone, format) }}`: an RFC 3339 timestamp in a user's time zone
+fn local_time(timestamp: String, timezone: Option<String>, format: Option<String>) -> String {
+    format_local(&timestamp, timezone.as_deref(), format.as_deref().unwrap_or("%b %-d, %Y %H:%M"))
+}
+
+/// The page's locale, as passed to `render_page`
+fn locale(state: &State) -> String {
+    state.lookup("locale")
+        .and_then(|locale| locale.as_str().map(str::to_string))
+        .unwrap_or_else(|| DEFAULT_LOCALE.to_string())
+}
+
+/// `{{ t("profile.joined", date=...) }}`: a message from the page locale's catalog;
+/// unknown keys render as themselves so they're easy to spot
+fn t(state: &State, key: String, args: Kwargs) -> Result<String, Error> {
+    let mut values = Vec::new();
+    for name in args.args() {
+        values.push((name, args.get::<Value>(name)?.to_string()));
+    }
+    Ok(i18n::text(&locale(state), &key, &values))
+}
+
+/// `{{ timestamp | relative_time }}`: e.g. "5m ago", for pages that aren't cached
+fn relative_time(state: &State, timestamp: String) -> String {
+    let locale = locale(state);
+    let ago = |key, count: i64| i18n::text(&locale, key, &[("count", count.to_string())]);
+    match age(&timestamp, chrono::Utc::now()) {
+        Some(Age::JustNow) => i18n::text(&locale, "time.just_now", &[]),
+        Some(Age::Minutes(n)) => ago("time.minutes_ago", n),
+        Some(Age::Hours(n)) => ago("time.hours_ago", n),
+        Some(Age::Days(n)) => ago("time.days_ago", n),
+        Some(Age::Older(t)) => t.format("%b %-d, %Y").to_string(),
+        None => timestamp,
+    }
+}
+
+/// Plain text of a post's HTML, cut to `max_chars`, for link previews
+pub fn excerpt(html: &str, max_chars: usize) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => { in_tag = false; text.push(' ') }
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    let text = text
+        .replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"")
+        .replace("&#39;", "'").replace("&nbsp;", " ").replace("&amp;", "&");
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text,
+    }
+}
+
+/// HTML escaping like minijinja's own, except that `/` is left alone: it is
+/// harmless in text and quoted attributes, and share URLs (`og:url`, canonical
+/// links) must reach crawlers as written
+fn format_value(out: &mut Output, state: &State, value: &Value) -> Result<(), Error> {
+    use std::fmt::Write;
+    match value.as_str() {
+        Some(text) if state.auto_escape() == AutoEscape::Html && !value.is_safe() => {
+            for c in text.chars() {
+                match c {
+                    '&' => out.write_str("&amp;")?,
+                    '<' => out.write_str("&lt;")?,
+                    '>' => out.write_str("&gt;")?,
+                    '"' => out.write_str("&quot;")?,
+                    '\'' => out.write_str("&#x27;")?,
+                    c => out.write_char(c)?,
+                }
             }
+            Ok(())
         }
+        _ => escape_formatter(out, state, value),
     }
-    
-    if target_user.is_none() {
-        return Ok(ApiError::NotFound("User not found".to_string()).into());
-    }
-    
-    let user = target_user.unwrap();
-    
-    // Load profile.html template
-    let template = Assets::get("profile.html")
-        .ok_or_else(|| anyhow::anyhow!("Profile template not found"))?
-        .data
-        .to_vec();
-    
-    let mut html = String::from_utf8(template)?;
-    
-    // Replace placeholders
-    let escaped_username = html_escape::encode_text(&user.username).to_string();
-    let escaped_user_id = html_escape::encode_text(&user.id).to_string();
-    
-    html = html.replace("PROFILE_USERNAME", &escaped_username);
-    html = html.replace("PROFILE_USER_ID", &escaped_user_id);
-    
-    // Replace bio section
-    let bio_section = user.bio.as_ref()
-        .map(|bio| format!(
-            r#"<div class="profile-field">
-                <div class="profile-field-label">Bio</div>
-                <div class="profile-field-value">{}</div>
-            </div>"#,
-            html_escape::encode_text(bio)
-        ))
-        .unwrap_or_default();
-    
-    html = html.replace("PROFILE_BIO", &bio_section);
-    
-    Ok(Response::builder()
+}
+
+/// Templates load from the embedded `templates/` folder on first use; `.html`
+/// templates are auto-escaped, so only values marked `| safe` go in as markup
+fn environment() -> &'static Environment<'static> {
+    static ENV: OnceLock<Environment<'static>> = OnceLock::new();
+    ENV.get_or_init(|| {
+        let mut env = Environment::new();
+        env.set_loader(|name| match Templates::get(name) {
+            Some(file) => String::from_utf8(file.data.into_owned())
+                .map(Some)
+                .map_err(|e| Error::new(ErrorKind::InvalidOperation, "template is not UTF-8").with_source(e)),
+            None => Ok(None),
+        });
+        env.set_formatter(format_value);
+        env.add_function("asset", asset);
+        env.add_function("t", t);
+        env.add_filter("local_time", local_time);
+        env.add_filter("relative_time", relative_time);
+        env.add_global("default_site_name", DEFAULT_SITE_NAME);
+        env.add_global("default_logo_url", DEFAULT_LOGO_URL);
+        env
+    })
+}
+
+/// Render a page template in `locale`, with the instance branding in scope as `branding`
+pub fn render_page(store: &dyn BordStore, name: &str, locale: &str, ctx: Value) -> anyhow::Result<String> {
+    let ctx = context! { branding => current_branding(store)?, locale, ..ctx };
+    Ok(environment().get_template(name)?.render(ctx)?)
+}
+
+pub fn html_response(html: String, locale: &str) -> Response {
+    Response::builder()
         .status(200)
         .header("Content-Type", "text/html; charset=utf-8")
+        .header("Content-Language", locale)
+        .header("Vary", "Accept-Language")
         .body(html.into_bytes())
-        .build())
+        .build()
+}
+
+fn wants_json(req: &Request) -> bool {
+    req.header("Accept")
+        .and_then(|h| h.as_str())
+        .map(|accept| accept.contains("application/json"))
+        .unwrap_or(false)
+}
+
+/// Middleware: browsers get a styled page for errors outside the API, rendered
+/// from the (already localized) problem document. API paths and clients that
+/// don't ask for HTML keep the JSON.
+pub fn html_errors(req: Request, next: Next) -> anyhow::Result<Response> {
+    let wants_html = req.header("Accept")
+        .and_then(|h| h.as_str())
+        .is_some_and(|accept| accept.contains("text/html"))
+        && strip_mount(req.path(), API_PREFIX).is_none();
+    let locale = request_locale(&req);
+    let resp = next(req)?;
+    if !wants_html || !is_problem(&resp) {
+        return Ok(resp);
+    }
+
+    let problem: serde_json::Value = match serde_json::from_slice(resp.body()) {
+        Ok(problem) => problem,
+        Err(_) => return Ok(resp),
+    };
+    let ctx = context! {
+        status => problem["status"].as_u64(),
+        title => problem["title"].as_str(),
+        detail => problem["detail"].as_str(),
+    };
+    // Storage trouble can break rendering too; the problem document still says what went wrong
+    let html = match render_page(&store()?, "error.html", locale, ctx) {
+        Ok(html) => html,
+        Err(_) => return Ok(resp),
+    };
+
+    let mut builder = Response::builder();
+    builder
+        .status(*resp.status())
+        .header("Content-Type", "text/html; charset=utf-8")
+        .header("Content-Language", locale)
+        .header("Vary", "Accept, Accept-Language");
+    if let Some(retry_after) = resp.header("Retry-After").and_then(|h| h.as_str()) {
+        builder.header("Retry-After", retry_after);
+    }
+    Ok(builder.body(html.into_bytes()).build())
+}
+
+/// `/{username}`: the profile page, or the user's JSON when the client asks for it.
+/// `?page=N` renders that page of the user's posts.
+pub fn render_user_profile(req: &Request, path: &str) -> anyhow::Result<Response> {
+    let username = path.trim_start_matches('/');
+    let store = store()?;
+
+    if wants_json(req) {
+        return Ok(match find_user_by_username(&store, username)? {
+            Some(user) => Response::builder()
+                .status(200)
+                .header("Content-Type", "application/json")
+                .header("Vary", "Accept")
+                .body(serde_json::to_vec(&build_user_json(&store, &user)?)?)
+                .build(),
+            None => ApiError::NotFound("user_not_found", "User not found".to_string()).into(),
+        });
+    }
+
+    let locale = request_locale(req);
+    let profile_response = |html: String| {
+        Response::builder()
+            .status(200)
+            .header("Content-Type", "text/html; charset=utf-8")
+            .header("Content-Language", locale)
+            .header("Vary", "Accept, Accept-Language")
+            .body(html.into_bytes())
+            .build()
+    };
+
+    // Only the first page is cached: it is what profile links open on.
+    // Served unless a deploy, a settings change or a new post made it stale.
+    // Share tags hold absolute URLs, so a page is only reused for the same origin.
+    let page = get_int(&parse_query_params(req.uri()), "page", 1);
+    let origin = origin(req);
+    let cache_key = profile_page_key(username, locale);
+    let version = format!("{} {}", page_version(&store)?, origin);
+    if page == 1 {
+        if let Some(cached) = store.get_json::<CachedPage>(&cache_key)? {
+            if cached.version == version {
+                return Ok(profile_response(cached.html));
+            }
+        }
+    }
+
+    let user = match find_user_by_username(&store, username)? {
+        Some(u) => u,
+        None => return Ok(ApiError::NotFound("user_not_found", "User not found".to_string()).into()),
+    };
+
+    let (posts, has_more) = user_posts_page(&user.id, page)?;
+    let names = [("username", user.username.clone()), ("site", current_branding(&store)?.site_name)];
+    let html = render_page(&store, "profile.html", locale, context! {
+        origin,
+        share => context! {
+            type => "profile",
+            title => i18n::text(locale, "share.profile_title", &names),
+            description => match user.bio.as_deref() {
+                Some(bio) if !bio.is_empty() => bio.to_string(),
+                _ => i18n::text(locale, "share.profile_description", &names),
+            },
+            path => format!("/{}", user.username),
+        },
+        follower_count => follower_count(&store, &user.id)?,
+        following_count => following_count(&store, &user.id)?,
+        // Not the whole `User`, which carries the password hash
+        user => context! {
+            id => user.id,
+            username => user.username,
+            bio => user.bio,
+            created_at => user.created_at,
+            timezone => user.timezone,
+        },
+        posts,
+        page,
+        has_more,
+    })?;
+
+    if page == 1 {
+        store.set_json(&cache_key, &CachedPage { version, html: html.clone() })?;
+    }
+    Ok(profile_response(html))
+}
+
+/// `GET /timeline?page=N`: the global feed as a plain page, readable without JS
+/// and by crawlers. Not cached, since relative times go stale.
+pub fn render_timeline(req: &Request) -> anyhow::Result<Response> {
+    let store = store()?;
+    let locale = request_locale(req);
+    let page = get_int(&parse_query_params(req.uri()), "page", 1);
+    let (posts, has_more) = feed_page(page)?;
+
+    let mut usernames: HashMap<String, String> = HashMap::new();
+    let mut items = Vec::new();
+    for post in posts {
+        if !usernames.contains_key(&post.user_id) {
+            usernames.insert(post.user_id.clone(), author_name(&store, &post.user_id)?);
+        }
+        items.push(context! { author => usernames[&post.user_id], post });
+    }
+
+    let html = render_page(&store, "timeline.html", locale, context! { posts => items, page, has_more })?;
+    Ok(html_response(html, locale))
+}
+
+/// `GET /posts/{id}`: one post on its own page, the link that gets shared
+pub fn render_post_page(req: &Request, post_id: &str) -> anyhow::Result<Response> {
+    let store = store()?;
+    let post = match load_visible_post(&store, post_id)? {
+        Some(post) => post,
+        None => return Ok(ApiError::NotFound("post_not_found", "Post not found".to_string()).into()),
+    };
+    let author = author_name(&store, &post.user_id)?;
+
+    let locale = request_locale(req);
+    let names = [("username", author.clone()), ("site", current_branding(&store)?.site_name)];
+    let html = render_page(&store, "post.html", locale, context! {
+        origin => origin(req),
+        share => context! {
+            type => "article",
+            title => i18n::text(locale, "share.post_title", &names),
+            description => excerpt(&post.content, 200),
+            path => format!("/posts/{}", post.id),
+        },
+        author,
+        post,
+    })?;
+    Ok(html_response(html, locale))
+}
+
+pub fn render_index(req: &Request) -> anyhow::Result<Response> {
+    let locale = request_locale(req);
+    Ok(html_response(render_page(&store()?, "index.html", locale, context! {})?, locale))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::db::MemoryStore;
+
+    #[test]
+    fn excerpts_are_plain_text() {
+        assert_eq!(excerpt("<p>Tom &amp; Jerry</p>\n<p>again</p>", 200), "Tom & Jerry again");
+        assert_eq!(excerpt("<b>héllo</b> wörld", 5), "héllo…");
+        assert_eq!(excerpt("", 10), "");
+    }
+
+    #[test]
+    fn pages_render_in_the_requested_locale() {
+        let backend = MemoryStore::default();
+        let ctx = || context! { posts => Vec::<Value>::new(), page => 2, has_more => true };
+        let en = render_page(&backend, "timeline.html", "en", ctx()).unwrap();
+        assert!(en.contains(r#"<html lang="en">"#) && en.contains("Latest Bords") && en.contains("Page 2"));
+
+        let ro = render_page(&backend, "timeline.html", "ro", ctx()).unwrap();
+        assert!(ro.contains(r#"<html lang="ro">"#) && ro.contains("Cele mai noi Bord-uri") && ro.contains("Pagina 2"));
+    }
+
+    #[test]
+    fn error_pages_show_the_problem() {
+        let backend = MemoryStore::default();
+        let html = render_page(&backend, "error.html", "en", context! {
+            status => 404,
+            title => "Not Found",
+            detail => "No <user> here",
+        }).unwrap();
+        assert!(html.contains("404") && html.contains("Not Found"));
+        assert!(html.contains("No &lt;user&gt; here"));
+        assert!(html.contains("Back to the front page"));
+    }
+
+    #[test]
+    fn pages_escape_user_fields_but_not_sanitized_posts() {
+        let backend = MemoryStore::default();
+        let html = render_page(&backend, "profile.html", "en", context! {
+            origin => "https://bord.example",
+            share => context! { type => "profile", title => "<b>eve</b>", description => "<i>hi</i>", path => "/eve" },
+            user => context! { id => "u1", username => "<b>eve</b>", bio => "<i>hi</i>" },
+            posts => vec![context! {
+                content => r#"<a href="https://example.com" rel="noopener noreferrer">link</a>"#,
+                created_at => "2024-01-01T12:00:00+00:00",
+            }],
+            page => 2,
+            has_more => false,
+            follower_count => 0,
+            following_count => 0,
+        }).unwrap();
+
+        assert!(html.contains("&lt;b&gt;eve"));
+        assert!(html.contains("&lt;i&gt;hi"));
+        assert!(!html.contains("<b>eve</b>") && !html.contains("<i>hi</i>"));
+        assert!(html.contains(r#"<a href="https://example.com" rel="noopener noreferrer">link</a>"#));
+        assert!(html.contains(r#"<a href="?page=1">"#));
+        assert!(html.contains("loadUserPosts(2)"));
+        assert!(html.contains(r#"<meta property="og:url" content="https://bord.example/eve">"#));
+        assert!(html.contains(r#"<meta property="og:image" content="https://bord.example/B."#));
+    }
 }
@@ -1,14 +1,31 @@
 use spin_sdk::http::{Request, Response};
+use serde::Deserialize;
+use crate::core::db::BordStore;
 use uuid::Uuid;
 use ammonia::Builder;
-use crate::models::models::{User, TokenData};
-use crate::core::helpers::{store, hash_password, verify_password, validate_uuid, now_iso};
-use crate::core::errors::ApiError;
-use crate::auth::validate_token;
+use crate::models::models::{User, Role, Tombstone};
+use crate::core::helpers::{store, hash_password, verify_password, now_iso};
+use crate::core::errors::{ApiError, FieldError};
+use crate::core::validate::{parse_body, nullable, Validate};
+use crate::core::query_params::{parse_query_params, get_string};
+use crate::core::bot_signals::{signup_verdict, FormSignals, SignupVerdict};
+use crate::core::timezone::parse_timezone;
+use crate::auth::{validate_token, issue_token, revoke_user_tokens, delete_user_sessions, uses_session_cookie, session_cookie, csrf_token};
+use crate::posts::delete_user_posts;
+use crate::moderation::delete_user_held_posts;
+use crate::oauth::delete_user_links;
+use crate::follow::{remove_user_follows, follower_count, following_count, is_following};
+use crate::templates::invalidate_profile_page;
+use crate::core::db::{add_member, remove_member};
+use crate::core::etag::json_with_etag;
+use crate::core::permissions::promote_if_listed;
+use crate::core::idempotency;
+use crate::webhooks;
+use crate::avatars::avatar_url;
 use crate::config::*;
 
 
-fn sanitize_text(text: &str) -> String {
+pub fn sanitize_text(text: &str) -> String {
     // Sanitize to plain text only - no HTML allowed
     // Use ammonia with all tags disabled to strip HTML
     Builder::default()
@@ -17,52 +34,210 @@ fn sanitize_text(text: &str) -> String {
         .to_string()
 }
 
-fn build_user_json(user: &User) -> serde_json::Value {
-    serde_json::json!({
+pub fn build_user_json(store: &dyn BordStore, user: &User) -> anyhow::Result<serde_json::Value> {
+    Ok(serde_json::json!({
         "id": user.id,
         "username": user.username,
         "bio": user.bio.as_ref().unwrap_or(&String::new()),
-    })
+        "role": user.role,
+        "avatar_url": avatar_url(&user.id),
+        "follower_count": follower_count(store, &user.id)?,
+        "following_count": following_count(store, &user.id)?,
+    }))
+}
+
+/// The user's own view of their account: public fields plus private settings
+fn build_own_user_json(store: &dyn BordStore, user: &User) -> anyhow::Result<serde_json::Value> {
+    let mut resp = build_user_json(store, user)?;
+    resp["timezone"] = user.timezone.clone().into();
+    resp["email"] = user.email.clone().into();
+    resp["created_at"] = user.created_at.clone().into();
+    Ok(resp)
+}
+
+/// Resolve a username through the `username:{name}` index
+pub fn find_user_id_by_username(store: &dyn BordStore, username: &str) -> anyhow::Result<Option<String>> {
+    store.get_json::<String>(&username_key(username))
+}
+
+/// Names nobody can sign up with; the system account is created via the admin API
+pub fn is_reserved_username(username: &str) -> bool {
+    username.eq_ignore_ascii_case(SYSTEM_USERNAME)
+}
+
+/// Names no new account can take: the reserved ones and those listed in
+/// BORD_ADMIN_USERNAMES, which would otherwise be promoted to admin
+pub fn is_signup_blocked(username: &str) -> bool {
+    is_reserved_username(username) || admin_usernames().iter().any(|name| name.eq_ignore_ascii_case(username))
+}
+
+/// ID of the system account, once an admin has created it
+pub fn system_account_id(store: &dyn BordStore) -> anyhow::Result<Option<String>> {
+    store.get_json::<String>(SYSTEM_ACCOUNT_KEY)
+}
+
+pub fn find_user_by_username(store: &dyn BordStore, username: &str) -> anyhow::Result<Option<User>> {
+    match find_user_id_by_username(store, username)? {
+        Some(id) => store.get_json::<User>(&user_key(&id)),
+        None => Ok(None),
+    }
+}
+
+/// Username to show as a post's author; deleted accounts show as `[deleted]`
+pub fn author_name(store: &dyn BordStore, user_id: &str) -> anyhow::Result<String> {
+    Ok(store.get_json::<User>(&user_key(user_id))?
+        .map(|u| u.username)
+        .unwrap_or_else(|| DELETED_USERNAME.to_string()))
+}
+
+/// Record a new account in the username index and the users list
+pub fn register_user(store: &dyn BordStore, user: &User) -> anyhow::Result<()> {
+    store.set_json(&user_key(&user.id), user)?;
+    store.set_json(&username_key(&user.username), &user.id)?;
+
+    add_member(store, USERS_LIST_KEY, &user.id)?;
+    promote_if_listed(store, &user.username)?;
+
+    webhooks::emit(store, "user.created", serde_json::json!({
+        "id": user.id,
+        "username": user.username,
+    }))?;
+
+    Ok(())
 }
 
 fn get_user_by_id(user_id: &str) -> anyhow::Result<Option<User>> {
-     let store = store();
+     let store = store()?;
      let user_key = user_key(user_id);
      store.get_json::<User>(&user_key)
 }
 
+/// Why a requested username can't be used as typed, before normalization, as (code, message)
+fn username_format_problem(username: &str) -> Option<(&'static str, &'static str)> {
+    if username.is_empty() {
+        Some(("username_required", "Username is required"))
+    } else if username.len() < MIN_USERNAME_LENGTH || username.len() > MAX_USERNAME_LENGTH {
+        Some(("username_length", "Username must be 3-50 characters"))
+    } else {
+        None
+    }
+}
+
+/// Whether an account has, or had, this username; a deleted account's name
+/// stays taken so nobody can pass as its author
+pub fn username_in_use(store: &dyn BordStore, username: &str) -> anyhow::Result<bool> {
+    Ok(find_user_id_by_username(store, username)?.is_some() || store.exists(&retired_username_key(username))?)
+}
+
+/// Whether a sanitized username is neither reserved nor taken
+fn username_free(store: &dyn BordStore, username: &str) -> anyhow::Result<bool> {
+    Ok(!is_signup_blocked(username) && !username_in_use(store, username)?)
+}
+
+/// `GET /users/available?username=`: applies the same rules as registration
+pub fn check_username_available(req: Request) -> anyhow::Result<Response> {
+    let params = parse_query_params(req.uri());
+    let username = match get_string(&params, "username", None) {
+        Some(username) => username,
+        None => return Ok(ApiError::BadRequest("username_required", "Username is required".to_string()).into()),
+    };
+
+    let sanitized = sanitize_text(&username);
+    let reason = match username_format_problem(&username) {
+        Some((_, problem)) => Some(problem),
+        None if !username_free(&store()?, &sanitized)? => Some("Username exists"),
+        None => None,
+    };
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&serde_json::json!({
+            "username": sanitized,
+            "available": reason.is_none(),
+            "reason": reason,
+        }))?)
+        .build())
+}
+
+/// Why a new password can't be used, if it can't
+pub fn password_problem(field: &'static str, password: &str) -> Option<FieldError> {
+    if password.is_empty() {
+        Some(FieldError::new(field, "password_required", "Password is required"))
+    } else if password.len() < MIN_PASSWORD_LENGTH {
+        Some(FieldError::new(field, "password_too_short", "Password must be at least 3 characters"))
+    } else {
+        None
+    }
+}
+
+/// Why an email address can't be stored, if it can't. Only the shape is
+/// checked; the address is proven by a reset mail arriving.
+fn email_problem(field: &'static str, email: &str) -> Option<FieldError> {
+    let valid = email.len() <= MAX_EMAIL_LENGTH
+        && !email.chars().any(|c| c.is_whitespace() || c.is_control())
+        && email.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty() && !domain.contains('@') && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.')
+        });
+    (!valid).then(|| FieldError::new(field, "email_invalid", "Invalid email address"))
+}
+
+/// An optional email from a request body, trimmed; empty means none
+fn normalize_email(email: Option<&str>) -> Option<String> {
+    email.map(str::trim).filter(|e| !e.is_empty()).map(str::to_string)
+}
+
+/// Body of `POST /users`
+#[derive(Deserialize)]
+struct NewUser {
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    password: String,
+    /// Optional, for password resets
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(flatten)]
+    signals: FormSignals,
+}
+
+impl Validate for NewUser {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut problems = Vec::new();
+        if let Some((code, problem)) = username_format_problem(&self.username) {
+            problems.push(FieldError::new("username", code, problem));
+        }
+        problems.extend(password_problem("password", &self.password));
+        if let Some(email) = normalize_email(self.email.as_deref()) {
+            problems.extend(email_problem("email", &email));
+        }
+        problems
+    }
+}
+
 pub fn create_user(req: Request) -> anyhow::Result<Response> {
-     let store = store();
-     let body = req.body();
+     let store = store()?;
  
-     let new_user: serde_json::Value = serde_json::from_slice(body)?;
-     let username = new_user["username"].as_str().unwrap_or("");
-     let password = new_user["password"].as_str().unwrap_or("");
+     let new_user: NewUser = match parse_body(&req) {
+         Ok(body) => body,
+         Err(e) => return Ok(e.into()),
+     };
+     let username = new_user.username.as_str();
+     let password = new_user.password.as_str();
  
-     if username.is_empty() {
-         return Ok(ApiError::BadRequest("Username is required".to_string()).into());
-     }
-     if username.len() < MIN_USERNAME_LENGTH || username.len() > MAX_USERNAME_LENGTH {
-         return Ok(ApiError::BadRequest("Username must be 3-50 characters".to_string()).into());
-     }
-     if password.is_empty() {
-         return Ok(ApiError::BadRequest("Password is required".to_string()).into());
-     }
-     if password.len() < MIN_PASSWORD_LENGTH {
-         return Ok(ApiError::BadRequest("Password must be at least 3 characters".to_string()).into());
+     // Reject likely bots before touching the store
+     match signup_verdict(&req, &new_user.signals) {
+         SignupVerdict::Block => return Ok(ApiError::Forbidden.into()),
+         SignupVerdict::Retry => return Ok(ApiError::BadRequest("signup_retry_required", "Signup looked automated; wait a moment and submit again".to_string()).into()),
+         SignupVerdict::Allow => {}
      }
  
      // Sanitize username at input time
      let sanitized_username = sanitize_text(username);
  
      // Check duplicate username
-     let existing_users: Vec<String> = store.get_json(USERS_LIST_KEY)?.unwrap_or_default();
-     for id in &existing_users {
-         if let Some(u) = store.get_json::<User>(&user_key(id))? {
-             if u.username == sanitized_username {
-                 return Ok(ApiError::Conflict("Username exists".to_string()).into());
-             }
-         }
+     if !username_free(&store, &sanitized_username)? {
+         return Ok(ApiError::Conflict("username_taken", "Username exists".to_string()).into());
      }
      let id = Uuid::new_v4().to_string();
      
@@ -71,146 +246,329 @@ pub fn create_user(req: Request) -> anyhow::Result<Response> {
          username: sanitized_username,
          password: hash_password(password)?,
          bio: None,
+         created_at: Some(now_iso()),
+         role: Role::User,
+         timezone: None,
+         email: normalize_email(new_user.email.as_deref()),
      };
      
-     let key = user_key(&id);
-     store.set_json(&key, &user)?;
-     
-     // Add to users_list
-     let mut users = existing_users;
-     users.push(id.clone());
-     store.set_json(USERS_LIST_KEY, &users)?;
+     register_user(&store, &user)?;
+     // Stored under the caller's IP, so account deletion can only find it this way
+     idempotency::attribute_to(&store, &req, &user.id)?;
  
      Ok(Response::builder()
          .status(201)
          .header("Content-Type", "application/json")
-         .body(serde_json::to_vec(&user)?)
+         .body(serde_json::to_vec(&build_own_user_json(&store, &user)?)?)
          .build())
  }
 
-pub fn get_profile(req: Request) -> anyhow::Result<Response> {
-     let user_id = match validate_token(&req) {
-         Some(uid) => uid,
-         None => return Ok(ApiError::Unauthorized.into()),
-     };
-
+pub fn get_profile(user_id: String) -> anyhow::Result<Response> {
      match get_user_by_id(&user_id)? {
          Some(user) => Ok(Response::builder()
              .status(200)
              .header("Content-Type", "application/json")
-             .body(serde_json::to_vec(&build_user_json(&user))?)
+             .body(serde_json::to_vec(&build_own_user_json(&store()?, &user)?)?)
              .build()),
-         None => Ok(ApiError::NotFound("User not found".to_string()).into()),
+         None => Ok(ApiError::NotFound("user_not_found", "User not found".to_string()).into()),
      }
 }
 
-pub fn get_user_details(path: &str) -> anyhow::Result<Response> {
-     let user_id = path.trim_start_matches("/users/");
-     
-     if user_id.is_empty() || !validate_uuid(user_id) {
-         return Ok(ApiError::BadRequest("User ID required".to_string()).into());
-     }
-
+pub fn get_user_details(req: Request, user_id: &str) -> anyhow::Result<Response> {
      match get_user_by_id(user_id)? {
-         Some(user) => Ok(Response::builder()
-             .status(200)
-             .header("Content-Type", "application/json")
-             .body(serde_json::to_vec(&build_user_json(&user))?)
-             .build()),
-         None => Ok(ApiError::NotFound("User not found".to_string()).into()),
+         Some(user) => {
+             let store = store()?;
+             let mut resp = build_user_json(&store, &user)?;
+
+             // Relationship to the caller, when they are logged in and looking at someone else
+             if let Some(viewer_id) = validate_token(&req).filter(|id| id != user_id) {
+                 resp["is_following"] = is_following(&store, &viewer_id, user_id)?.into();
+                 resp["follows_you"] = is_following(&store, user_id, &viewer_id)?.into();
+             }
+
+             Ok(json_with_etag(&req, serde_json::to_vec(&resp)?))
+         }
+         None => {
+             // Deleted accounts still resolve so references to them keep rendering
+             if let Some(tombstone) = store()?.get_json::<Tombstone>(&tombstone_key(user_id))? {
+                 let resp = serde_json::json!({
+                     "id": tombstone.id,
+                     "username": DELETED_USERNAME,
+                     "bio": "",
+                     "avatar_url": avatar_url(&tombstone.id),
+                     "deleted": true,
+                 });
+                 return Ok(json_with_etag(&req, serde_json::to_vec(&resp)?));
+             }
+             Ok(ApiError::NotFound("user_not_found", "User not found".to_string()).into())
+         }
      }
 }
 
-pub fn update_profile(req: Request) -> anyhow::Result<Response> {
-     let user_id = match validate_token(&req) {
-         Some(uid) => uid,
-         None => return Ok(ApiError::Unauthorized.into()),
+/// Body of `DELETE /profile`
+#[derive(Deserialize)]
+struct AccountDeletion {
+    #[serde(default)]
+    password: String,
+}
+
+impl Validate for AccountDeletion {
+    // A missing password is just a wrong one: 401, not a validation error
+    fn validate(&self) -> Vec<FieldError> {
+        Vec::new()
+    }
+}
+
+pub fn delete_account(req: Request, user_id: String) -> anyhow::Result<Response> {
+     let store = store()?;
+     let user = match get_user_by_id(&user_id)? {
+         Some(u) => u,
+         None => return Ok(ApiError::NotFound("user_not_found", "User not found".to_string()).into()),
      };
- 
-     let store = store();
+
+     // Require the password so a stolen token can't destroy the account
+     let body: AccountDeletion = match parse_body(&req) {
+         Ok(body) => body,
+         Err(e) => return Ok(e.into()),
+     };
+     if !verify_password(&body.password, &user.password) {
+         return Ok(ApiError::Unauthorized.into());
+     }
+
+     purge_user(&store, &user)?;
+
+     let tombstone = Tombstone {
+         id: user_id.clone(),
+         deleted_at: now_iso(),
+     };
+     store.set_json(&tombstone_key(&user_id), &tombstone)?;
+     store.set_json(&retired_username_key(&user.username), &user_id)?;
+     invalidate_profile_page(&store, &user.username)?;
+
+     Ok(Response::builder().status(204).build())
+}
+
+/// Remove everything stored for a user: account, posts, follows, sessions,
+/// provider links, warnings, counters, login failures and stored idempotent responses
+fn purge_user(store: &dyn BordStore, user: &User) -> anyhow::Result<()> {
+     delete_user_posts(store, &user.id)?;
+     delete_user_held_posts(store, &user.id)?;
+     remove_user_follows(store, &user.id)?;
+     delete_user_sessions(store, &user.id)?;
+     delete_user_links(store, &user.id)?;
+     idempotency::forget_user(store, &user.id)?;
+     store.delete_ttl(&login_failures_key(&format!("user:{}", user.username)))?;
+
+     remove_member(store, USERS_LIST_KEY, &user.id)?;
+     store.delete(&user_key(&user.id))?;
+     store.delete(&username_key(&user.username))?;
+     store.delete(&last_post_key(&user.id))?;
+     store.delete(&warnings_key(&user.id))?;
+
+     Ok(())
+}
+
+/// Body of `PUT /profile`; absent fields are left alone
+#[derive(Deserialize)]
+struct ProfileChanges {
+    bio: Option<String>,
+    /// `null` or `""` goes back to UTC
+    #[serde(default, deserialize_with = "nullable")]
+    timezone: Option<Option<String>>,
+    /// `null` or `""` removes it; changing it needs `old_password`, since
+    /// whoever controls the address can reset the password
+    #[serde(default, deserialize_with = "nullable")]
+    email: Option<Option<String>>,
+    new_password: Option<String>,
+    old_password: Option<String>,
+}
+
+impl Validate for ProfileChanges {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut problems = Vec::new();
+        if self.bio.as_ref().is_some_and(|bio| bio.len() > MAX_BIO_LENGTH) {
+            problems.push(FieldError::new("bio", "bio_too_long", format!("Bio too long (max {} chars)", MAX_BIO_LENGTH)));
+        }
+        if let Some(Some(tz)) = &self.timezone {
+            if !tz.is_empty() && parse_timezone(tz).is_none() {
+                problems.push(FieldError::new("timezone", "invalid_time_zone", "Invalid time zone"));
+            }
+        }
+        if let Some(Some(email)) = &self.email {
+            if let Some(email) = normalize_email(Some(email)) {
+                problems.extend(email_problem("email", &email));
+            }
+        }
+        if let Some(new_password) = &self.new_password {
+            problems.extend(password_problem("new_password", new_password));
+        }
+        if (self.new_password.is_some() || self.email.is_some()) && self.old_password.is_none() {
+            problems.push(FieldError::new("old_password", "current_password_required", "Current password required"));
+        }
+        problems
+    }
+}
+
+pub fn update_profile(req: Request, user_id: String) -> anyhow::Result<Response> {
+     let store = store()?;
      let user_key = user_key(&user_id);
      
      if let Some(mut user) = store.get_json::<User>(&user_key)? {
-         let value: serde_json::Value = serde_json::from_slice(req.body())?;
+         let changes: ProfileChanges = match parse_body(&req) {
+             Ok(body) => body,
+             Err(e) => return Ok(e.into()),
+         };
          let mut password_changed = false;
  
          // Update bio if provided
-         if let Some(bio) = value["bio"].as_str() {
-             if bio.len() > MAX_BIO_LENGTH {
-                 return Ok(ApiError::BadRequest("Bio too long (max 500 chars)".to_string()).into());
-             }
+         if let Some(bio) = &changes.bio {
              // Sanitize bio at input time
              let sanitized_bio = sanitize_text(bio);
              user.bio = if sanitized_bio.is_empty() { None } else { Some(sanitized_bio) };
          }
  
-         // Update password if provided
-         if let Some(new_password) = value["new_password"].as_str() {
-            if new_password.is_empty() || new_password.len() < 3 {
-                return Ok(ApiError::BadRequest("Password must be 3+ characters".to_string()).into());
-            }
-            
-            let old_password = value["old_password"].as_str()
-                .ok_or_else(|| ApiError::BadRequest("Current password required".to_string()))?;
-            
+         // Update time zone if provided
+         if let Some(timezone) = changes.timezone {
+             user.timezone = timezone.filter(|tz| !tz.is_empty());
+         }
+
+         // Password and email changes need the current password; validation guarantees it came along
+         if let Some(old_password) = &changes.old_password {
             if !verify_password(old_password, &user.password) {
                 return Ok(ApiError::Unauthorized.into());
             }
-            
+         }
+         if let Some(email) = &changes.email {
+            user.email = normalize_email(email.as_deref());
+         }
+         if let Some(new_password) = &changes.new_password {
             user.password = hash_password(new_password)?;
             password_changed = true;
          }
  
          store.set_json(&user_key, &user)?;
+         invalidate_profile_page(&store, &user.username)?;
          
          // If password changed, invalidate all tokens for this user and issue a new one
-         let mut response_data = build_user_json(&user);
+         let mut response_data = build_own_user_json(&store, &user)?;
+         let mut builder = Response::builder();
          if password_changed {
-             let all_tokens: Vec<String> = store.get_json(TOKENS_LIST_KEY)?.unwrap_or_default();
-             
-             // Filter out tokens for this user and delete them
-             let filtered_tokens: Vec<String> = all_tokens
-                 .into_iter()
-                 .filter(|token| {
-                     let token_key = token_key(token);
-                     if let Ok(Some(token_data)) = store.get_json::<TokenData>(&token_key) {
-                         if token_data.user_id == user_id {
-                             // Delete token from store
-                             let _ = store.delete(&token_key);
-                             false // Exclude from filtered list
-                         } else {
-                             true // Keep token from other users
-                         }
-                     } else {
-                         true // Keep if we can't read it
-                     }
-                 })
-                 .collect();
-             store.set_json(TOKENS_LIST_KEY, &filtered_tokens)?;
-             
-             // Generate new token
-             let new_token = Uuid::new_v4().to_string();
-             let token_data = TokenData {
-                 user_id: user_id.clone(),
-                 created_at: now_iso(),
-             };
-             store.set_json(&token_key(&new_token), &token_data)?;
-             
-             // Add to tokens_list
-             let mut updated_tokens = filtered_tokens;
-             updated_tokens.push(new_token.clone());
-             store.set_json(TOKENS_LIST_KEY, &updated_tokens)?;
+             revoke_user_tokens(&store, &user_id)?;
+             let new_token = issue_token(&store, &user_id, &req)?;
              
+             // Cookie sessions get the replacement cookie and its CSRF token
+             if uses_session_cookie(&req) {
+                 response_data["csrf_token"] = csrf_token(&new_token).into();
+                 builder.header("Set-Cookie", session_cookie(&new_token));
+             }
+
              // Include new token in response
              response_data["token"] = serde_json::Value::String(new_token);
          }
  
-         Ok(Response::builder()
+         Ok(builder
              .status(200)
              .header("Content-Type", "application/json")
              .body(serde_json::to_vec(&response_data)?)
              .build())
      } else {
-         Ok(ApiError::NotFound("User not found".to_string()).into())
+         Ok(ApiError::NotFound("user_not_found", "User not found".to_string()).into())
      }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use crate::core::db::MemoryStore;
+    use crate::follow::follow_user;
+    use crate::moderation::hold_post;
+    use crate::posts::publish_post;
+
+    fn user(id: &str, username: &str) -> User {
+        User {
+            id: id.to_string(),
+            username: username.to_string(),
+            password: String::new(),
+            bio: None,
+            created_at: Some(now_iso()),
+            role: Role::User,
+            timezone: None,
+            email: None,
+        }
+    }
+
+    #[test]
+    fn purge_leaves_nothing_that_refers_to_the_user() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+        let gone = user("7d0c7f5e-1111-4aaa-8bbb-000000000001", "gone");
+        let other = user("7d0c7f5e-1111-4aaa-8bbb-000000000002", "other");
+        register_user(store, &gone).unwrap();
+        register_user(store, &other).unwrap();
+
+        let post = publish_post(store, &gone.id, "hello", None).unwrap();
+        hold_post(store, &gone.id, "held", None, None).unwrap();
+        follow_user(store, &gone.id, &other.id).unwrap();
+        follow_user(store, &other.id, &gone.id).unwrap();
+        store.set_json(&impressions_key(&post.id, "2026-01-01"), &3u64).unwrap();
+        store.set_json(&oauth_link_key("github", "42"), &gone.id).unwrap();
+        store.set_json(&warnings_key(&gone.id), &serde_json::json!([{"reason": "spam"}])).unwrap();
+        store.set_json(&last_post_key(&gone.id), &0i64).unwrap();
+        store.set_json_ttl(&login_failures_key("user:gone"), &1u32, 60).unwrap();
+        add_member(store, &user_tokens_set(&gone.id), "expired-session").unwrap();
+        store.set_json(&session_activity_key("expired-session"), &"gone was here").unwrap();
+
+        // The signup's stored response is keyed by IP, not by the new account
+        let signup = Request::builder()
+            .method(spin_sdk::http::Method::Post)
+            .uri("/users")
+            .header(IDEMPOTENCY_HEADER, "signup-1")
+            .body(Vec::new())
+            .build();
+        let signup_key = idempotency_key_for("/users", "unknown", "signup-1");
+        store.set_json_ttl(&signup_key, &"gone's profile", 60).unwrap();
+        idempotency::attribute_to(store, &signup, &gone.id).unwrap();
+
+        purge_user(store, &gone).unwrap();
+
+        let leftovers: Vec<String> = store.scan("").unwrap()
+            .into_iter()
+            .filter(|key| {
+                let value = String::from_utf8_lossy(&store.get(key).unwrap().unwrap_or_default()).into_owned();
+                key.contains(&gone.id) || key.contains(&post.id) || key.ends_with(":gone")
+                    || value.contains(&gone.id) || value.contains("gone")
+            })
+            .collect();
+        assert!(leftovers.is_empty(), "{:?}", leftovers);
+        assert!(store.exists(&user_key(&other.id)).unwrap());
+    }
+
+    #[test]
+    fn deleted_usernames_stay_taken() {
+        let backend = MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+        assert!(username_free(store, "gone").unwrap());
+
+        store.set_json(&retired_username_key("gone"), &"7d0c7f5e-1111-4aaa-8bbb-000000000001").unwrap();
+        assert!(!username_free(store, "gone").unwrap());
+    }
+
+    #[test]
+    fn email_problem_accepts_only_plain_addresses() {
+        for ok in ["a@example.com", "first.last+tag@mail.example.org"] {
+            assert!(email_problem("email", ok).is_none(), "{}", ok);
+        }
+        for bad in ["", "example.com", "@example.com", "a@b", "a@@example.com", "a b@example.com", "a@example.com."] {
+            assert!(email_problem("email", bad).is_some(), "{}", bad);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn sanitize_text_leaves_no_tags(text in "(\\PC|<[a-z]+[^>]*>|</[a-z]+>)*") {
+            let clean = sanitize_text(&text);
+            prop_assert!(!clean.contains('<'));
+            prop_assert!(!clean.contains('>'));
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! Outgoing webhooks. Admins register URLs for some of `WEBHOOK_EVENTS`, on
+//! origins listed in `BORD_WEBHOOK_ALLOWED_ORIGINS`. `emit` sends a signed
+//! delivery per subscribed webhook right away, waiting at most
+//! `WEBHOOK_TIMEOUT_MS` to connect and as long again for the response.
+//! Failed deliveries are queued and retried with backoff by
+//! `POST /admin/maintenance/webhooks`, which a scheduler (cron, a Kubernetes
+//! CronJob) should call every minute or so.
+
+use spin_sdk::http::{Request, Response};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+use crate::core::db::BordStore;
+use crate::models::models::{Webhook, WebhookDelivery};
+use crate::core::helpers::{store, now_iso, post_with_timeout};
+use crate::core::errors::{ApiError, FieldError};
+use crate::core::permissions::{authorize, Permission};
+use crate::config::*;
+
+/// Hex HMAC-SHA256 of a payload, sent as `X-Bord-Signature: sha256=<hex>`
+fn signature(secret: &str, payload: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(payload);
+    mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn webhooks(store: &dyn BordStore) -> anyhow::Result<Vec<Webhook>> {
+    Ok(store.get_json(WEBHOOKS_KEY)?.unwrap_or_default())
+}
+
+/// POST the delivery; any 2xx counts as delivered
+fn attempt(webhook: &Webhook, delivery: &WebhookDelivery) -> bool {
+    let headers = [
+        ("content-type", "application/json".to_string()),
+        ("x-bord-event", delivery.event.clone()),
+        ("x-bord-delivery", delivery.id.clone()),
+        ("x-bord-signature", format!("sha256={}", signature(&webhook.secret, delivery.payload.as_bytes()))),
+    ];
+    matches!(post_with_timeout(&webhook.url, &headers, delivery.payload.as_bytes(), WEBHOOK_TIMEOUT_MS), Ok(200..=299))
+}
+
+/// Seconds to wait before retrying after `attempts` failures
+fn backoff_seconds(attempts: u32) -> i64 {
+    WEBHOOK_RETRY_BASE_SECONDS << attempts.saturating_sub(1).min(16)
+}
+
+/// Try a delivery; on failure (re)queue it, or drop it once it is out of attempts
+fn deliver(store: &dyn BordStore, webhook: &Webhook, delivery: WebhookDelivery) -> anyhow::Result<bool> {
+    let sent = attempt(webhook, &delivery);
+    record_attempt(store, delivery, sent)?;
+    Ok(sent)
+}
+
+fn record_attempt(store: &dyn BordStore, mut delivery: WebhookDelivery, sent: bool) -> anyhow::Result<()> {
+    let key = webhook_delivery_key(&delivery.id);
+    if sent {
+        store.delete(&key)?;
+        return Ok(());
+    }
+
+    delivery.attempts += 1;
+    if delivery.attempts >= WEBHOOK_MAX_ATTEMPTS {
+        store.delete(&key)?;
+    } else {
+        delivery.next_attempt_at = chrono::Utc::now().timestamp() + backoff_seconds(delivery.attempts);
+        store.set_json(&key, &delivery)?;
+    }
+    Ok(())
+}
+
+/// Send `event` to each subscribed webhook; deliveries that fail are queued for `retry_due`
+pub fn emit(store: &dyn BordStore, event: &str, data: serde_json::Value) -> anyhow::Result<()> {
+    emit_with(store, event, data, attempt)
+}
+
+fn emit_with(
+    store: &dyn BordStore,
+    event: &str,
+    data: serde_json::Value,
+    attempt: impl Fn(&Webhook, &WebhookDelivery) -> bool,
+) -> anyhow::Result<()> {
+    let subscribed: Vec<Webhook> = webhooks(store)?
+        .into_iter()
+        .filter(|w| w.events.iter().any(|e| e == event))
+        .collect();
+    if subscribed.is_empty() {
+        return Ok(());
+    }
+
+    let payload = serde_json::json!({
+        "event": event,
+        "created_at": now_iso(),
+        "data": data,
+    }).to_string();
+
+    for webhook in &subscribed {
+        let delivery = WebhookDelivery {
+            id: Uuid::new_v4().to_string(),
+            webhook_id: webhook.id.clone(),
+            event: event.to_string(),
+            payload: payload.clone(),
+            attempts: 0,
+            next_attempt_at: 0,
+        };
+        let sent = attempt(webhook, &delivery);
+        record_attempt(store, delivery, sent)?;
+    }
+    Ok(())
+}
+
+/// Send queued deliveries whose backoff has passed; returns how many went through
+pub fn retry_due(store: &dyn BordStore) -> anyhow::Result<usize> {
+    let webhooks = webhooks(store)?;
+    let now = chrono::Utc::now().timestamp();
+    let mut delivered = 0;
+
+    for key in store.scan(WEBHOOK_DELIVERY_PREFIX)? {
+        let delivery = match store.get_json::<WebhookDelivery>(&key)? {
+            Some(d) if d.next_attempt_at <= now => d,
+            _ => continue,
+        };
+        match webhooks.iter().find(|w| w.id == delivery.webhook_id) {
+            Some(webhook) => {
+                if deliver(store, webhook, delivery)? {
+                    delivered += 1;
+                }
+            }
+            // The webhook was removed since this was queued
+            None => store.delete(&key)?,
+        }
+    }
+    Ok(delivered)
+}
+
+/// `scheme://host[:port]` of an http(s) URL, lowercased, or `None` if it has
+/// no host or carries credentials
+fn url_origin(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    let scheme = scheme.to_ascii_lowercase();
+    if scheme != "https" && scheme != "http" {
+        return None;
+    }
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    if authority.is_empty() || authority.contains('@') {
+        return None;
+    }
+    Some(format!("{}://{}", scheme, authority.to_ascii_lowercase()))
+}
+
+/// Event names from a registration body, all known and at least one
+fn parse_events(value: &serde_json::Value) -> Result<Vec<String>, FieldError> {
+    let events: Vec<String> = value.as_array()
+        .map(|list| list.iter().filter_map(|e| e.as_str()).map(str::to_string).collect())
+        .unwrap_or_default();
+    if events.is_empty() {
+        return Err(FieldError::new("events", "events_required", "At least one event is required"));
+    }
+    if let Some(unknown) = events.iter().find(|e| !WEBHOOK_EVENTS.contains(&e.as_str())) {
+        return Err(FieldError::new("events", "unknown_event", format!("Unknown event: {}", unknown)));
+    }
+    Ok(events)
+}
+
+/// Listed without the secret, which is only shown once at registration
+fn webhook_json(webhook: &Webhook) -> serde_json::Value {
+    serde_json::json!({
+        "id": webhook.id,
+        "url": webhook.url,
+        "events": webhook.events,
+        "created_at": webhook.created_at,
+    })
+}
+
+pub fn create_webhook(req: Request) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ManageInstance) {
+        return Ok(e.into());
+    }
+
+    let value: serde_json::Value = serde_json::from_slice(req.body())?;
+    let url = value["url"].as_str().unwrap_or_default().trim();
+    let events = parse_events(&value["events"]);
+
+    // Spin refuses requests to hosts outside allowed_outbound_hosts, so a URL
+    // elsewhere could never be delivered to
+    let mut problems = Vec::new();
+    match url_origin(url) {
+        None => problems.push(FieldError::new("url", "url_invalid", "URL must be http:// or https:// with a host")),
+        Some(origin) if !webhook_allowed_origins().contains(&origin) => {
+            problems.push(FieldError::new("url", "url_not_allowed", format!("{} is not in BORD_WEBHOOK_ALLOWED_ORIGINS", origin)));
+        }
+        Some(_) => {}
+    }
+    let events = match events {
+        Ok(events) if problems.is_empty() => events,
+        Ok(_) => return Ok(ApiError::Invalid(problems).into()),
+        Err(problem) => {
+            problems.push(problem);
+            return Ok(ApiError::Invalid(problems).into());
+        }
+    };
+
+    let webhook = Webhook {
+        id: Uuid::new_v4().to_string(),
+        url: url.to_string(),
+        events,
+        secret: Uuid::new_v4().simple().to_string(),
+        created_at: now_iso(),
+    };
+
+    let store = store()?;
+    let mut all = webhooks(&store)?;
+    all.push(webhook.clone());
+    store.set_json(WEBHOOKS_KEY, &all)?;
+
+    let mut resp = webhook_json(&webhook);
+    resp["secret"] = webhook.secret.into();
+
+    Ok(Response::builder()
+        .status(201)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&resp)?)
+        .build())
+}
+
+pub fn list_webhooks(req: Request) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ManageInstance) {
+        return Ok(e.into());
+    }
+
+    let list: Vec<serde_json::Value> = webhooks(&store()?)?.iter().map(webhook_json).collect();
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&list)?)
+        .build())
+}
+
+pub fn delete_webhook(req: Request, webhook_id: &str) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ManageInstance) {
+        return Ok(e.into());
+    }
+
+    let store = store()?;
+    let mut all = webhooks(&store)?;
+    let before = all.len();
+    all.retain(|w| w.id != webhook_id);
+    if all.len() == before {
+        return Ok(ApiError::NotFound("webhook_not_found", "Webhook not found".to_string()).into());
+    }
+    store.set_json(WEBHOOKS_KEY, &all)?;
+
+    Ok(Response::builder().status(204).build())
+}
+
+pub fn retry_deliveries(req: Request) -> anyhow::Result<Response> {
+    if let Err(e) = authorize(&req, Permission::ManageInstance) {
+        return Ok(e.into());
+    }
+
+    let delivered = retry_due(&store()?)?;
+
+    Ok(Response::builder()
+        .status(200)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_vec(&serde_json::json!({"delivered": delivered}))?)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_matches_hmac_sha256() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn events_must_be_known() {
+        assert!(parse_events(&serde_json::json!(["post.created", "user.followed"])).is_ok());
+        assert!(parse_events(&serde_json::json!([])).is_err());
+        assert!(parse_events(&serde_json::json!(["post.deleted"])).is_err());
+        assert!(parse_events(&serde_json::Value::Null).is_err());
+    }
+
+    #[test]
+    fn backoff_doubles_per_attempt() {
+        assert_eq!(backoff_seconds(1), WEBHOOK_RETRY_BASE_SECONDS);
+        assert_eq!(backoff_seconds(3), WEBHOOK_RETRY_BASE_SECONDS * 4);
+    }
+
+    #[test]
+    fn origins_are_normalized_for_the_allow_list() {
+        assert_eq!(url_origin("https://Hooks.Example.com/bord?x=1").as_deref(), Some("https://hooks.example.com"));
+        assert_eq!(url_origin("http://localhost:8080").as_deref(), Some("http://localhost:8080"));
+        assert_eq!(url_origin("https://hooks.example.com@evil.example/"), None);
+        assert_eq!(url_origin("ftp://hooks.example.com/"), None);
+        assert_eq!(url_origin("https:///bord"), None);
+    }
+
+    #[test]
+    fn emit_queues_failed_deliveries_for_subscribers() {
+        let backend = crate::core::db::MemoryStore::default();
+        let store: &dyn BordStore = &backend;
+        let webhook = Webhook {
+            id: "w1".to_string(),
+            url: "https://hooks.example.com/bord".to_string(),
+            events: vec!["post.created".to_string()],
+            secret: "s".to_string(),
+            created_at: now_iso(),
+        };
+        store.set_json(WEBHOOKS_KEY, &vec![webhook]).unwrap();
+
+        emit_with(store, "post.created", serde_json::json!({"id": "p1"}), |_, _| true).unwrap();
+        assert!(store.scan(WEBHOOK_DELIVERY_PREFIX).unwrap().is_empty());
+
+        emit_with(store, "post.created", serde_json::json!({"id": "p2"}), |_, _| false).unwrap();
+        emit_with(store, "user.created", serde_json::json!({"id": "u1"}), |_, _| false).unwrap();
+
+        let queued = store.scan(WEBHOOK_DELIVERY_PREFIX).unwrap();
+        assert_eq!(queued.len(), 1);
+        let delivery = store.get_json::<WebhookDelivery>(&queued[0]).unwrap().unwrap();
+        assert_eq!((delivery.event.as_str(), delivery.attempts), ("post.created", 1));
+        assert!(delivery.next_attempt_at > chrono::Utc::now().timestamp());
+    }
+}
@@ -2,10 +2,17 @@
  * API utility for consistent fetch handling
  */
 
-const API_BASE = window.location.origin;
+const API_BASE = window.location.origin + '/api/v1';
 
-// Must match POSTS_PER_PAGE in src/config.rs
-const POSTS_PER_PAGE = 10;
+// Defaults until loadClientConfig() fetches the server's values
+let POSTS_PER_PAGE = 10;
+let USERS_PER_PAGE = 20;
+let CLIENT_CONFIG = {
+    max_post_length: 5000,
+    max_bio_length: 500,
+    features: {},
+    announcement: null
+};
 
 /**
  * Make an API request
@@ -13,7 +20,7 @@ const POSTS_PER_PAGE = 10;
  * @param {Object} options - Fetch options
  * @param {string} options.method - HTTP method (default: 'GET')
  * @param {Object} options.body - Request body (auto-stringified)
- * @param {string} options.token - Auth token (auto-added as Bearer)
+ * @param {string} options.token - Auth token (auto-added as Bearer); without one the session cookie is used
  * @param {boolean} options.json - Parse response as JSON (default: true)
  * @returns {Promise<{status: number, data: any, ok: boolean}>}
  */
@@ -33,6 +40,12 @@ async function apiCall(endpoint, options = {}) {
         headers['Authorization'] = `Bearer ${token}`;
     }
 
+    // Cookie sessions must echo their CSRF token on anything that changes state
+    const csrfToken = localStorage.getItem('csrf_token');
+    if (csrfToken && method !== 'GET') {
+        headers['X-CSRF-Token'] = csrfToken;
+    }
+
     const fetchOptions = {
         method,
         headers
@@ -71,6 +84,33 @@ async function apiCall(endpoint, options = {}) {
     }
 }
 
+/**
+ * Load limits and feature flags from GET /client-config
+ * Keeps the defaults above if the request fails
+ * @returns {Promise<Object>} The client config
+ */
+async function loadClientConfig() {
+    const res = await apiCall('/client-config');
+    if (res.ok && res.data) {
+        CLIENT_CONFIG = res.data;
+        POSTS_PER_PAGE = res.data.posts_per_page;
+        USERS_PER_PAGE = res.data.users_per_page;
+    }
+    return CLIENT_CONFIG;
+}
+
+/**
+ * Show the instance announcement, if any, in the given container
+ * @param {string} containerId - ID of the announcement container
+ */
+function renderAnnouncement(containerId) {
+    const container = document.getElementById(containerId);
+    if (container && CLIENT_CONFIG.announcement) {
+        container.textContent = CLIENT_CONFIG.announcement;
+        container.classList.add('show');
+    }
+}
+
 /**
  * Show error message (requires showError function in global scope)
  */
@@ -82,6 +122,8 @@ function handleError(error, statusCode) {
         403: 'Access denied',
         404: 'Not found',
         409: 'Conflict - resource already exists',
+        423: 'Too many failed logins - try again later',
+        429: 'Too many requests - please slow down',
         500: 'Server error'
     };
 
@@ -119,7 +161,8 @@ function renderPosts(postsArray, containerId, showUsername = true, showActionsFo
     container.innerHTML = postsArray.map(p => `
         <div class="post">
             ${showUsername ? `<div style="font-size: 13px; color: #666; margin-bottom: 8px; font-weight: 500;">
-                <a href="/${p.username}" style="color: #209CEE; text-decoration: none;">${p.username}</a>
+                ${p.author_avatar_url ? `<img src="${p.author_avatar_url}" alt="" width="20" height="20" style="border-radius: 50%; vertical-align: middle; margin-right: 6px;">` : ''}
+                <a href="/${p.username}" style="color: var(--accent); text-decoration: none;">${p.username}</a>
             </div>` : ''}
             <div class="post-content">${p.content}</div>
             <div class="post-meta">
@@ -134,6 +177,12 @@ function renderPosts(postsArray, containerId, showUsername = true, showActionsFo
             </div>
         </div>
     `).join('');
+
+    // Report what was shown in one batch; failures don't affect rendering
+    apiCall('/impressions', {
+        method: 'POST',
+        body: { post_ids: postsArray.map(p => p.id) }
+    });
 }
 
 /**
@@ -1,3 +1,10 @@
+# token signing secret both manifests read, created once
+kubectl get secret bord-secrets 2>$null | Out-Null
+if ($LASTEXITCODE -ne 0) {
+    $secret = -join ((1..64) | ForEach-Object { '{0:x}' -f (Get-Random -Maximum 16) })
+    kubectl create secret generic bord-secrets --from-literal=jwt-secret=$secret
+}
+
 # timestamp start
 $start = Get-Date
 
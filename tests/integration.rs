@@ -1,376 +1,1161 @@
+use bord::client::{error_code, error_status, field_codes, BordClient, PostQuery, ProfileUpdate, SettingsUpdate};
 use serde_json::json;
 use std::sync::Mutex;
 
 const BASE_URL: &str = "http://127.0.0.1:3000";
+// The app under test must run with BORD_MODERATION_FILTER_SECRET set to this
+const MODERATION_FILTER_SECRET: &str = "test-filter-secret";
 static TEST_LOCK: Mutex<()> = Mutex::new(());
 
 fn lock_test() -> std::sync::MutexGuard<'static, ()> {
     TEST_LOCK.lock().unwrap()
 }
 
+fn unique_name(prefix: &str) -> String {
+    format!("{}_{}", prefix, &uuid::Uuid::new_v4().to_string()[0..8])
+}
+
+/// Create a user and return a client logged in as them, plus their ID
+async fn signed_up(username: &str, password: &str) -> (BordClient, String) {
+    let mut client = BordClient::new(BASE_URL);
+    let user = client.create_user(username, password).await.expect("Failed to create user");
+    let login = client.login(username, password).await.expect("Failed to login");
+    assert_eq!(login.user_id, user.id);
+    (client, user.id)
+}
+
 #[tokio::test]
 async fn test_full_user_flow() {
     let _lock = lock_test();
-    let client = reqwest::Client::new();
-    
-    // 1. Create user
-    let username = format!("flow_{}", &uuid::Uuid::new_v4().to_string()[0..8]);
-    let create_body = json!({
-        "username": username,
-        "password": "test"
-    });
+    let mut client = BordClient::new(BASE_URL);
 
-    let user_resp = client
-        .post(&format!("{}/users", BASE_URL))
-        .json(&create_body)
-        .send()
-        .await
-        .expect("Failed to create user");
-
-    assert_eq!(user_resp.status(), 201);
-    let user = user_resp.json::<serde_json::Value>().await.unwrap();
-    assert!(user.get("id").is_some(), "User ID missing in create response: {:?}", user);
-    let user_id = user["id"].as_str().unwrap().to_string();
+    // 1. Create user
+    let username = unique_name("flow");
+    let user = client.create_user(&username, "test").await.expect("Failed to create user");
+    assert!(!user.id.is_empty(), "User ID missing in create response: {:?}", user);
+    let user_id = user.id;
 
     // 2. Login
-    let login_body = json!({
-        "username": &username,
-        "password": "test"
-    });
-
-    let login_resp = client
-        .post(&format!("{}/login", BASE_URL))
-        .json(&login_body)
-        .send()
-        .await
-        .expect("Failed to login");
-
-    assert_eq!(login_resp.status(), 200);
-    let token_data = login_resp.json::<serde_json::Value>().await.unwrap();
-    assert!(token_data.get("token").is_some(), "Token field missing in response: {:?}", token_data);
-    let token = token_data["token"].as_str().unwrap().to_string();
+    let login = client.login(&username, "test").await.expect("Failed to login");
+    assert!(!login.token.is_empty(), "Token missing in response: {:?}", login);
 
     // 3. Create post
-    let post_body = json!({
-        "content": "Test post from integration test."
-    });
-
-    let post_resp = client
-        .post(&format!("{}/posts", BASE_URL))
-        .header("Authorization", format!("Bearer {}", token))
-        .json(&post_body)
-        .send()
+    let post = client
+        .create_post("Test post from integration test.")
         .await
         .expect("Failed to create post");
-
-    assert_eq!(post_resp.status(), 201);
-    let post = post_resp.json::<serde_json::Value>().await.unwrap();
-    assert_eq!(post["content"], "Test post from integration test.");
-    assert_eq!(post["user_id"], user_id);
-    let post_id = post["id"].as_str().unwrap().to_string();
+    assert_eq!(post.content, "Test post from integration test.");
+    assert_eq!(post.user_id, user_id);
 
     // 4. Edit post
-    let edit_body = json!({
-        "content": "Updated content from integration test."
-    });
-
-    let edit_resp = client
-        .put(&format!("{}/posts/{}", BASE_URL, post_id))
-        .header("Authorization", format!("Bearer {}", token))
-        .json(&edit_body)
-        .send()
+    let edited_post = client
+        .edit_post(&post.id, "Updated content from integration test.")
         .await
         .expect("Failed to edit post");
+    assert_eq!(edited_post.content, "Updated content from integration test.");
+    assert!(edited_post.updated_at.is_some(), "updated_at should be set after edit");
 
-    assert_eq!(edit_resp.status(), 200);
-    let edited_post = edit_resp.json::<serde_json::Value>().await.unwrap();
-    assert_eq!(edited_post["content"], "Updated content from integration test.");
-    assert!(edited_post["updated_at"].is_string(), "updated_at should be set after edit");
+    // 5. The post shows up in the author's own listing
+    let own_posts = client.list_posts(&PostQuery::default()).await.expect("Failed to list posts");
+    assert!(own_posts.iter().any(|p| p.id == post.id));
 }
 
 #[tokio::test]
 async fn test_post_content_validation() {
     let _lock = lock_test();
-    let client = reqwest::Client::new();
-    
+
     // Create and login a user
-    let username = format!("val_{}", &uuid::Uuid::new_v4().to_string()[0..8]);
-    let create_body = json!({
-        "username": username,
-        "password": "test"
-    });
+    let (client, _) = signed_up(&unique_name("val"), "test").await;
 
-    let user_resp = client
-        .post(&format!("{}/users", BASE_URL))
-        .json(&create_body)
-        .send()
+    // Try empty content
+    let response = client.create_post("").await;
+    assert_eq!(error_status(&response), Some(400));
+    assert_eq!(error_code(&response), Some("validation_failed"));
+    assert_eq!(field_codes(&response), ["content_required"]);
+
+    // Try content > 5000 chars
+    let long_content = "a".repeat(5001);
+    let response = client.create_post(&long_content).await;
+    assert_eq!(error_status(&response), Some(400));
+    assert_eq!(field_codes(&response), ["content_too_long"]);
+}
+
+#[tokio::test]
+async fn test_login_invalid_credentials() {
+    let _lock = lock_test();
+    let mut client = BordClient::new(BASE_URL);
+
+    let response = client.login("nonexistent_user", "wrongpass").await;
+    assert_eq!(error_status(&response), Some(401));
+}
+
+#[tokio::test]
+async fn test_create_post_requires_auth() {
+    let _lock = lock_test();
+    let client = BordClient::new(BASE_URL);
+
+    let response = client.create_post("Test post without auth").await;
+    assert_eq!(error_status(&response), Some(401));
+}
+
+#[tokio::test]
+async fn test_bio_xss_protection() {
+    let _lock = lock_test();
+    let (mut client, _) = signed_up(&unique_name("bio"), "test123").await;
+
+    // Update profile with XSS payload in bio
+    let xss_payload = "<img src=x onerror='alert(\"xss\")'>";
+    let updated_user = client
+        .update_profile(&ProfileUpdate {
+            bio: Some(xss_payload.to_string()),
+            ..Default::default()
+        })
         .await
-        .expect("Failed to create user");
+        .expect("Failed to update profile");
+    let stored_bio = updated_user.bio;
 
-    assert_eq!(user_resp.status(), 201);
+    // Bio should not contain img tags or onerror attributes
+    assert!(!stored_bio.contains("<img"));
+    assert!(!stored_bio.contains("onerror"));
+    assert!(!stored_bio.contains("alert"));
+}
 
-    let login_body = json!({
-        "username": &username,
-        "password": "test"
-    });
+#[tokio::test]
+async fn test_follow_unfollow_user() {
+    let _lock = lock_test();
 
-    let login_resp = client
-        .post(&format!("{}/login", BASE_URL))
-        .json(&login_body)
-        .send()
+    // Create two users, logged in as the first
+    let username1 = unique_name("follow1");
+    let (client, user1_id) = signed_up(&username1, "test").await;
+    let username2 = unique_name("follow2");
+    let (_, user2_id) = signed_up(&username2, "test").await;
+
+    // User1 follows user2
+    let follow_result = client.follow(&user2_id).await.expect("Failed to follow user");
+    assert_eq!(follow_result.status, "followed");
+
+    // Following by username resolves to the same user
+    client.follow_username(&username2).await.expect("Failed to follow by username");
+
+    // Check user1's followings list
+    let followings = client.followings(&user1_id).await.expect("Failed to get followings");
+    assert!(followings.contains(&user2_id), "user2_id should be in user1's followings");
+
+    // Counts are reported on user responses
+    let user2_details = client.user(&user2_id).await.expect("Failed to get user2");
+    assert_eq!(user2_details.follower_count, 1);
+    assert_eq!(user2_details.following_count, 0);
+
+    // Expanded lists return user summaries instead of bare IDs
+    let expanded = client
+        .followers_expanded(&user2_id, 1)
         .await
-        .expect("Failed to login");
+        .expect("Failed to get expanded followers");
+    assert_eq!(expanded.len(), 1);
+    assert_eq!(expanded[0].id, user1_id);
+    assert_eq!(expanded[0].username, username1);
 
-    assert_eq!(login_resp.status(), 200);
-    let token_data = login_resp.json::<serde_json::Value>().await.unwrap();
-    assert!(token_data.get("token").is_some(), "Token field missing in response: {:?}", token_data);
-    let token = token_data["token"]
-        .as_str()
-        .unwrap()
-        .to_string();
+    // User1 unfollows user2
+    let unfollow_result = client.unfollow(&user2_id).await.expect("Failed to unfollow user");
+    assert_eq!(unfollow_result.status, "unfollowed");
 
-    // Try empty content
-    let empty_body = json!({"content": ""});
+    // Check user1's followings list is now empty
+    let followings = client
+        .followings(&user1_id)
+        .await
+        .expect("Failed to get followings after unfollow");
+    assert!(!followings.contains(&user2_id), "user2_id should not be in user1's followings after unfollow");
+    assert!(followings.is_empty(), "user1's followings should be empty");
+
+    let user1_details = client.user(&user1_id).await.expect("Failed to get user1");
+    assert_eq!(user1_details.following_count, 0);
+}
+
+#[tokio::test]
+async fn test_password_reset_flow() {
+    let _lock = lock_test();
+
+    // Create user and log in
+    let username = unique_name("reset");
+    let (old_session, _) = signed_up(&username, "old").await;
+    let mut client = BordClient::new(BASE_URL);
+
+    // Request a reset token (returned directly when BORD_TEST_MODE is on)
+    let forgot = client.forgot_password(&username).await.expect("Failed to request reset");
+    let reset_token = forgot.reset_token.expect("reset_token missing (server started without BORD_TEST_MODE=true?)");
+
+    // Reset the password
+    client.reset_password(&reset_token, "new").await.expect("Failed to reset password");
+
+    // Token is single use
+    let reuse = client.reset_password(&reset_token, "other").await;
+    assert_eq!(error_status(&reuse), Some(400));
+
+    // Old session is revoked
+    let profile = old_session.profile().await;
+    assert_eq!(error_status(&profile), Some(401));
+
+    // New password works
+    client.login(&username, "new").await.expect("Failed to login");
+}
 
+#[tokio::test]
+async fn test_signup_honeypot_blocks_bots() {
+    let _lock = lock_test();
+    let client = reqwest::Client::new();
+
+    // The typed client never fills in the honeypot, so send the raw form
     let response = client
-        .post(&format!("{}/posts", BASE_URL))
-        .header("Authorization", format!("Bearer {}", token))
-        .json(&empty_body)
+        .post(&format!("{}/users", BASE_URL))
+        .json(&json!({
+            "username": unique_name("bot"),
+            "password": "test",
+            "website": "http://spam.example"
+        }))
         .send()
         .await
         .expect("Failed to make request");
 
-    assert_eq!(response.status(), 400);
+    assert_eq!(response.status(), 403);
+}
 
-    // Try content > 5000 chars
-    let long_content = "a".repeat(5001);
-    let long_body = json!({"content": long_content});
+#[tokio::test]
+async fn test_signup_too_fast_asks_for_retry() {
+    let _lock = lock_test();
+    let client = reqwest::Client::new();
 
+    // A form submitted instantly, without browser headers, is borderline: retry, not block
     let response = client
-        .post(&format!("{}/posts", BASE_URL))
-        .header("Authorization", format!("Bearer {}", token))
-        .json(&long_body)
+        .post(&format!("{}/users", BASE_URL))
+        .json(&json!({
+            "username": unique_name("fast"),
+            "password": "test",
+            "form_elapsed_ms": 10
+        }))
         .send()
         .await
         .expect("Failed to make request");
 
     assert_eq!(response.status(), 400);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["code"], "signup_retry_required");
 }
 
 #[tokio::test]
-async fn test_login_invalid_credentials() {
+async fn test_delete_account() {
     let _lock = lock_test();
-    let client = reqwest::Client::new();
-    
-    let login_body = json!({
-        "username": "nonexistent_user",
-        "password": "wrongpass"
-    });
 
-    let response = client
+    let username = unique_name("del");
+    let (mut client, user_id) = signed_up(&username, "test").await;
+
+    // Wrong password is rejected
+    let delete = client.delete_account("wrong").await;
+    assert_eq!(error_status(&delete), Some(401));
+
+    // The failed attempt kept the session; the real one drops it
+    client.delete_account("test").await.expect("Failed to delete account");
+
+    // Account is gone but still resolves as a tombstone
+    let login = client.login(&username, "test").await;
+    assert_eq!(error_status(&login), Some(401));
+
+    let user = client.user(&user_id).await.expect("Failed to get user");
+    assert!(user.deleted);
+
+    // Nobody can take over the deleted account's name
+    let reuse = BordClient::new(BASE_URL).create_user(&username, "test").await;
+    assert_eq!(error_status(&reuse), Some(409));
+}
+
+#[tokio::test]
+async fn test_login_lockout() {
+    let _lock = lock_test();
+
+    let username = unique_name("lockout");
+    let mut client = BordClient::new(BASE_URL);
+    client.create_user(&username, "right").await.expect("Failed to create user");
+
+    // Failures up to the limit are plain 401s
+    for _ in 0..5 {
+        let login = client.login(&username, "wrong").await;
+        assert_eq!(error_status(&login), Some(401));
+    }
+
+    // Now locked, even with the correct password
+    let locked_resp = reqwest::Client::new()
         .post(&format!("{}/login", BASE_URL))
-        .json(&login_body)
+        .json(&json!({ "username": &username, "password": "right" }))
         .send()
         .await
         .expect("Failed to make request");
+    assert_eq!(locked_resp.status(), 423);
+    assert!(locked_resp.headers().get("Retry-After").is_some());
 
-    assert_eq!(response.status(), 401);
+    // A name nobody has locks the same way, so the 423 doesn't reveal accounts
+    let nobody = unique_name("nobody");
+    for _ in 0..5 {
+        let login = client.login(&nobody, "wrong").await;
+        assert_eq!(error_status(&login), Some(401));
+    }
+    let login = client.login(&nobody, "wrong").await;
+    assert_eq!(error_status(&login), Some(423));
 }
 
 #[tokio::test]
-async fn test_create_post_requires_auth() {
-    let _lock = lock_test();
-    let client = reqwest::Client::new();
-    
-    let body = json!({
-        "content": "Test post without auth"
-    });
+async fn test_client_config() {
+    let client = BordClient::new(BASE_URL);
 
-    let response = client
-        .post(&format!("{}/posts", BASE_URL))
-        .json(&body)
+    let config = client.client_config().await.expect("Failed to get client config");
+    assert_eq!(config.posts_per_page, 10);
+    assert_eq!(config.max_post_length, 5000);
+    assert_eq!(config.max_bio_length, 500);
+}
+
+#[tokio::test]
+async fn test_record_impressions() {
+    let client = BordClient::new(BASE_URL);
+
+    let result = client
+        .record_impressions(&[uuid::Uuid::new_v4().to_string(), "not-a-uuid".to_string()])
+        .await
+        .expect("Failed to record impressions");
+    assert_eq!(result.recorded, 0);
+
+    let missing_resp = reqwest::Client::new()
+        .post(&format!("{}/impressions", BASE_URL))
+        .json(&json!({}))
         .send()
         .await
         .expect("Failed to make request");
+    assert_eq!(missing_resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_public_export_disabled_by_default() {
+    let _lock = lock_test();
+    let client = BordClient::new(BASE_URL);
 
-    assert_eq!(response.status(), 401);
+    let export = client.export_public_posts(None).await;
+    assert_eq!(error_status(&export), Some(404));
+
+    // Only admins can switch it on
+    let settings = client
+        .update_settings(&SettingsUpdate {
+            public_export_enabled: Some(true),
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(error_status(&settings), Some(401));
 }
 
 #[tokio::test]
-async fn test_bio_xss_protection() {
+async fn test_instance_branding() {
+    let client = BordClient::new(BASE_URL);
+
+    let instance = client.instance().await.expect("Failed to get instance");
+    assert!(instance.accent_color.starts_with('#'));
+
+    // Branding is injected into the server-rendered index page
+    let index = reqwest::Client::new()
+        .get(&format!("{}/", BASE_URL))
+        .send()
+        .await
+        .expect("Failed to get index")
+        .text()
+        .await
+        .unwrap();
+    assert!(index.contains(&format!("<title>{}</title>", instance.site_name)));
+    assert!(!index.contains("BRAND_"));
+}
+
+#[tokio::test]
+async fn test_friends() {
     let _lock = lock_test();
-    let client = reqwest::Client::new();
-    
-    // Create user first
-    let username = format!("bio_{}", &uuid::Uuid::new_v4().to_string()[0..8]);
-    let create_body = json!({
-        "username": username,
-        "password": "test123"
-    });
-
-    let user_resp = client
-        .post(&format!("{}/users", BASE_URL))
-        .json(&create_body)
+
+    let (alice, alice_id) = signed_up(&unique_name("friend1"), "test").await;
+    let (bob, bob_id) = signed_up(&unique_name("friend2"), "test").await;
+
+    alice.follow(&bob_id).await.expect("Failed to follow");
+
+    // One-way follows show up on user details but don't make friends
+    let bob_seen_by_alice = alice.user(&bob_id).await.expect("Failed to get user");
+    assert_eq!(bob_seen_by_alice.is_following, Some(true));
+    assert_eq!(bob_seen_by_alice.follows_you, Some(false));
+    assert!(alice.friends().await.expect("Failed to get friends").is_empty());
+
+    bob.follow(&alice_id).await.expect("Failed to follow back");
+
+    assert_eq!(alice.friends().await.expect("Failed to get friends"), vec![bob_id.clone()]);
+    assert_eq!(bob.friends().await.expect("Failed to get friends"), vec![alice_id]);
+
+    let friend_requires_auth = BordClient::new(BASE_URL).friends().await;
+    assert_eq!(error_status(&friend_requires_auth), Some(401));
+}
+
+#[tokio::test]
+async fn test_follow_batch() {
+    let _lock = lock_test();
+
+    let (client, user_id) = signed_up(&unique_name("batch"), "test").await;
+    let (_, target1) = signed_up(&unique_name("batch_t1"), "test").await;
+    let (_, target2) = signed_up(&unique_name("batch_t2"), "test").await;
+    let missing = uuid::Uuid::new_v4().to_string();
+
+    let targets = vec![target1.clone(), target2.clone(), missing, "not-a-uuid".to_string(), user_id.clone()];
+    let result = client.follow_batch(&targets).await.expect("Failed to batch follow");
+    let statuses: Vec<&str> = result.results.iter().map(|r| r.status.as_str()).collect();
+    assert_eq!(statuses, vec!["followed", "followed", "not_found", "invalid", "invalid"]);
+
+    let followings = client.followings(&user_id).await.expect("Failed to get followings");
+    assert_eq!(followings, vec![target1.clone(), target2.clone()]);
+
+    client.unfollow_batch(&[target1, target2]).await.expect("Failed to batch unfollow");
+    assert!(client.followings(&user_id).await.expect("Failed to get followings").is_empty());
+
+    // Oversized batches are rejected outright
+    let too_many: Vec<String> = (0..51).map(|_| uuid::Uuid::new_v4().to_string()).collect();
+    assert_eq!(error_status(&client.follow_batch(&too_many).await), Some(400));
+}
+
+#[tokio::test]
+async fn test_export_user_data() {
+    let _lock = lock_test();
+
+    let username = unique_name("gdpr");
+    let (client, user_id) = signed_up(&username, "test").await;
+    let (_, other_id) = signed_up(&unique_name("gdpr_other"), "test").await;
+
+    let post = client.create_post("Post to export").await.expect("Failed to create post");
+    client.follow(&other_id).await.expect("Failed to follow");
+
+    let export = client.export_data().await.expect("Failed to export data");
+    assert_eq!(export.profile.id, user_id);
+    assert_eq!(export.profile.username, username);
+    assert!(export.posts.iter().any(|p| p.id == post.id));
+    assert_eq!(export.followings, vec![other_id]);
+    assert!(!export.sessions.is_empty());
+
+    let anonymous = BordClient::new(BASE_URL).export_data().await;
+    assert_eq!(error_status(&anonymous), Some(401));
+}
+
+#[tokio::test]
+async fn test_profile_page_content_negotiation() {
+    let _lock = lock_test();
+
+    let username = unique_name("profile");
+    let (client, user_id) = signed_up(&username, "test").await;
+    client.create_post("Rendered <b>server side</b>").await.expect("Failed to create post");
+    let http = reqwest::Client::new();
+
+    // The posts are in the page itself, not only loaded by the script
+    let html = http
+        .get(&format!("{}/{}", BASE_URL, username))
+        .send()
+        .await
+        .expect("Failed to get profile page")
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains("Rendered <b>server side</b>"));
+
+    // Browsers get the rendered page, opened on the requested posts page
+    let html = http
+        .get(&format!("{}/{}?page=3", BASE_URL, username))
         .send()
         .await
-        .expect("Failed to create user");
+        .expect("Failed to get profile page")
+        .text()
+        .await
+        .unwrap();
+    assert!(html.contains(&username));
+    assert!(html.contains("loadUserPosts(3)"));
+
+    // API clients get the same user JSON as /users/{id}
+    let json: serde_json::Value = http
+        .get(&format!("{}/{}", BASE_URL, username))
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to get profile JSON")
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(json["id"], user_id.as_str());
+    assert_eq!(json["username"], username.as_str());
+}
+
+#[tokio::test]
+async fn test_weekly_digest_page() {
+    let _lock = lock_test();
+
+    let resp = reqwest::get(&format!("{}/digest/latest", BASE_URL))
+        .await
+        .expect("Failed to get digest");
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers()["Content-Type"].to_str().unwrap().starts_with("text/html"));
+
+    let html = resp.text().await.unwrap();
+    assert!(html.contains("Weekly digest"));
+    assert!(!html.contains("DIGEST_"));
+}
+
+#[tokio::test]
+async fn test_timeline_page() {
+    let _lock = lock_test();
+    let username = unique_name("timeline");
+    let (client, _) = signed_up(&username, "test").await;
+    client.create_post("On the public timeline").await.expect("Failed to create post");
+
+    let resp = reqwest::get(&format!("{}/timeline", BASE_URL))
+        .await
+        .expect("Failed to get timeline");
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers()["Content-Type"].to_str().unwrap().starts_with("text/html"));
+
+    let html = resp.text().await.unwrap();
+    assert!(html.contains("On the public timeline"));
+    assert!(html.contains(&format!("href=\"/{}\"", username)));
+    assert!(html.contains("just now"));
+}
+
+#[tokio::test]
+async fn test_pages_and_errors_follow_accept_language() {
+    let _lock = lock_test();
+    let http = reqwest::Client::new();
+
+    let html = http.get(&format!("{}/timeline", BASE_URL))
+        .header("Accept-Language", "ro-RO,ro;q=0.9,en;q=0.8")
+        .send().await.unwrap().text().await.unwrap();
+    assert!(html.contains("Cele mai noi Bord-uri"));
+
+    let resp = http.get(&format!("{}/api/v1/users/{}", BASE_URL, "00000000-0000-4000-8000-000000000000"))
+        .header("Accept-Language", "ro")
+        .send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+    assert_eq!(resp.headers()["Content-Language"], "ro");
+    let problem: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(problem["code"], "user_not_found");
+    assert_eq!(problem["detail"], "Utilizatorul nu a fost găsit");
+
+    let client = BordClient::new(BASE_URL);
+    let catalog = client.messages("ro").await.expect("Failed to get catalog");
+    assert_eq!(catalog.locale, "ro");
+    assert!(catalog.locales.contains(&"en".to_string()));
+    assert_eq!(catalog.messages["time.just_now"], "chiar acum");
+    assert_eq!(client.messages("xx").await.unwrap().locale, "en");
+}
+
+#[tokio::test]
+async fn test_identicon_avatars() {
+    let _lock = lock_test();
+    let username = unique_name("avatar");
+    let (client, user_id) = signed_up(&username, "test").await;
+
+    let details = client.user(&user_id).await.expect("Failed to get user");
+    assert_eq!(details.avatar_url, format!("/avatars/{}", user_id));
+    let post = client.create_post("Has a face").await.expect("Failed to create post");
+    assert_eq!(post.author_avatar_url, details.avatar_url);
+
+    let http = reqwest::Client::new();
+    let url = format!("{}{}", BASE_URL, details.avatar_url);
+    let resp = http.get(&url).send().await.expect("Failed to get avatar");
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["Content-Type"], "image/svg+xml");
+    let etag = resp.headers()["ETag"].to_str().unwrap().to_string();
+    let svg = resp.text().await.unwrap();
+    assert!(svg.starts_with("<svg"));
+    assert_eq!(http.get(&url).send().await.unwrap().text().await.unwrap(), svg);
+    assert_eq!(http.get(&url).header("If-None-Match", etag).send().await.unwrap().status(), 304);
+
+    let missing = http.get(&format!("{}/avatars/00000000-0000-4000-8000-000000000000", BASE_URL)).send().await.unwrap();
+    assert_eq!(missing.status(), 404);
+}
+
+#[tokio::test]
+async fn test_oembed_and_embed_page() {
+    let _lock = lock_test();
+    let username = unique_name("embedded");
+    let (client, _) = signed_up(&username, "test").await;
+    let post = client.create_post("Embed me").await.expect("Failed to create post");
+    let http = reqwest::Client::new();
+    let permalink = format!("{}/posts/{}", BASE_URL, post.id);
+    let oembed_url = |url: &str| format!("{}/oembed?url={}", BASE_URL, urlencoding::encode(url));
+
+    let resp = http.get(&oembed_url(&permalink)).send().await.expect("Failed to query oEmbed");
+    assert_eq!(resp.status(), 200);
+    let oembed: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(oembed["version"], "1.0");
+    assert_eq!(oembed["type"], "rich");
+    assert_eq!(oembed["author_name"], username);
+    assert!(oembed["html"].as_str().unwrap().contains(&format!("/embed/{}", post.id)));
+
+    let narrow: serde_json::Value = http.get(&format!("{}&maxwidth=300", oembed_url(&permalink)))
+        .send().await.unwrap().json().await.unwrap();
+    assert_eq!(narrow["width"], 300);
+
+    let xml = http.get(&format!("{}&format=xml", oembed_url(&permalink))).send().await.unwrap();
+    assert_eq!(xml.status(), 501);
+    let foreign = http.get(&oembed_url(&format!("https://elsewhere.example/posts/{}", post.id))).send().await.unwrap();
+    assert_eq!(foreign.status(), 404);
+
+    let embed = http.get(&format!("{}/embed/{}", BASE_URL, post.id)).send().await.unwrap();
+    assert_eq!(embed.status(), 200);
+    let html = embed.text().await.unwrap();
+    assert!(html.contains("Embed me") && !html.contains("site-footer"));
+
+    let page = http.get(&permalink).send().await.unwrap().text().await.unwrap();
+    assert!(page.contains("application/json+oembed"));
+}
+
+#[tokio::test]
+async fn test_browsers_get_html_error_pages() {
+    let _lock = lock_test();
+    let http = reqwest::Client::new();
+    let browser_accept = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+    let missing_user = format!("{}/{}", BASE_URL, unique_name("nobody"));
+
+    let page = http.get(&missing_user).header("Accept", browser_accept).send().await.unwrap();
+    assert_eq!(page.status(), 404);
+    assert!(page.headers()["Content-Type"].to_str().unwrap().starts_with("text/html"));
+    let html = page.text().await.unwrap();
+    assert!(html.contains("User not found") && html.contains("<html"));
+
+    let page = http.get(&format!("{}/missing.css", BASE_URL)).header("Accept", browser_accept).send().await.unwrap();
+    assert_eq!(page.status(), 404);
+
+    // API clients, and anything under /api/v1, keep problem documents
+    let json = http.get(&missing_user).send().await.unwrap();
+    assert_eq!(json.status(), 404);
+    assert_eq!(json.headers()["Content-Type"], "application/problem+json");
+    let api = http.get(&format!("{}/api/v1/nope", BASE_URL)).header("Accept", browser_accept).send().await.unwrap();
+    assert_eq!(api.headers()["Content-Type"], "application/problem+json");
+}
+
+#[tokio::test]
+async fn test_post_page_has_share_tags() {
+    let _lock = lock_test();
+    let username = unique_name("shared");
+    let (client, _) = signed_up(&username, "test").await;
+    let post = client.create_post("Worth <b>sharing</b>").await.expect("Failed to create post");
+
+    let resp = reqwest::get(&format!("{}/posts/{}", BASE_URL, post.id))
+        .await
+        .expect("Failed to get post page");
+    assert_eq!(resp.status(), 200);
+    let html = resp.text().await.unwrap();
+    assert!(html.contains(r#"<meta property="og:type" content="article">"#));
+    assert!(html.contains(r#"<meta property="og:description" content="Worth sharing">"#));
+    assert!(html.contains(&format!(r#"<meta property="og:url" content="{}/posts/{}">"#, BASE_URL, post.id)));
+    assert!(html.contains(r#"<meta name="twitter:card" content="summary">"#));
+
+    let html = reqwest::get(&format!("{}/{}", BASE_URL, username)).await.unwrap().text().await.unwrap();
+    assert!(html.contains(r#"<meta property="og:type" content="profile">"#));
+    assert!(html.contains(&format!(r#"<meta property="og:url" content="{}/{}">"#, BASE_URL, username)));
+
+    let missing = reqwest::get(&format!("{}/posts/{}", BASE_URL, "00000000-0000-4000-8000-000000000000")).await.unwrap();
+    assert_eq!(missing.status(), 404);
+}
+
+#[tokio::test]
+async fn test_system_account_is_reserved() {
+    let _lock = lock_test();
+    let client = BordClient::new(BASE_URL);
+
+    // Nobody can sign up as the system account, in any casing
+    let signup = client.create_user("System", "test").await;
+    assert_eq!(error_status(&signup), Some(409));
+
+    // It is managed through the admin API only
+    let upsert = client.upsert_system_account(Some("Announcements")).await;
+    assert_eq!(error_status(&upsert), Some(401));
+    let post = client.create_system_post("Maintenance tonight").await;
+    assert_eq!(error_status(&post), Some(401));
+}
 
-    assert_eq!(user_resp.status(), 201);
-    let _user = user_resp.json::<serde_json::Value>().await.unwrap();
+#[tokio::test]
+async fn test_username_availability() {
+    let _lock = lock_test();
+    let client = BordClient::new(BASE_URL);
+
+    let username = unique_name("avail");
+    let free = client.username_available(&username).await.expect("Failed to check username");
+    assert!(free.available);
+    assert_eq!(free.reason, None);
+
+    signed_up(&username, "test").await;
+    let taken = client.username_available(&username).await.expect("Failed to check username");
+    assert!(!taken.available);
 
-    // Login
-    let login_body = json!({
-        "username": &username,
-        "password": "test123"
-    });
+    let reserved = client.username_available("System").await.expect("Failed to check username");
+    assert!(!reserved.available);
 
-    let login_resp = client
+    let short = client.username_available("ab").await.expect("Failed to check username");
+    assert!(!short.available);
+    assert!(short.reason.is_some());
+}
+
+#[tokio::test]
+async fn test_token_cleanup_requires_admin() {
+    let _lock = lock_test();
+
+    let anonymous = BordClient::new(BASE_URL).cleanup_tokens().await;
+    assert_eq!(error_status(&anonymous), Some(401));
+
+    let (client, _) = signed_up(&unique_name("sweep"), "test").await;
+    assert_eq!(error_status(&client.cleanup_tokens().await), Some(403));
+    assert_eq!(error_status(&client.compact_feed().await), Some(403));
+    assert_eq!(error_status(&client.sweep_expired().await), Some(403));
+    assert_eq!(error_status(&client.retry_webhooks().await), Some(403));
+}
+
+#[tokio::test]
+async fn test_concurrent_posts_all_reach_feed() {
+    let _lock = lock_test();
+
+    let mut writers = Vec::new();
+    for _ in 0..5 {
+        let username = unique_name("burst");
+        let (client, _) = signed_up(&username, "test").await;
+        writers.push(tokio::spawn(async move {
+            let post = client.create_post("burst post").await.expect("Failed to create post");
+            (username, post.id)
+        }));
+    }
+
+    let reader = BordClient::new(BASE_URL);
+    for writer in writers {
+        let (username, post_id) = writer.await.unwrap();
+        let posts = reader
+            .list_posts(&PostQuery { user: Some(username), ..Default::default() })
+            .await
+            .expect("Failed to list posts");
+        assert!(posts.iter().any(|p| p.id == post_id), "post {} missing from feed", post_id);
+    }
+}
+
+#[tokio::test]
+async fn test_cookie_session_requires_csrf() {
+    let _lock = lock_test();
+
+    let username = unique_name("cookie");
+    BordClient::new(BASE_URL).create_user(&username, "test").await.expect("Failed to create user");
+    let http = reqwest::Client::new();
+
+    let login_resp = http
         .post(&format!("{}/login", BASE_URL))
-        .json(&login_body)
+        .json(&json!({ "username": &username, "password": "test", "cookie": true }))
         .send()
         .await
         .expect("Failed to login");
-
     assert_eq!(login_resp.status(), 200);
-    let token_data = login_resp.json::<serde_json::Value>().await.unwrap();
-    let token = token_data["token"].as_str().unwrap().to_string();
+    let set_cookie = login_resp.headers()["Set-Cookie"].to_str().unwrap().to_string();
+    assert!(set_cookie.contains("HttpOnly"));
+    let cookie = set_cookie.split(';').next().unwrap().to_string();
+    let login: serde_json::Value = login_resp.json().await.unwrap();
+    let csrf = login["csrf_token"].as_str().expect("csrf_token missing").to_string();
 
-    // Update profile with XSS payload in bio
-    let xss_payload = "<img src=x onerror='alert(\"xss\")'>";
-    let update_body = json!({
-        "bio": xss_payload
-    });
-
-    let update_resp = client
-        .put(&format!("{}/profile", BASE_URL))
-        .header("Authorization", format!("Bearer {}", token))
-        .json(&update_body)
+    // Reads work with the cookie alone
+    let profile_resp = http
+        .get(&format!("{}/profile", BASE_URL))
+        .header("Cookie", &cookie)
         .send()
         .await
-        .expect("Failed to update profile");
+        .expect("Failed to get profile");
+    assert_eq!(profile_resp.status(), 200);
 
-    assert_eq!(update_resp.status(), 200);
-    let updated_user = update_resp.json::<serde_json::Value>().await.unwrap();
-    let stored_bio = updated_user["bio"].as_str().unwrap_or("");
-    
-    // Bio should not contain img tags or onerror attributes
-    assert!(!stored_bio.contains("<img"));
-    assert!(!stored_bio.contains("onerror"));
-    assert!(!stored_bio.contains("alert"));
+    // Writes need the CSRF token as well
+    let forged = http
+        .post(&format!("{}/posts", BASE_URL))
+        .header("Cookie", &cookie)
+        .json(&json!({ "content": "Forged post" }))
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(forged.status(), 401);
+
+    let posted = http
+        .post(&format!("{}/posts", BASE_URL))
+        .header("Cookie", &cookie)
+        .header("X-CSRF-Token", &csrf)
+        .json(&json!({ "content": "Cookie post" }))
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(posted.status(), 201);
 }
 
 #[tokio::test]
-async fn test_follow_unfollow_user() {
+async fn test_profile_timezone() {
     let _lock = lock_test();
-    let client = reqwest::Client::new();
-    
-    // Create first user
-    let username1 = format!("follow1_{}", &uuid::Uuid::new_v4().to_string()[0..8]);
-    let user1_body = json!({
-        "username": username1,
-        "password": "test"
-    });
-    
-    let user1_resp = client
-        .post(&format!("{}/users", BASE_URL))
-        .json(&user1_body)
+    let (mut client, _) = signed_up(&unique_name("tz"), "test").await;
+
+    let updated = client
+        .update_profile(&ProfileUpdate {
+            timezone: Some("Europe/Bucharest".to_string()),
+            ..Default::default()
+        })
+        .await
+        .expect("Failed to set time zone");
+    assert_eq!(updated.timezone.as_deref(), Some("Europe/Bucharest"));
+
+    let invalid = client
+        .update_profile(&ProfileUpdate {
+            timezone: Some("Mars/Olympus_Mons".to_string()),
+            ..Default::default()
+        })
+        .await;
+    assert_eq!(error_status(&invalid), Some(400));
+
+    // Timestamps in the API stay in UTC regardless
+    let post = client.create_post("Time zone test").await.expect("Failed to create post");
+    assert!(post.created_at.ends_with("+00:00") || post.created_at.ends_with('Z'));
+}
+
+#[tokio::test]
+async fn test_session_metadata() {
+    let _lock = lock_test();
+    let (client, _) = signed_up(&unique_name("sessions"), "test").await;
+
+    let sessions = client.sessions().await.expect("Failed to list sessions");
+    let current = sessions.iter().find(|s| s.current).expect("Current session missing");
+    assert!(current.last_used_at.is_some());
+    assert!(current.ip.is_some());
+    assert_eq!(current.ip, current.last_ip);
+}
+
+#[tokio::test]
+async fn test_malformed_path_ids_rejected() {
+    let http = reqwest::Client::new();
+
+    for path in ["/users/not-a-uuid", "/followers/123", "/followings/abc"] {
+        let resp = http
+            .get(&format!("{}{}", BASE_URL, path))
+            .send()
+            .await
+            .expect("Failed to make request");
+        assert_eq!(resp.status(), 400, "{} should be rejected", path);
+    }
+
+    let resp = http
+        .delete(&format!("{}/posts/not-a-uuid", BASE_URL))
         .send()
         .await
-        .expect("Failed to create user1");
-    
-    assert_eq!(user1_resp.status(), 201);
-    let user1 = user1_resp.json::<serde_json::Value>().await.unwrap();
-    let user1_id = user1["id"].as_str().unwrap().to_string();
-    
-    // Create second user
-    let username2 = format!("follow2_{}", &uuid::Uuid::new_v4().to_string()[0..8]);
-    let user2_body = json!({
-        "username": username2,
-        "password": "test"
-    });
-    
-    let user2_resp = client
-        .post(&format!("{}/users", BASE_URL))
-        .json(&user2_body)
+        .expect("Failed to make request");
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_rejects_bad_bodies() {
+    let http = reqwest::Client::new();
+
+    let not_json = http
+        .post(&format!("{}/login", BASE_URL))
+        .header("Content-Type", "text/plain")
+        .body("username=alice&password=alice")
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(not_json.status(), 415);
+
+    let malformed = http
+        .post(&format!("{}/login", BASE_URL))
+        .header("Content-Type", "application/json")
+        .body("{\"username\": ")
         .send()
         .await
-        .expect("Failed to create user2");
-    
-    assert_eq!(user2_resp.status(), 201);
-    let user2 = user2_resp.json::<serde_json::Value>().await.unwrap();
-    let user2_id = user2["id"].as_str().unwrap().to_string();
-    
-    // Login as user1
-    let login_body = json!({
-        "username": &username1,
-        "password": "test"
-    });
-    
-    let login_resp = client
+        .expect("Failed to make request");
+    assert_eq!(malformed.status(), 400);
+
+    let oversized = http
+        .post(&format!("{}/posts", BASE_URL))
+        .json(&json!({ "content": "a".repeat(100 * 1024) }))
+        .send()
+        .await
+        .expect("Failed to make request");
+    assert_eq!(oversized.status(), 413);
+
+    let nested = format!("{}{}", "[".repeat(100), "]".repeat(100));
+    let too_deep = http
         .post(&format!("{}/login", BASE_URL))
-        .json(&login_body)
+        .header("Content-Type", "application/json")
+        .body(nested)
         .send()
         .await
-        .expect("Failed to login");
-    
-    assert_eq!(login_resp.status(), 200);
-    let token_data = login_resp.json::<serde_json::Value>().await.unwrap();
-    let token = token_data["token"].as_str().unwrap().to_string();
-    
-    // User1 follows user2
-    let follow_body = json!({
-        "target_user_id": user2_id
-    });
-    
-    let follow_resp = client
-        .post(&format!("{}/follow", BASE_URL))
-        .header("Authorization", format!("Bearer {}", token))
-        .json(&follow_body)
+        .expect("Failed to make request");
+    assert_eq!(too_deep.status(), 400);
+    let problem: serde_json::Value = too_deep.json().await.unwrap();
+    assert_eq!(problem["code"], "json_too_deep");
+}
+
+#[tokio::test]
+async fn test_validation_lists_every_field() {
+    let http = reqwest::Client::new();
+
+    let response = http
+        .post(&format!("{}/password/reset", BASE_URL))
+        .json(&json!({ "token": "not-a-token", "new_password": "" }))
         .send()
         .await
-        .expect("Failed to follow user");
-    
-    assert_eq!(follow_resp.status(), 200);
-    let follow_result = follow_resp.json::<serde_json::Value>().await.unwrap();
-    assert_eq!(follow_result["status"], "followed");
-    
-    // Check user1's followings list
-    let followings_resp = client
-        .get(&format!("{}/followings/{}", BASE_URL, user1_id))
+        .expect("Failed to make request");
+    assert_eq!(response.status(), 400);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["code"], "validation_failed");
+    assert_eq!(problem["errors"][0]["field"], "token");
+    assert_eq!(problem["errors"][1]["code"], "password_required");
+
+    // Wrongly typed fields can't be listed one by one, but are still a 400
+    let wrong_type = http
+        .post(&format!("{}/password/reset", BASE_URL))
+        .json(&json!({ "token": 42 }))
         .send()
         .await
-        .expect("Failed to get followings");
-    
-    assert_eq!(followings_resp.status(), 200);
-    let followings = followings_resp.json::<Vec<String>>().await.unwrap();
-    assert!(followings.contains(&user2_id), "user2_id should be in user1's followings");
-    
-    // User1 unfollows user2
-    let unfollow_body = json!({
-        "target_user_id": user2_id
-    });
-    
-    let unfollow_resp = client
-        .post(&format!("{}/unfollow", BASE_URL))
-        .header("Authorization", format!("Bearer {}", token))
-        .json(&unfollow_body)
+        .expect("Failed to make request");
+    assert_eq!(wrong_type.status(), 400);
+}
+
+#[tokio::test]
+async fn test_versioned_api_and_legacy_aliases() {
+    let _lock = lock_test();
+    let http = reqwest::Client::new();
+
+    // The typed client talks to /api/v1; the old paths still answer the same
+    for path in ["/api/v1/instance", "/instance"] {
+        let resp = http.get(&format!("{}{}", BASE_URL, path)).send().await.expect("Failed to get instance");
+        assert_eq!(resp.status(), 200, "{}", path);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert!(body["site_name"].is_string(), "{}", path);
+    }
+
+    // Unknown API paths are JSON 404s rather than profile pages
+    let missing = http.get(&format!("{}/api/v1/nope", BASE_URL)).send().await.unwrap();
+    assert_eq!(missing.status(), 404);
+
+    // Pages aren't versioned
+    let page = http.get(&format!("{}/api/v1/digest/latest", BASE_URL)).send().await.unwrap();
+    assert_eq!(page.status(), 404);
+}
+
+#[tokio::test]
+async fn test_etag_not_modified() {
+    let _lock = lock_test();
+    let (_, user_id) = signed_up(&unique_name("etag"), "test").await;
+    let http = reqwest::Client::new();
+
+    for path in ["/api/v1/posts?all=true".to_string(), format!("/api/v1/users/{}", user_id)] {
+        let url = format!("{}{}", BASE_URL, path);
+        let first = http.get(&url).send().await.expect("Failed to fetch");
+        assert_eq!(first.status(), 200, "{}", path);
+        let etag = first.headers()["ETag"].to_str().unwrap().to_string();
+        assert!(etag.starts_with("W/\""), "{}", path);
+
+        let again = http.get(&url).header("If-None-Match", &etag).send().await.unwrap();
+        assert_eq!(again.status(), 304, "{}", path);
+        assert!(again.bytes().await.unwrap().is_empty());
+
+        let stale = http.get(&url).header("If-None-Match", "W/\"stale\"").send().await.unwrap();
+        assert_eq!(stale.status(), 200, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn test_webhooks_require_admin() {
+    let _lock = lock_test();
+
+    let anonymous = BordClient::new(BASE_URL)
+        .create_webhook("https://hooks.example.com/bord", &["post.created"])
+        .await;
+    assert_eq!(error_status(&anonymous), Some(401));
+
+    let (client, _) = signed_up(&unique_name("hook"), "test").await;
+    let created = client.create_webhook("https://hooks.example.com/bord", &["post.created"]).await;
+    assert_eq!(error_status(&created), Some(403));
+    assert_eq!(error_status(&client.webhooks().await), Some(403));
+}
+
+#[tokio::test]
+async fn test_held_posts_wait_for_review() {
+    let _lock = lock_test();
+
+    let (client, user_id) = signed_up(&unique_name("held"), "test").await;
+
+    // The moderation filter forwards the post marked for holding
+    let held = reqwest::Client::new()
+        .post(&format!("{}/api/v1/posts", BASE_URL))
+        .bearer_auth(client.token().unwrap())
+        .header("X-Moderation-Secret", MODERATION_FILTER_SECRET)
+        .header("X-Moderation-Action", "hold")
+        .header("X-Moderation-Reason", "possible spam")
+        .json(&json!({"content": "Held for review"}))
         .send()
         .await
-        .expect("Failed to unfollow user");
-    
-    assert_eq!(unfollow_resp.status(), 200);
-    let unfollow_result = unfollow_resp.json::<serde_json::Value>().await.unwrap();
-    assert_eq!(unfollow_result["status"], "unfollowed");
-    
-    // Check user1's followings list is now empty
-    let followings_resp = client
-        .get(&format!("{}/followings/{}", BASE_URL, user1_id))
+        .expect("Failed to create post");
+    assert_eq!(held.status(), 202);
+
+    // Without the filter's secret the hold header is ignored and the post goes out
+    let forged = reqwest::Client::new()
+        .post(&format!("{}/api/v1/posts", BASE_URL))
+        .bearer_auth(client.token().unwrap())
+        .header("X-Moderation-Action", "hold")
+        .json(&json!({"content": "Not actually held"}))
         .send()
         .await
-        .expect("Failed to get followings after unfollow");
-    
-    assert_eq!(followings_resp.status(), 200);
-    let followings = followings_resp.json::<Vec<String>>().await.unwrap();
-    assert!(!followings.contains(&user2_id), "user2_id should not be in user1's followings after unfollow");
-    assert!(followings.is_empty(), "user1's followings should be empty");
-}
\ No newline at end of file
+        .expect("Failed to create post");
+    assert_eq!(forged.status(), 201);
+
+    // It isn't published, but its author can see it waiting
+    let own = client.list_posts(&PostQuery::default()).await.expect("Failed to list posts");
+    assert!(own.iter().all(|p| p.content != "Held for review"));
+
+    let waiting = client.held_posts().await.expect("Failed to list held posts");
+    assert_eq!(waiting.len(), 1);
+    assert_eq!(waiting[0].user_id, user_id);
+    assert_eq!(waiting[0].status, "pending");
+    assert_eq!(waiting[0].reason.as_deref(), Some("possible spam"));
+
+    // Only moderators review the queue
+    assert_eq!(error_status(&client.moderation_queue().await), Some(403));
+    assert_eq!(error_status(&client.approve_held_post(&waiting[0].id).await), Some(403));
+    assert_eq!(error_status(&client.reject_held_post(&waiting[0].id, None).await), Some(403));
+}
+
+#[tokio::test]
+async fn test_filter_scores_stay_private() {
+    let _lock = lock_test();
+
+    let (client, _) = signed_up(&unique_name("scored"), "test").await;
+    let created: serde_json::Value = reqwest::Client::new()
+        .post(&format!("{}/api/v1/posts", BASE_URL))
+        .bearer_auth(client.token().unwrap())
+        .header("X-Moderation-Secret", MODERATION_FILTER_SECRET)
+        .header("X-Moderation-Sentiment", "-0.4")
+        .header("X-Moderation-Categories", "spam")
+        .header("X-Moderation-Source", "llm")
+        .json(&json!({"content": "Scored by the filter"}))
+        .send()
+        .await
+        .expect("Failed to create post")
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(created["content"], "Scored by the filter");
+    assert!(created.get("moderation").is_none());
+
+    let own = client.list_posts(&PostQuery::default()).await.expect("Failed to list posts");
+    let post = own.iter().find(|p| p.id == created["id"]).expect("Post should be published");
+    assert!(post.moderation.is_none());
+}
+
+#[tokio::test]
+async fn test_static_assets_revalidate() {
+    let _lock = lock_test();
+    let http = reqwest::Client::new();
+    let manifest: serde_json::Value = http.get(&format!("{}/asset-manifest.json", BASE_URL))
+        .send().await.unwrap().json().await.unwrap();
+    let url = format!("{}{}", BASE_URL, manifest["style.css"].as_str().unwrap());
+
+    let resp = http.get(&url).send().await.expect("Failed to get asset");
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers()["Cache-Control"].to_str().unwrap().contains("immutable"));
+    let etag = resp.headers()["ETag"].to_str().unwrap().to_string();
+    let modified = resp.headers()["Last-Modified"].to_str().unwrap().to_string();
+
+    let resp = http.get(&url).header("If-None-Match", &etag).send().await.unwrap();
+    assert_eq!(resp.status(), 304);
+    assert!(resp.bytes().await.unwrap().is_empty());
+    let resp = http.get(&url).header("If-Modified-Since", &modified).send().await.unwrap();
+    assert_eq!(resp.status(), 304);
+    let resp = http.get(&url).header("If-None-Match", "\"other\"").send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let plain = http.get(&format!("{}/style.css", BASE_URL)).send().await.unwrap();
+    assert_eq!(plain.headers()["Cache-Control"], "no-cache");
+    assert_eq!(plain.headers()["ETag"].to_str().unwrap(), etag);
+}
+
+#[tokio::test]
+async fn test_static_assets_are_precompressed() {
+    let _lock = lock_test();
+    let http = reqwest::Client::new();
+    let url = format!("{}/api.js", BASE_URL);
+
+    let identity = http.get(&url).send().await.unwrap();
+    assert!(identity.headers().get("Content-Encoding").is_none());
+    assert!(identity.headers()["Vary"].to_str().unwrap().contains("Accept-Encoding"));
+    let identity_etag = identity.headers()["ETag"].to_str().unwrap().to_string();
+    let identity_len = identity.bytes().await.unwrap().len();
+
+    for (accept, coding) in [("gzip, br", "br"), ("gzip", "gzip"), ("br;q=0, gzip", "gzip")] {
+        let resp = http.get(&url).header("Accept-Encoding", accept).send().await.unwrap();
+        assert_eq!(resp.headers()["Content-Encoding"], coding);
+        assert_ne!(resp.headers()["ETag"].to_str().unwrap(), identity_etag);
+        assert!(resp.bytes().await.unwrap().len() < identity_len);
+    }
+
+    let png = http.get(&format!("{}/B.png", BASE_URL)).header("Accept-Encoding", "br").send().await.unwrap();
+    assert!(png.headers().get("Content-Encoding").is_none());
+}
+
+#[tokio::test]
+async fn test_webfinger_and_nodeinfo() {
+    let _lock = lock_test();
+    let username = unique_name("finger");
+    let (_, user_id) = signed_up(&username, "test").await;
+    let http = reqwest::Client::new();
+
+    let url = format!("{}/.well-known/webfinger?resource=acct:{}@127.0.0.1:3000", BASE_URL, username);
+    let resp = http.get(&url).send().await.expect("Failed to query webfinger");
+    assert_eq!(resp.status(), 200);
+    let jrd: serde_json::Value = resp.json().await.unwrap();
+    assert_eq!(jrd["subject"], format!("acct:{}@127.0.0.1:3000", username));
+    let links = jrd["links"].as_array().unwrap();
+    assert!(links.iter().any(|l| l["href"].as_str().unwrap().ends_with(&user_id)));
+
+    let other_host = format!("{}/.well-known/webfinger?resource=acct:{}@elsewhere.example", BASE_URL, username);
+    assert_eq!(http.get(&other_host).send().await.unwrap().status(), 404);
+
+    let links: serde_json::Value = http.get(&format!("{}/.well-known/nodeinfo", BASE_URL))
+        .send().await.unwrap().json().await.unwrap();
+    let href = links["links"][0]["href"].as_str().unwrap();
+    assert!(href.ends_with("/nodeinfo/2.0"));
+
+    let nodeinfo: serde_json::Value = http.get(&format!("{}/nodeinfo/2.0", BASE_URL))
+        .send().await.unwrap().json().await.unwrap();
+    assert_eq!(nodeinfo["software"]["name"], "bord");
+    assert!(nodeinfo["usage"]["users"]["total"].as_u64().unwrap() >= 1);
+    assert!(nodeinfo["usage"]["localPosts"].is_u64());
+}
+
+#[tokio::test]
+async fn test_idempotency_key_replays_post() {
+    let _lock = lock_test();
+    let (client, _) = signed_up(&unique_name("idem"), "test").await;
+    let token = client.token().unwrap().to_string();
+    let http = reqwest::Client::new();
+    let key = uuid::Uuid::new_v4().to_string();
+
+    let send = |content: &'static str| {
+        http.post(&format!("{}/api/v1/posts", BASE_URL))
+            .bearer_auth(&token)
+            .header("Idempotency-Key", &key)
+            .json(&json!({ "content": content }))
+            .send()
+    };
+
+    let first = send("Posted once despite retries").await.expect("Failed to create post");
+    assert!(first.status().is_success());
+    let first: serde_json::Value = first.json().await.unwrap();
+
+    let retry = send("Posted once despite retries").await.unwrap();
+    assert!(retry.status().is_success());
+    assert_eq!(retry.headers()["Idempotent-Replayed"], "true");
+    let retry: serde_json::Value = retry.json().await.unwrap();
+    assert_eq!(retry["id"], first["id"]);
+
+    // The same key can't be reused for a different post
+    let other = send("Something else").await.unwrap();
+    assert_eq!(other.status(), 409);
+
+    let posts = client.list_posts(&PostQuery::default()).await.expect("Failed to list posts");
+    assert_eq!(posts.iter().filter(|p| p.content == "Posted once despite retries").count(), 1);
+}
@@ -1,5 +1,8 @@
-use serde_json::json;
-use std::time::Instant;
+use bord::client::{error_status, BordClient, PostQuery};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 const BASE_URL: &str = "http://127.0.0.1:3000";
 const NUM_USERS: usize = 100;
@@ -11,7 +14,7 @@ const LOADED_USER_POSTS: usize = 50;
 #[ignore] 
 #[tokio::test(flavor = "multi_thread")]
 async fn perf_test_users_with_posts() {
-    let client = reqwest::Client::new();
+    let client = BordClient::new(BASE_URL);
     let start = Instant::now();
 
     println!("\n=== Performance Test ===");
@@ -25,23 +28,8 @@ async fn perf_test_users_with_posts() {
         let username = format!("perf_user_{}_{}", i, uuid::Uuid::new_v4().to_string()[0..8].to_string());
         let password = "password123";
 
-        let create_resp = client
-            .post(&format!("{}/users", BASE_URL))
-            .json(&json!({
-                "username": username,
-                "password": password
-            }))
-            .send()
-            .await;
-
-        if let Ok(resp) = create_resp {
-            if resp.status() == 201 {
-                if let Ok(user) = resp.json::<serde_json::Value>().await {
-                    if let Some(user_id) = user["id"].as_str() {
-                        user_credentials.push((user_id.to_string(), username, password.to_string()));
-                    }
-                }
-            }
+        if let Ok(user) = client.create_user(&username, password).await {
+            user_credentials.push((user.id, username, password.to_string()));
         }
 
         if (i + 1) % 100 == 0 {
@@ -63,47 +51,21 @@ async fn perf_test_users_with_posts() {
     let mut posts_failed = 0;
 
     for (idx, (_, username, password)) in user_credentials.iter().enumerate() {
-        // Login
-        let login_resp = client
-            .post(&format!("{}/login", BASE_URL))
-            .json(&json!({
-                "username": username,
-                "password": password
-            }))
-            .send()
-            .await;
-
-        if let Ok(resp) = login_resp {
-            if resp.status() == 200 {
-                if let Ok(token_data) = resp.json::<serde_json::Value>().await {
-                    if let Some(token) = token_data["token"].as_str() {
-                        // Create posts for this user
-                        for post_num in 0..POSTS_PER_USER {
-                            let content = format!(
-                                "Post {} from user {} - Perf test at {}",
-                                post_num + 1,
-                                idx,
-                                chrono::Utc::now().to_rfc3339()
-                            );
-
-                            let post_resp = client
-                                .post(&format!("{}/posts", BASE_URL))
-                                .header("Authorization", format!("Bearer {}", token))
-                                .json(&json!({ "content": content }))
-                                .send()
-                                .await;
-
-                            if let Ok(resp) = post_resp {
-                                if resp.status() == 201 {
-                                    posts_created += 1;
-                                } else {
-                                    posts_failed += 1;
-                                }
-                            } else {
-                                posts_failed += 1;
-                            }
-                        }
-                    }
+        // Login with a per-user client so each keeps its own token
+        let mut user_client = BordClient::new(BASE_URL);
+        if user_client.login(username, password).await.is_ok() {
+            // Create posts for this user
+            for post_num in 0..POSTS_PER_USER {
+                let content = format!(
+                    "Post {} from user {} - Perf test at {}",
+                    post_num + 1,
+                    idx,
+                    chrono::Utc::now().to_rfc3339()
+                );
+
+                match user_client.create_post(&content).await {
+                    Ok(_) => posts_created += 1,
+                    Err(_) => posts_failed += 1,
                 }
             }
         }
@@ -143,87 +105,34 @@ async fn perf_test_users_with_posts() {
 #[ignore]
 #[tokio::test(flavor = "multi_thread")]
 async fn perf_test_load_user_with_many_posts() {
-    let client = reqwest::Client::new();
+    let mut client = BordClient::new(BASE_URL);
     let start = Instant::now();
 
     println!("\n=== Load User Performance Test ===");
 
     let mut user_id = None;
-    let mut token = None;
 
     // Try to login first
-    let login_resp = client
-        .post(&format!("{}/login", BASE_URL))
-        .json(&json!({
-            "username": LOADED_USER_NAME,
-            "password": LOADED_USER_PASSWORD
-        }))
-        .send()
-        .await;
-
-    if let Ok(resp) = login_resp {
-        if resp.status() == 200 {
-            if let Ok(token_data) = resp.json::<serde_json::Value>().await {
-                if let Some(t) = token_data["token"].as_str() {
-                    token = Some(t.to_string());
-                    println!("User '{}' already exists, skipping creation", LOADED_USER_NAME);
-                }
-            }
-        }
-    }
-
-    // If login failed, try to create user
-    if token.is_none() {
+    if client.login(LOADED_USER_NAME, LOADED_USER_PASSWORD).await.is_ok() {
+        println!("User '{}' already exists, skipping creation", LOADED_USER_NAME);
+    } else {
+        // If login failed, try to create user
         println!(
             "Creating user '{}' with {} posts...",
             LOADED_USER_NAME, LOADED_USER_POSTS
         );
 
-        let create_resp = client
-            .post(&format!("{}/users", BASE_URL))
-            .json(&json!({
-                "username": LOADED_USER_NAME,
-                "password": LOADED_USER_PASSWORD
-            }))
-            .send()
-            .await;
-
-        if let Ok(resp) = create_resp {
-            if resp.status() == 201 {
-                if let Ok(user) = resp.json::<serde_json::Value>().await {
-                    user_id = user["id"].as_str().map(|s| s.to_string());
-                }
-            }
+        if let Ok(user) = client.create_user(LOADED_USER_NAME, LOADED_USER_PASSWORD).await {
+            user_id = Some(user.id);
         }
 
         // Try to login again
-        let login_resp = client
-            .post(&format!("{}/login", BASE_URL))
-            .json(&json!({
-                "username": LOADED_USER_NAME,
-                "password": LOADED_USER_PASSWORD
-            }))
-            .send()
-            .await;
-
-        if let Ok(resp) = login_resp {
-            if resp.status() == 200 {
-                if let Ok(token_data) = resp.json::<serde_json::Value>().await {
-                    if let Some(t) = token_data["token"].as_str() {
-                        token = Some(t.to_string());
-                    }
-                }
-            }
-        }
-
-        if token.is_none() {
+        if client.login(LOADED_USER_NAME, LOADED_USER_PASSWORD).await.is_err() {
             println!("Failed to login or create user");
             return;
         }
     }
 
-    let token = token.unwrap();
-
     // Create posts
     let post_creation_start = Instant::now();
     let mut posts_created = 0;
@@ -236,21 +145,9 @@ async fn perf_test_load_user_with_many_posts() {
             chrono::Utc::now().to_rfc3339()
         );
 
-        let post_resp = client
-            .post(&format!("{}/posts", BASE_URL))
-            .header("Authorization", format!("Bearer {}", token))
-            .json(&json!({ "content": content }))
-            .send()
-            .await;
-
-        if let Ok(resp) = post_resp {
-            if resp.status() == 201 {
-                posts_created += 1;
-            } else {
-                posts_failed += 1;
-            }
-        } else {
-            posts_failed += 1;
+        match client.create_post(&content).await {
+            Ok(_) => posts_created += 1,
+            Err(_) => posts_failed += 1,
         }
 
         if (post_num + 1) % 10 == 0 {
@@ -261,10 +158,7 @@ async fn perf_test_load_user_with_many_posts() {
 
     // Fetch user to load posts
     let fetch_start = Instant::now();
-    let fetch_resp = client
-        .get(&format!("{}/users/{}", BASE_URL, user_id.unwrap_or_default()))
-        .send()
-        .await;
+    let fetch_resp = client.user(&user_id.unwrap_or_default()).await;
     let fetch_time = fetch_start.elapsed();
 
     let total_time = start.elapsed();
@@ -275,7 +169,191 @@ async fn perf_test_load_user_with_many_posts() {
     println!("Posts created: {}", posts_created);
     println!("Posts failed: {}", posts_failed);
     println!("User fetch time: {:.2}ms", fetch_time.as_millis());
-    if let Ok(resp) = fetch_resp {
-        println!("User fetch status: {}", resp.status());
+    match fetch_resp {
+        Ok(_) => println!("User fetch status: 200"),
+        Err(e) => println!("User fetch status: {}", e.status().unwrap_or_default()),
+    }
+}
+
+/// Soak settings, overridable through `SOAK_*` environment variables, e.g.
+/// `SOAK_SECONDS=600 SOAK_WORKERS=32 cargo test --test perf perf_soak -- --ignored --nocapture`
+struct SoakConfig {
+    duration: Duration,
+    workers: usize,
+    // Relative weights of each kind of operation
+    read_weight: u32,
+    write_weight: u32,
+    follow_weight: u32,
+    // Percentage of operations replaced by a deliberately invalid request
+    invalid_pct: u32,
+    max_think_ms: u64,
+    // SLOs the run must meet
+    max_error_rate: f64,
+    max_p95_ms: u128,
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+impl SoakConfig {
+    fn from_env() -> Self {
+        SoakConfig {
+            duration: Duration::from_secs(env_or("SOAK_SECONDS", 60)),
+            workers: env_or("SOAK_WORKERS", 8),
+            read_weight: env_or("SOAK_READ_WEIGHT", 70),
+            write_weight: env_or("SOAK_WRITE_WEIGHT", 20),
+            follow_weight: env_or("SOAK_FOLLOW_WEIGHT", 10),
+            invalid_pct: env_or("SOAK_INVALID_PCT", 5),
+            max_think_ms: env_or("SOAK_MAX_THINK_MS", 50),
+            max_error_rate: env_or("SOAK_MAX_ERROR_RATE", 0.01),
+            max_p95_ms: env_or("SOAK_MAX_P95_MS", 500),
+        }
+    }
+}
+
+#[derive(Default)]
+struct SoakStats {
+    ok: usize,
+    errors: usize,
+    latencies_ms: Vec<u128>,
+}
+
+impl SoakStats {
+    fn record(&mut self, started: Instant, ok: bool) {
+        self.latencies_ms.push(started.elapsed().as_millis());
+        if ok {
+            self.ok += 1;
+        } else {
+            self.errors += 1;
+        }
+    }
+
+    fn merge(&mut self, other: SoakStats) {
+        self.ok += other.ok;
+        self.errors += other.errors;
+        self.latencies_ms.extend(other.latencies_ms);
+    }
+}
+
+fn percentile(sorted: &[u128], pct: f64) -> u128 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let idx = ((sorted.len() - 1) as f64 * pct / 100.0).round() as usize;
+    sorted[idx]
+}
+
+/// One worker: a logged-in user issuing a random mix of operations until the deadline.
+/// Invalid requests count as successes only when the server rejects them with the expected 4xx.
+async fn soak_worker(config: &SoakConfig, client: BordClient, user_ids: Vec<String>, deadline: Instant) -> SoakStats {
+    let mut rng = StdRng::from_entropy();
+    let mut stats = SoakStats::default();
+    let total_weight = (config.read_weight + config.write_weight + config.follow_weight).max(1);
+
+    while Instant::now() < deadline {
+        let started = Instant::now();
+
+        if rng.gen_range(0..100) < config.invalid_pct {
+            let ok = match rng.gen_range(0..3) {
+                0 => error_status(&client.create_post("").await) == Some(400),
+                1 => error_status(&client.follow("not-a-uuid").await) == Some(400),
+                _ => error_status(&client.user(&uuid::Uuid::new_v4().to_string()).await) == Some(404),
+            };
+            stats.record(started, ok);
+        } else {
+            let pick = rng.gen_range(0..total_weight);
+            let ok = if pick < config.read_weight {
+                match rng.gen_range(0..3) {
+                    0 => client.list_posts(&PostQuery { all: true, ..Default::default() }).await.is_ok(),
+                    1 => client.feed(1).await.is_ok(),
+                    _ => client.user(&user_ids[rng.gen_range(0..user_ids.len())]).await.is_ok(),
+                }
+            } else if pick < config.read_weight + config.write_weight {
+                let content = format!("Soak post at {}", chrono::Utc::now().to_rfc3339());
+                client.create_post(&content).await.is_ok()
+            } else {
+                let target = &user_ids[rng.gen_range(0..user_ids.len())];
+                if rng.gen_bool(0.5) {
+                    // Following yourself is rejected; that's an expected 400, not an error
+                    let result = client.follow(target).await;
+                    result.is_ok() || error_status(&result) == Some(400)
+                } else {
+                    client.unfollow(target).await.is_ok()
+                }
+            };
+            stats.record(started, ok);
+        }
+
+        if config.max_think_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(rng.gen_range(0..=config.max_think_ms))).await;
+        }
     }
+
+    stats
+}
+
+#[ignore]
+#[tokio::test(flavor = "multi_thread")]
+async fn perf_soak_mixed_workload() {
+    let config = std::sync::Arc::new(SoakConfig::from_env());
+
+    println!("\n=== Soak Test ===");
+    println!(
+        "{} workers for {}s (read/write/follow = {}/{}/{}, {}% invalid)",
+        config.workers,
+        config.duration.as_secs(),
+        config.read_weight,
+        config.write_weight,
+        config.follow_weight,
+        config.invalid_pct
+    );
+
+    // One user per worker; they follow each other at random during the run
+    let mut clients = Vec::new();
+    let mut user_ids = Vec::new();
+    for i in 0..config.workers {
+        let username = format!("soak_{}_{}", i, &uuid::Uuid::new_v4().to_string()[0..8]);
+        let mut client = BordClient::new(BASE_URL);
+        let user = client.create_user(&username, "password123").await.expect("Failed to create soak user");
+        client.login(&username, "password123").await.expect("Failed to login soak user");
+        user_ids.push(user.id);
+        clients.push(client);
+    }
+
+    let deadline = Instant::now() + config.duration;
+    let handles: Vec<_> = clients
+        .into_iter()
+        .map(|client| {
+            let config = config.clone();
+            let user_ids = user_ids.clone();
+            tokio::spawn(async move { soak_worker(&config, client, user_ids, deadline).await })
+        })
+        .collect();
+
+    let mut stats = SoakStats::default();
+    for handle in handles {
+        stats.merge(handle.await.expect("Soak worker panicked"));
+    }
+
+    let total = stats.ok + stats.errors;
+    let error_rate = stats.errors as f64 / total.max(1) as f64;
+    stats.latencies_ms.sort_unstable();
+    let p50 = percentile(&stats.latencies_ms, 50.0);
+    let p95 = percentile(&stats.latencies_ms, 95.0);
+    let p99 = percentile(&stats.latencies_ms, 99.0);
+
+    println!("\n=== Results ===");
+    println!("Requests: {} ({} errors, {:.2}% error rate)", total, stats.errors, error_rate * 100.0);
+    println!("Throughput: {:.0} requests/sec", total as f64 / config.duration.as_secs_f64());
+    println!("Latency p50/p95/p99: {}/{}/{}ms", p50, p95, p99);
+
+    assert!(total > 0, "No requests completed");
+    assert!(
+        error_rate <= config.max_error_rate,
+        "Error rate {:.2}% above SLO of {:.2}%",
+        error_rate * 100.0,
+        config.max_error_rate * 100.0
+    );
+    assert!(p95 <= config.max_p95_ms, "p95 latency {}ms above SLO of {}ms", p95, config.max_p95_ms);
 }